@@ -0,0 +1,57 @@
+/// Hint about how the pages backing an allocation are going to be used.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Advice {
+    /// The pages are not going to be used soon, so the system can reclaim
+    /// them. On Linux, anonymous pages read back as zero afterwards.
+    DontNeed,
+    /// The pages are not going to be used soon, but the system can wait for
+    /// memory pressure before reclaiming them. Until then, the pages keep
+    /// their contents, so they are undefined until written again. Falls back
+    /// to `DontNeed` on the systems without `MADV_FREE`.
+    Free,
+    /// The pages are going to be used soon, so the system can read them
+    /// ahead.
+    WillNeed,
+    /// The pages are going to be accessed sequentially.
+    Sequential,
+    /// The pages are going to be accessed in random order.
+    Random,
+}
+
+impl Advice {
+    /// Returns the equivalent `MADV_*` constant for `madvise`.
+    pub(crate) fn to_madvise(self) -> libc::c_int {
+        match self {
+            Advice::DontNeed => libc::MADV_DONTNEED,
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "solaris",
+                target_os = "illumos"
+            ))]
+            Advice::Free => libc::MADV_FREE,
+            #[cfg(not(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "solaris",
+                target_os = "illumos"
+            )))]
+            Advice::Free => libc::MADV_DONTNEED,
+            Advice::WillNeed => libc::MADV_WILLNEED,
+            Advice::Sequential => libc::MADV_SEQUENTIAL,
+            Advice::Random => libc::MADV_RANDOM,
+        }
+    }
+}
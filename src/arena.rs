@@ -0,0 +1,165 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::Cell,
+    ptr::{self, NonNull},
+};
+
+use crate::MMapAllocator;
+
+/// Arena handing out sub-allocations of a single mapping.
+///
+/// Allocating bumps a pointer through the mapping, which is much cheaper
+/// than mapping a page per allocation. Deallocating does nothing: the whole
+/// mapping is only released when the arena is dropped. The most recent
+/// allocation can be grown or shrunk in place.
+#[derive(Debug)]
+pub struct BumpArena {
+    allocator: MMapAllocator,
+    region: NonNull<[u8]>,
+    layout: Layout,
+    /// Offset in the region of the first free byte.
+    offset: Cell<usize>,
+}
+
+impl BumpArena {
+    /// Creates an arena with room for at least `capacity` bytes, mapped with
+    /// regular anonymous pages.
+    pub fn new(capacity: usize) -> Result<Self, AllocError> {
+        Self::with_allocator(MMapAllocator::new(), capacity)
+    }
+
+    /// Creates an arena with room for at least `capacity` bytes, mapped with
+    /// `allocator`.
+    pub fn with_allocator(allocator: MMapAllocator, capacity: usize) -> Result<Self, AllocError> {
+        let layout = Layout::from_size_align(capacity, 1).map_err(|_| AllocError)?;
+        let region = allocator.allocate(layout)?;
+
+        Ok(Self {
+            allocator,
+            region,
+            layout,
+            offset: Cell::new(0),
+        })
+    }
+
+    /// Returns the number of bytes that the arena can hold.
+    pub fn capacity(&self) -> usize {
+        self.region.len()
+    }
+
+    /// Returns the number of bytes already handed out, including the
+    /// padding needed to align the allocations.
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    /// Returns the offset of `ptr` from the beginning of the region.
+    fn offset_of(&self, ptr: NonNull<u8>) -> usize {
+        ptr.as_ptr() as usize - self.region.as_mut_ptr() as usize
+    }
+
+    /// Returns whether `ptr`, allocated with `layout`, is the most recent
+    /// allocation, and thus ends at the first free byte.
+    fn is_last(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.offset_of(ptr) + layout.size() == self.offset.get()
+    }
+}
+
+unsafe impl Allocator for BumpArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let offset = self.offset.get();
+
+        let free_ptr = unsafe { self.region.as_mut_ptr().add(offset) };
+        let start = offset
+            .checked_add(free_ptr.align_offset(layout.align()))
+            .ok_or(AllocError)?;
+        let end = start
+            .checked_add(layout.size())
+            .filter(|end| *end <= self.capacity())
+            .ok_or(AllocError)?;
+
+        self.offset.set(end);
+
+        // SAFETY: `start` is within the region, which is never null.
+        let ptr = unsafe { NonNull::new_unchecked(self.region.as_mut_ptr().add(start)) };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+        // The memory is only released when the arena is dropped.
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        // The most recent allocation can be extended into the free space, if
+        // it is already aligned for the new layout.
+        if self.is_last(ptr, old_layout) && ptr.as_ptr().align_offset(new_layout.align()) == 0 {
+            let end = self
+                .offset_of(ptr)
+                .checked_add(new_layout.size())
+                .filter(|end| *end <= self.capacity())
+                .ok_or(AllocError)?;
+            self.offset.set(end);
+
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+
+        // SAFETY: both allocations are valid for `old_layout.size()` bytes and
+        // are distinct, since the arena never hands out the same memory twice.
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), old_layout.size());
+
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
+        );
+
+        if ptr.as_ptr().align_offset(new_layout.align()) != 0 {
+            let new_ptr = self.allocate(new_layout)?;
+
+            // SAFETY: same as in `grow`.
+            ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), new_layout.size());
+
+            return Ok(new_ptr);
+        }
+
+        // The tail of the most recent allocation can be given back.
+        if self.is_last(ptr, old_layout) {
+            self.offset.set(self.offset_of(ptr) + new_layout.size());
+        }
+
+        Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+    }
+}
+
+impl Drop for BumpArena {
+    fn drop(&mut self) {
+        unsafe {
+            self.allocator
+                .deallocate(self.region.as_non_null_ptr(), self.layout)
+        };
+    }
+}
+
+// The arena owns its mapping, which can be released from any thread. It is
+// not `Sync`, as the offset of the first free byte is not atomic.
+unsafe impl Send for BumpArena {}
@@ -0,0 +1,61 @@
+#[cfg(target_os = "linux")]
+use crate::HugePageSize;
+#[cfg(unix)]
+use crate::Source;
+use crate::{GenericMMapAllocator, MemoryMapper};
+
+/// Memory backing the allocations of an allocator, as reported by
+/// `MMapAllocator::backing`.
+///
+/// Only anonymous memory starts zeroed, so that `allocate_zeroed` does not
+/// need to clear it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Backing {
+    /// Fresh anonymous memory, private to the process.
+    Anonymous,
+    /// Fresh anonymous memory, shared with the child processes.
+    #[cfg(unix)]
+    SharedAnonymous,
+    /// Fresh anonymous huge pages of the given size, shared with the child
+    /// processes if `shared` is `true`.
+    #[cfg(target_os = "linux")]
+    HugePages { size: HugePageSize, shared: bool },
+    /// Consecutive regions of the file `fd`, whose writes are kept in private
+    /// copy-on-write pages if `private` is `true`.
+    #[cfg(unix)]
+    File { fd: libc::c_int, private: bool },
+    /// Consecutive regions of the file `fd` on persistent memory, mapped with
+    /// `MAP_SYNC`.
+    #[cfg(target_os = "linux")]
+    PersistentMemory { fd: libc::c_int },
+}
+
+impl<M: MemoryMapper> GenericMMapAllocator<M> {
+    /// Returns the memory backing the allocations.
+    pub fn backing(&self) -> Backing {
+        #[cfg(unix)]
+        match self.backing {
+            Source::Anonymous | Source::SharedAnonymous => {
+                let shared = matches!(self.backing, Source::SharedAnonymous);
+
+                #[cfg(target_os = "linux")]
+                if let Some(size) = self.huge_pages {
+                    return Backing::HugePages { size, shared };
+                }
+
+                if shared {
+                    Backing::SharedAnonymous
+                } else {
+                    Backing::Anonymous
+                }
+            }
+            #[cfg(target_os = "linux")]
+            Source::File { fd, sync: true, .. } => Backing::PersistentMemory { fd },
+            Source::File { fd, private, .. } => Backing::File { fd, private },
+        }
+
+        #[cfg(windows)]
+        Backing::Anonymous
+    }
+}
@@ -0,0 +1,256 @@
+#[cfg(unix)]
+use core::sync::atomic::AtomicU64;
+
+#[cfg(target_os = "linux")]
+use crate::HugePageSize;
+#[cfg(unix)]
+use crate::Source;
+use crate::{BuildError, MMapAllocator, Protection, Stats};
+
+/// Builder combining several options of an `MMapAllocator`.
+///
+/// Every option starts with the same value as in `MMapAllocator::new`. The
+/// options are only checked for compatibility with each other by `build`.
+#[derive(Debug)]
+pub struct MMapAllocatorBuilder {
+    allocator: MMapAllocator,
+}
+
+impl MMapAllocatorBuilder {
+    /// Creates a builder with the default options.
+    pub const fn new() -> Self {
+        Self {
+            allocator: MMapAllocator::new(),
+        }
+    }
+
+    /// Backs the allocations with huge pages of the given size, like
+    /// `MMapAllocator::with_huge_pages`.
+    #[cfg(target_os = "linux")]
+    pub fn huge_pages(mut self, size: HugePageSize) -> Self {
+        self.allocator.huge_pages = Some(size);
+        self
+    }
+
+    /// Surrounds every allocation with `count` guard pages on each side, like
+    /// `MMapAllocator::with_guard_pages`.
+    #[cfg(unix)]
+    pub fn guard_pages(mut self, count: usize) -> Self {
+        self.allocator.guard_pages = count;
+        self
+    }
+
+    /// Maps memory with the given protection, like
+    /// `MMapAllocator::with_protection`.
+    pub fn protection(mut self, protection: Protection) -> Self {
+        self.allocator.protection = protection;
+        self
+    }
+
+    /// Prefaults the pages of every allocation, like
+    /// `MMapAllocator::with_populate`.
+    #[cfg(unix)]
+    pub fn populate(mut self, populate: bool) -> Self {
+        self.allocator.populate = populate;
+        self
+    }
+
+    /// Does not reserve swap space for the allocations, like
+    /// `MMapAllocator::with_no_reserve`.
+    #[cfg(unix)]
+    pub fn no_reserve(mut self, no_reserve: bool) -> Self {
+        self.allocator.no_reserve = no_reserve;
+        self
+    }
+
+    /// Locks every allocation in memory, like `MMapAllocator::with_locked`.
+    #[cfg(unix)]
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.allocator.locked = locked;
+        self
+    }
+
+    /// Binds every allocation to the NUMA node `node`, like
+    /// `MMapAllocator::with_numa_node`.
+    #[cfg(target_os = "linux")]
+    pub fn numa_node(mut self, node: u32) -> Self {
+        self.allocator.numa_node = Some(node);
+        self
+    }
+
+    /// Asks for large allocations to be backed by transparent huge pages,
+    /// like `MMapAllocator::with_thp`.
+    #[cfg(target_os = "linux")]
+    pub fn thp(mut self, thp: bool) -> Self {
+        self.allocator.thp = thp;
+        self
+    }
+
+    /// Aligns large allocations to 2 MiB for transparent huge pages, like
+    /// `MMapAllocator::with_thp_alignment`.
+    #[cfg(target_os = "linux")]
+    pub fn thp_alignment(mut self, thp_alignment: bool) -> Self {
+        self.allocator.thp_alignment = thp_alignment;
+        self
+    }
+
+    /// Keeps every allocation out of the child processes, like
+    /// `MMapAllocator::with_dontfork`.
+    #[cfg(target_os = "linux")]
+    pub fn dontfork(mut self, dontfork: bool) -> Self {
+        self.allocator.dontfork = dontfork;
+        self
+    }
+
+    /// Names every anonymous allocation `name`, like
+    /// `MMapAllocator::with_name`.
+    #[cfg(target_os = "linux")]
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.allocator.name = Some(name);
+        self
+    }
+
+    /// Places every allocation in the low 2 GiB of the address space, like
+    /// `MMapAllocator::with_32bit`.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    pub fn map_32bit(mut self, map_32bit: bool) -> Self {
+        self.allocator.map_32bit = map_32bit;
+        self
+    }
+
+    /// Aligns every allocation to at least `min_align` bytes, like
+    /// `MMapAllocator::with_min_align`.
+    pub fn min_align(mut self, min_align: usize) -> Self {
+        self.allocator.min_align = min_align;
+        self
+    }
+
+    /// Overwrites memory with zeros before unmapping it, like
+    /// `MMapAllocator::with_zero_on_free`.
+    pub fn zero_on_free(mut self, zero_on_free: bool) -> Self {
+        self.allocator.zero_on_free = zero_on_free;
+        self
+    }
+
+    /// Overwrites memory with `byte` before unmapping it, like
+    /// `MMapAllocator::with_poison`.
+    pub fn poison(mut self, byte: u8) -> Self {
+        self.allocator.poison = Some(byte);
+        self
+    }
+
+    /// Rounds the allocations to pages of `page_size` bytes, like
+    /// `MMapAllocator::with_page_size_for_testing`.
+    #[cfg(all(unix, feature = "page-size-override"))]
+    pub fn page_size_for_testing(mut self, page_size: usize) -> Self {
+        self.allocator.page_size_override = Some(page_size);
+        self
+    }
+
+    /// Keeps track of the memory mapped by the allocator, like
+    /// `MMapAllocator::with_stats`.
+    pub fn stats(mut self, stats: bool) -> Self {
+        self.allocator.stats = if stats { Some(Stats::new()) } else { None };
+        self
+    }
+
+    /// Caches the freed single-page mappings in the freeing thread, like
+    /// `MMapAllocator::with_thread_cache`.
+    #[cfg(all(unix, feature = "std"))]
+    pub fn thread_cache(mut self, thread_cache: bool) -> Self {
+        self.allocator.thread_cache = thread_cache.then(crate::thread_cache::new_owner);
+        self
+    }
+
+    /// Shares the memory with the child processes, like
+    /// `MMapAllocator::shared`.
+    #[cfg(unix)]
+    pub fn shared(mut self) -> Self {
+        self.allocator.backing = Source::SharedAnonymous;
+        self
+    }
+
+    /// Maps consecutive regions of the file `fd`, starting at `offset`, like
+    /// `MMapAllocator::file_backed`.
+    #[cfg(unix)]
+    pub fn file_backed(mut self, fd: libc::c_int, offset: u64) -> Self {
+        self.allocator.backing = Source::File {
+            fd,
+            offset: AtomicU64::new(offset),
+            private: false,
+            #[cfg(target_os = "linux")]
+            sync: false,
+        };
+        self
+    }
+
+    /// Maps private copy-on-write regions of the file `fd`, starting at
+    /// `offset`, like `MMapAllocator::cow_file`.
+    #[cfg(unix)]
+    pub fn cow_file(mut self, fd: libc::c_int, offset: u64) -> Self {
+        self.allocator.backing = Source::File {
+            fd,
+            offset: AtomicU64::new(offset),
+            private: true,
+            #[cfg(target_os = "linux")]
+            sync: false,
+        };
+        self
+    }
+
+    /// Maps consecutive regions of the file `fd` on a persistent memory
+    /// device, starting at `offset`, like `MMapAllocator::pmem_file`.
+    #[cfg(target_os = "linux")]
+    pub fn pmem_file(mut self, fd: libc::c_int, offset: u64) -> Self {
+        self.allocator.backing = Source::File {
+            fd,
+            offset: AtomicU64::new(offset),
+            private: false,
+            sync: true,
+        };
+        self
+    }
+
+    /// Creates the allocator, checking that the options can be combined.
+    pub fn build(self) -> Result<MMapAllocator, BuildError> {
+        let allocator = self.allocator;
+
+        if !allocator.min_align.is_power_of_two() {
+            return Err(BuildError::MinAlignNotPowerOfTwo);
+        }
+
+        #[cfg(all(unix, feature = "page-size-override"))]
+        if let Some(page_size) = allocator.page_size_override {
+            if !page_size.is_power_of_two() {
+                return Err(BuildError::PageSizeNotPowerOfTwo);
+            }
+        }
+
+        if allocator.zero_on_free && !allocator.protection.contains(Protection::WRITE) {
+            return Err(BuildError::ZeroOnFreeNotWritable);
+        }
+
+        if allocator.poison.is_some() && !allocator.protection.contains(Protection::WRITE) {
+            return Err(BuildError::PoisonNotWritable);
+        }
+
+        #[cfg(target_os = "linux")]
+        if allocator.huge_pages.is_some() {
+            if allocator.guard_pages > 0 {
+                return Err(BuildError::GuardPagesWithHugePages);
+            }
+
+            if let Source::File { .. } = allocator.backing {
+                return Err(BuildError::HugePagesWithFile);
+            }
+        }
+
+        Ok(allocator)
+    }
+}
+
+impl Default for MMapAllocatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
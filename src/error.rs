@@ -0,0 +1,123 @@
+use core::{alloc::AllocError, fmt};
+
+/// Error describing why the allocator failed to create or release a mapping.
+///
+/// The `errno` values are the ones reported by the system right after the
+/// failed call. On Windows, they are the values returned by `GetLastError`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum MMapError {
+    /// The requested alignment cannot be satisfied by the allocator.
+    AlignmentTooLarge,
+    /// The size of the allocation overflows once rounded to whole pages.
+    SizeOverflow,
+    /// The page size of the allocator is not a power of two, so allocations
+    /// cannot be rounded to whole pages.
+    PageSizeNotPowerOfTwo,
+    /// The pages retained by a shrink would outnumber the pages of the
+    /// memory block being shrunk.
+    ShrinkWouldGrow,
+    /// The file backing the allocator is too small to hold the next
+    /// allocation.
+    FileTooSmall,
+    /// `mmap` failed with the contained `errno`.
+    MmapFailed(i32),
+    /// `munmap` failed with the contained `errno`.
+    MunmapFailed(i32),
+    /// `mremap` failed with the contained `errno`.
+    MremapFailed(i32),
+    /// `mprotect` failed with the contained `errno`.
+    MprotectFailed(i32),
+    /// `mlock` failed with the contained `errno`.
+    MlockFailed(i32),
+    /// `madvise` failed with the contained `errno`.
+    MadviseFailed(i32),
+    /// `msync` failed with the contained `errno`.
+    MsyncFailed(i32),
+    /// `fstat` failed with the contained `errno`.
+    FstatFailed(i32),
+    /// `mbind` failed with the contained `errno`.
+    MbindFailed(i32),
+    /// `mincore` failed with the contained `errno`.
+    MincoreFailed(i32),
+    /// The options of the allocator cannot be combined.
+    InvalidOptions(BuildError),
+}
+
+impl fmt::Display for MMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MMapError::AlignmentTooLarge => f.write_str("alignment too large"),
+            MMapError::SizeOverflow => f.write_str("allocation size overflow"),
+            MMapError::PageSizeNotPowerOfTwo => f.write_str("page size is not a power of two"),
+            MMapError::ShrinkWouldGrow => f.write_str("shrink would grow the allocation"),
+            MMapError::FileTooSmall => f.write_str("file too small"),
+            MMapError::MmapFailed(errno) => write!(f, "mmap failed (errno={})", errno),
+            MMapError::MunmapFailed(errno) => write!(f, "munmap failed (errno={})", errno),
+            MMapError::MremapFailed(errno) => write!(f, "mremap failed (errno={})", errno),
+            MMapError::MprotectFailed(errno) => write!(f, "mprotect failed (errno={})", errno),
+            MMapError::MlockFailed(errno) => write!(f, "mlock failed (errno={})", errno),
+            MMapError::MadviseFailed(errno) => write!(f, "madvise failed (errno={})", errno),
+            MMapError::MsyncFailed(errno) => write!(f, "msync failed (errno={})", errno),
+            MMapError::FstatFailed(errno) => write!(f, "fstat failed (errno={})", errno),
+            MMapError::MbindFailed(errno) => write!(f, "mbind failed (errno={})", errno),
+            MMapError::MincoreFailed(errno) => write!(f, "mincore failed (errno={})", errno),
+            MMapError::InvalidOptions(err) => write!(f, "invalid options: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MMapError {}
+
+/// Error describing why the options given to an `MMapAllocatorBuilder` cannot
+/// be combined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The minimum alignment is not a power of two.
+    MinAlignNotPowerOfTwo,
+    /// The overridden page size is not a power of two.
+    PageSizeNotPowerOfTwo,
+    /// Memory cannot be zeroed when freed if it is not writable.
+    ZeroOnFreeNotWritable,
+    /// Memory cannot be poisoned when freed if it is not writable.
+    PoisonNotWritable,
+    /// Guard pages cannot be used with huge pages, as each of them would take
+    /// a whole huge page.
+    GuardPagesWithHugePages,
+    /// Huge pages can only back anonymous mappings.
+    HugePagesWithFile,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MinAlignNotPowerOfTwo => {
+                f.write_str("minimum alignment is not a power of two")
+            }
+            BuildError::PageSizeNotPowerOfTwo => f.write_str("page size is not a power of two"),
+            BuildError::ZeroOnFreeNotWritable => {
+                f.write_str("zeroing on free requires writable memory")
+            }
+            BuildError::PoisonNotWritable => {
+                f.write_str("poisoning on free requires writable memory")
+            }
+            BuildError::GuardPagesWithHugePages => {
+                f.write_str("guard pages cannot be used with huge pages")
+            }
+            BuildError::HugePagesWithFile => {
+                f.write_str("huge pages cannot be used with file mappings")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+impl From<MMapError> for AllocError {
+    fn from(_: MMapError) -> Self {
+        AllocError
+    }
+}
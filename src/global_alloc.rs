@@ -0,0 +1,44 @@
+use core::{
+    alloc::{Allocator, GlobalAlloc, Layout},
+    ptr::{self, NonNull},
+};
+
+use crate::MMapAllocator;
+
+/// Allows using the allocator as `#[global_allocator]`.
+///
+/// Every allocation still gets its own mapping, so this is only suitable for
+/// programs making few, large allocations.
+unsafe impl GlobalAlloc for MMapAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocate(layout)
+            .map_or(ptr::null_mut(), |ptr| ptr.as_mut_ptr())
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.allocate_zeroed(layout)
+            .map_or(ptr::null_mut(), |ptr| ptr.as_mut_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: `ptr` was returned by `alloc`, so it is not null.
+        self.deallocate(NonNull::new_unchecked(ptr), layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // SAFETY: `ptr` was returned by `alloc`, so it is not null. The new
+        // size is guaranteed not to overflow once rounded to the alignment.
+        let ptr = NonNull::new_unchecked(ptr);
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+        let res = if new_size > layout.size() {
+            self.grow(ptr, layout, new_layout)
+        } else if new_size < layout.size() {
+            self.shrink(ptr, layout, new_layout)
+        } else {
+            return ptr.as_ptr();
+        };
+
+        res.map_or(ptr::null_mut(), |ptr| ptr.as_mut_ptr())
+    }
+}
@@ -0,0 +1,112 @@
+//! Hooks intercepting the system calls made by the allocator.
+//!
+//! This module is only available with the `syscall-hooks` feature, and is
+//! meant for tests that need to observe or simulate the behavior of the
+//! system calls. Hooks are installed per thread, so they only affect the
+//! allocations made by the thread that installed them.
+//!
+//! Every call to `mmap`, `munmap` and `mremap` is also counted per thread,
+//! whether it is intercepted or not, so that tests can check which system
+//! calls an operation needed.
+
+use core::{cell::Cell, ffi::c_void};
+
+/// Hook called in place of `mmap`, with the same arguments and return value.
+pub type MmapHook = unsafe fn(
+    addr: *mut c_void,
+    len: usize,
+    prot: libc::c_int,
+    flags: libc::c_int,
+    fd: libc::c_int,
+    offset: libc::off_t,
+) -> *mut c_void;
+
+/// Hook called in place of `munmap`, with the same arguments and return
+/// value.
+pub type MunmapHook = unsafe fn(addr: *mut c_void, len: usize) -> libc::c_int;
+
+/// Hook called in place of `mremap`, with the same arguments and return
+/// value.
+#[cfg(target_os = "linux")]
+pub type MremapHook =
+    unsafe fn(addr: *mut c_void, old_len: usize, new_len: usize, flags: libc::c_int) -> *mut c_void;
+
+/// Number of system calls made by the current thread.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct SyscallCounts {
+    /// Number of calls to `mmap`.
+    pub mmap: usize,
+    /// Number of calls to `munmap`.
+    pub munmap: usize,
+    /// Number of calls to `mremap`.
+    pub mremap: usize,
+}
+
+std::thread_local! {
+    static MMAP_HOOK: Cell<Option<MmapHook>> = const { Cell::new(None) };
+    static MUNMAP_HOOK: Cell<Option<MunmapHook>> = const { Cell::new(None) };
+    #[cfg(target_os = "linux")]
+    static MREMAP_HOOK: Cell<Option<MremapHook>> = const { Cell::new(None) };
+    static SYSCALL_COUNTS: Cell<SyscallCounts> = const {
+        Cell::new(SyscallCounts {
+            mmap: 0,
+            munmap: 0,
+            mremap: 0,
+        })
+    };
+}
+
+/// Installs `hook` in place of `mmap` for the current thread, or removes the
+/// current hook if `hook` is `None`.
+pub fn set_mmap_hook(hook: Option<MmapHook>) {
+    MMAP_HOOK.with(|current| current.set(hook));
+}
+
+/// Installs `hook` in place of `munmap` for the current thread, or removes
+/// the current hook if `hook` is `None`.
+pub fn set_munmap_hook(hook: Option<MunmapHook>) {
+    MUNMAP_HOOK.with(|current| current.set(hook));
+}
+
+/// Installs `hook` in place of `mremap` for the current thread, or removes
+/// the current hook if `hook` is `None`.
+#[cfg(target_os = "linux")]
+pub fn set_mremap_hook(hook: Option<MremapHook>) {
+    MREMAP_HOOK.with(|current| current.set(hook));
+}
+
+/// Returns the number of system calls made by the current thread since the
+/// last call to `reset_syscall_counts`.
+pub fn syscall_counts() -> SyscallCounts {
+    SYSCALL_COUNTS.with(Cell::get)
+}
+
+/// Resets the system call counters of the current thread.
+pub fn reset_syscall_counts() {
+    SYSCALL_COUNTS.with(|counts| counts.set(SyscallCounts::default()));
+}
+
+/// Returns the hook installed in place of `mmap` for the current thread.
+pub(crate) fn mmap_hook() -> Option<MmapHook> {
+    MMAP_HOOK.try_with(Cell::get).ok().flatten()
+}
+
+/// Returns the hook installed in place of `munmap` for the current thread.
+pub(crate) fn munmap_hook() -> Option<MunmapHook> {
+    MUNMAP_HOOK.try_with(Cell::get).ok().flatten()
+}
+
+/// Returns the hook installed in place of `mremap` for the current thread.
+#[cfg(target_os = "linux")]
+pub(crate) fn mremap_hook() -> Option<MremapHook> {
+    MREMAP_HOOK.try_with(Cell::get).ok().flatten()
+}
+
+/// Updates the system call counters of the current thread with `f`.
+pub(crate) fn count(f: impl FnOnce(&mut SyscallCounts)) {
+    let _ = SYSCALL_COUNTS.try_with(|counts| {
+        let mut current = counts.get();
+        f(&mut current);
+        counts.set(current);
+    });
+}
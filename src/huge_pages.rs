@@ -0,0 +1,95 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Size of the huge pages backing an allocation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HugePageSize {
+    /// The default huge page size of the system, as reported by
+    /// `/proc/meminfo`.
+    Default,
+    /// 2 MiB huge pages.
+    Size2MiB,
+    /// 1 GiB huge pages.
+    Size1GiB,
+}
+
+/// Fallback used when the default huge page size cannot be determined.
+const FALLBACK_DEFAULT_SIZE: usize = 2 << 20;
+
+impl HugePageSize {
+    /// Returns the size in bytes of a huge page.
+    pub fn size(self) -> usize {
+        match self {
+            HugePageSize::Default => default_size(),
+            HugePageSize::Size2MiB => 2 << 20,
+            HugePageSize::Size1GiB => 1 << 30,
+        }
+    }
+
+    /// Returns the flags needed to request huge pages of this size from
+    /// `mmap`.
+    pub(crate) fn map_flags(self) -> libc::c_int {
+        libc::MAP_HUGETLB
+            | match self {
+                HugePageSize::Default => 0,
+                HugePageSize::Size2MiB => libc::MAP_HUGE_2MB,
+                HugePageSize::Size1GiB => libc::MAP_HUGE_1GB,
+            }
+    }
+}
+
+/// Returns the default huge page size, reading it from `/proc/meminfo` the
+/// first time it is needed.
+fn default_size() -> usize {
+    static DEFAULT_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+    let size = DEFAULT_SIZE.load(Ordering::Relaxed);
+    if size != 0 {
+        return size;
+    }
+
+    let size = read_default_size().unwrap_or(FALLBACK_DEFAULT_SIZE);
+    DEFAULT_SIZE.store(size, Ordering::Relaxed);
+    size
+}
+
+/// Parses the `Hugepagesize:` entry of `/proc/meminfo`.
+fn read_default_size() -> Option<usize> {
+    const KEY: &[u8] = b"Hugepagesize:";
+
+    let mut buf = [0u8; 4096];
+    let mut len = 0;
+
+    unsafe {
+        let fd = libc::open(
+            b"/proc/meminfo\0".as_ptr().cast(),
+            libc::O_RDONLY | libc::O_CLOEXEC,
+        );
+        if fd == -1 {
+            return None;
+        }
+
+        while len < buf.len() {
+            let res = libc::read(fd, buf[len..].as_mut_ptr().cast(), buf.len() - len);
+            if res <= 0 {
+                break;
+            }
+            len += res as usize;
+        }
+
+        libc::close(fd);
+    }
+
+    let contents = &buf[..len];
+    let start = contents.windows(KEY.len()).position(|w| w == KEY)? + KEY.len();
+
+    // The entry is formatted as `Hugepagesize:    2048 kB`.
+    let kibibytes = contents[start..]
+        .iter()
+        .skip_while(|c| c.is_ascii_whitespace())
+        .take_while(|c| c.is_ascii_digit())
+        .try_fold(0usize, |acc, c| {
+            acc.checked_mul(10)?.checked_add(usize::from(c - b'0'))
+        })?;
+
+    kibibytes.checked_mul(1024).filter(|size| *size != 0)
+}
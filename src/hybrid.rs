@@ -0,0 +1,140 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ptr::{self, NonNull},
+};
+use std::alloc::Global;
+
+use crate::MMapAllocator;
+
+/// Allocator serving small allocations from the global allocator, and the
+/// others with an `MMapAllocator`.
+///
+/// Allocations smaller than the threshold would otherwise waste most of the
+/// page they are mapped to. The backend of an allocation only depends on the
+/// size of its layout, so the memory is always returned to the backend it
+/// came from. This requires the `std` feature.
+#[derive(Debug)]
+pub struct HybridAllocator {
+    mmap: MMapAllocator,
+    threshold: usize,
+}
+
+impl HybridAllocator {
+    /// Creates an allocator mapping allocations of at least `threshold`
+    /// bytes with regular anonymous pages.
+    pub const fn new(threshold: usize) -> Self {
+        Self::with_allocator(MMapAllocator::new(), threshold)
+    }
+
+    /// Creates an allocator mapping allocations of at least `threshold`
+    /// bytes with `mmap`.
+    pub const fn with_allocator(mmap: MMapAllocator, threshold: usize) -> Self {
+        Self { mmap, threshold }
+    }
+
+    /// Returns the allocator used for allocations of at least `threshold`
+    /// bytes.
+    pub fn mmap_allocator(&self) -> &MMapAllocator {
+        &self.mmap
+    }
+
+    /// Returns whether an allocation of `layout` is mapped with `mmap`.
+    fn is_mapped(&self, layout: Layout) -> bool {
+        layout.size() >= self.threshold
+    }
+
+    /// Moves an allocation between the two backends, zeroing the new memory
+    /// if `zeroed` is `true`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::grow` or `Allocator::shrink`, and the old and new
+    /// layouts must belong to different backends.
+    unsafe fn relocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+        zeroed: bool,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = if zeroed {
+            self.allocate_zeroed(new_layout)?
+        } else {
+            self.allocate(new_layout)?
+        };
+
+        // SAFETY: both allocations are valid for reads and writes of the
+        // smallest of the two sizes, and they are distinct, as the old one
+        // was not yet deallocated.
+        let size = old_layout.size().min(new_layout.size());
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), size);
+        self.deallocate(ptr, old_layout);
+
+        Ok(new_ptr)
+    }
+}
+
+unsafe impl Allocator for HybridAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if self.is_mapped(layout) {
+            self.mmap.allocate(layout)
+        } else {
+            Global.allocate(layout)
+        }
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if self.is_mapped(layout) {
+            self.mmap.allocate_zeroed(layout)
+        } else {
+            Global.allocate_zeroed(layout)
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if self.is_mapped(layout) {
+            self.mmap.deallocate(ptr, layout)
+        } else {
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (self.is_mapped(old_layout), self.is_mapped(new_layout)) {
+            (true, true) => self.mmap.grow(ptr, old_layout, new_layout),
+            (false, false) => Global.grow(ptr, old_layout, new_layout),
+            _ => self.relocate(ptr, old_layout, new_layout, false),
+        }
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (self.is_mapped(old_layout), self.is_mapped(new_layout)) {
+            (true, true) => self.mmap.grow_zeroed(ptr, old_layout, new_layout),
+            (false, false) => Global.grow_zeroed(ptr, old_layout, new_layout),
+            _ => self.relocate(ptr, old_layout, new_layout, true),
+        }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (self.is_mapped(old_layout), self.is_mapped(new_layout)) {
+            (true, true) => self.mmap.shrink(ptr, old_layout, new_layout),
+            (false, false) => Global.shrink(ptr, old_layout, new_layout),
+            _ => self.relocate(ptr, old_layout, new_layout, false),
+        }
+    }
+}
@@ -1,68 +1,1129 @@
 #![feature(allocator_api)]
-#![feature(nonnull_slice_from_raw_parts)]
 #![feature(slice_ptr_get)]
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(all(unix, feature = "std"))]
+use core::num::NonZeroUsize;
 use core::{
     alloc::{AllocError, Allocator, Layout},
-    ffi::c_void,
+    mem,
     ptr::{self, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
 };
+#[cfg(unix)]
+use core::{convert::TryFrom, ffi::c_void, sync::atomic::AtomicU64};
 
-#[derive(Clone, Copy, Default, Debug)]
-pub struct MMapAllocator;
+/// Prints a one-line trace of an operation of the allocator to `stderr`, if
+/// the `trace` feature is enabled and `MMAP_ALLOC_TRACE` is set.
+///
+/// The arguments are not evaluated when the feature is disabled.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        if crate::trace::enabled() {
+            std::eprintln!("mmap-allocator: {}", format_args!($($arg)*));
+        }
+    };
+}
 
-unsafe impl Allocator for MMapAllocator {
-    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
-        if layout.align() > page_size::get() {
-            // `mmap` can only map memory page-aligned.
-            return Err(AllocError);
+#[cfg(unix)]
+mod advice;
+mod arena;
+mod backing;
+mod builder;
+mod error;
+mod global_alloc;
+#[cfg(all(unix, feature = "syscall-hooks"))]
+pub mod hooks;
+#[cfg(target_os = "linux")]
+mod huge_pages;
+#[cfg(feature = "std")]
+mod hybrid;
+mod mapper;
+mod mapping;
+#[cfg(feature = "std")]
+mod pool;
+mod protection;
+#[cfg(unix)]
+mod reservation;
+#[cfg(feature = "std")]
+mod slab;
+#[cfg(unix)]
+mod stack;
+mod stats;
+#[cfg(unix)]
+mod sys;
+#[cfg(feature = "tags")]
+mod tags;
+#[cfg(all(unix, feature = "std"))]
+mod thread_cache;
+#[cfg(feature = "trace")]
+mod trace;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(unix)]
+pub use advice::Advice;
+pub use arena::BumpArena;
+pub use backing::Backing;
+pub use builder::MMapAllocatorBuilder;
+pub use error::{BuildError, MMapError};
+#[cfg(target_os = "linux")]
+pub use huge_pages::HugePageSize;
+#[cfg(feature = "std")]
+pub use hybrid::HybridAllocator;
+pub use mapper::{MemoryMapper, SystemMapper};
+pub use mapping::Mapping;
+#[cfg(feature = "std")]
+pub use pool::PagePool;
+pub use protection::Protection;
+#[cfg(unix)]
+pub use reservation::{GrowableRegion, Reservation, ReservedAllocator};
+#[cfg(feature = "std")]
+pub use slab::SlabAllocator;
+#[cfg(target_os = "linux")]
+pub use stack::GrowsDownRegion;
+#[cfg(unix)]
+pub use stack::Stack;
+#[cfg(feature = "tags")]
+pub use tags::{dump_live_tagged, TaggedAllocation};
+
+use stats::Stats;
+
+/// Allocator mapping every allocation to its own set of pages.
+///
+/// The mappings are made through the `MemoryMapper` `M`, which issues the
+/// system calls for `MMapAllocator`.
+///
+/// A configured allocator can be shared by several collections by passing it
+/// by reference, as `&MMapAllocator` implements `Allocator` as well.
+///
+/// The allocator is `Send` and `Sync`, so it can also be shared across
+/// threads, for example through an `Arc`. Its only mutable state, the offset
+/// of the next mapping of a file and the statistics, is kept in atomics. The
+/// standard library implements `Allocator` for `Arc<A>` whenever `A`
+/// implements it, so collections can also own a cloned `Arc<MMapAllocator>`,
+/// which keeps the allocator alive as long as any of them does.
+#[derive(Debug)]
+pub struct GenericMMapAllocator<M = SystemMapper> {
+    #[cfg(unix)]
+    backing: Source,
+    #[cfg(target_os = "linux")]
+    huge_pages: Option<HugePageSize>,
+    #[cfg(unix)]
+    guard_pages: usize,
+    protection: Protection,
+    #[cfg(unix)]
+    populate: bool,
+    #[cfg(unix)]
+    no_reserve: bool,
+    #[cfg(unix)]
+    locked: bool,
+    #[cfg(target_os = "linux")]
+    numa_node: Option<u32>,
+    #[cfg(target_os = "linux")]
+    thp: bool,
+    #[cfg(target_os = "linux")]
+    thp_alignment: bool,
+    #[cfg(target_os = "linux")]
+    dontfork: bool,
+    #[cfg(target_os = "linux")]
+    name: Option<&'static str>,
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    map_32bit: bool,
+    min_align: usize,
+    zero_on_free: bool,
+    poison: Option<u8>,
+    #[cfg(all(unix, feature = "page-size-override"))]
+    page_size_override: Option<usize>,
+    stats: Option<Stats>,
+    #[cfg(all(unix, feature = "std"))]
+    thread_cache: Option<NonZeroUsize>,
+    mapper: M,
+}
+
+/// Allocator mapping every allocation to its own set of pages with the
+/// system calls.
+pub type MMapAllocator = GenericMMapAllocator<SystemMapper>;
+
+/// Maps anonymous memory instead of a file.
+///
+/// Every `libc` for Linux, Android and Emscripten provides `MAP_ANONYMOUS`,
+/// while musl only provides `MAP_ANON` for some architectures. The other
+/// systems all provide the historical BSD spelling `MAP_ANON`.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "emscripten"))]
+const ANON_FLAG: libc::c_int = libc::MAP_ANONYMOUS;
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "android", target_os = "emscripten"))
+))]
+const ANON_FLAG: libc::c_int = libc::MAP_ANON;
+
+/// Prefaults the pages of a new mapping.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const MAP_POPULATE: libc::c_int = libc::MAP_POPULATE;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const MAP_POPULATE: libc::c_int = 0;
+
+/// Maps memory without reserving swap space for it.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const MAP_NORESERVE: libc::c_int = libc::MAP_NORESERVE;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const MAP_NORESERVE: libc::c_int = 0;
+
+/// Places a mapping exactly at the requested address, failing if it is
+/// already in use.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const MAP_FIXED_NOREPLACE: libc::c_int = libc::MAP_FIXED_NOREPLACE;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const MAP_FIXED_NOREPLACE: libc::c_int = 0;
+
+/// Marks a mapping as being used as a thread stack.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "openbsd"
+))]
+const MAP_STACK: libc::c_int = libc::MAP_STACK;
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "openbsd"
+    ))
+))]
+const MAP_STACK: libc::c_int = 0;
+
+/// Memory policy restricting allocations to the nodes of a node mask.
+#[cfg(target_os = "linux")]
+const MPOL_BIND: libc::c_int = 2;
+/// Number of NUMA nodes that an allocation can be bound to.
+#[cfg(target_os = "linux")]
+const MAX_NUMA_NODES: usize = 1024;
+
+/// Size of the smallest allocations hinted to use transparent huge pages,
+/// which is the size of a huge page on most architectures.
+#[cfg(target_os = "linux")]
+const THP_THRESHOLD: usize = 2 * 1024 * 1024;
+
+/// Collapses pages into transparent huge pages synchronously, since Linux
+/// 6.1. Not every `libc` target provides it, but its value is the same on all
+/// architectures.
+#[cfg(target_os = "linux")]
+const MADV_COLLAPSE: libc::c_int = 25;
+
+/// Maximum length of the name of a mapping, including the NUL terminator.
+#[cfg(target_os = "linux")]
+const MAX_VMA_NAME_LEN: usize = 80;
+
+/// Memory backing the mappings created by the allocator.
+#[cfg(unix)]
+#[derive(Debug)]
+enum Source {
+    /// Fresh anonymous memory.
+    Anonymous,
+    /// Fresh anonymous memory, shared with the child processes.
+    SharedAnonymous,
+    /// Consecutive regions of a file, shared with every other mapping of it
+    /// unless `private` is `true`.
+    File {
+        fd: libc::c_int,
+        /// Offset in the file of the next mapping.
+        offset: AtomicU64,
+        /// Whether writes are kept in private copy-on-write pages instead of
+        /// reaching the file.
+        private: bool,
+        /// Whether the file is on persistent memory and mapped with
+        /// `MAP_SYNC`.
+        #[cfg(target_os = "linux")]
+        sync: bool,
+    },
+}
+
+/// Outcome of resizing a memory block with `MMapAllocator::resize`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Resize {
+    /// The resized memory block.
+    pub ptr: NonNull<[u8]>,
+    /// Whether the memory block no longer starts at the same address, so
+    /// that any pointer into the old memory block must be updated.
+    pub moved: bool,
+}
+
+impl MMapAllocator {
+    /// Returns a builder to create an allocator combining several options.
+    pub const fn builder() -> MMapAllocatorBuilder {
+        MMapAllocatorBuilder::new()
+    }
+
+    /// Creates the allocator configured by `builder`, checking up front that
+    /// the system can satisfy its options.
+    ///
+    /// On top of the checks made by `MMapAllocatorBuilder::build`, whose
+    /// failures are reported as `MMapError::InvalidOptions`, a single page is
+    /// mapped with every configured option and unmapped right away. This
+    /// reports the failures that would otherwise only surface at the first
+    /// allocation, such as `MmapFailed(ENOMEM)` when no huge pages are
+    /// reserved, `MbindFailed` when the NUMA node does not exist, or
+    /// `MlockFailed` when `RLIMIT_MEMLOCK` is too low. File mappings are probed
+    /// at their starting offset, which is then used again by the first
+    /// allocation.
+    pub fn try_new(builder: MMapAllocatorBuilder) -> Result<Self, MMapError> {
+        let allocator = builder.build().map_err(MMapError::InvalidOptions)?;
+
+        #[cfg(unix)]
+        let offset = match &allocator.backing {
+            Source::File { offset, .. } => offset.load(Ordering::Relaxed),
+            _ => 0,
+        };
+
+        let layout = Layout::new::<u8>();
+        let probe = allocator.try_allocate(layout)?;
+        unsafe { allocator.try_deallocate(probe.as_non_null_ptr(), layout)? };
+
+        // The allocator is not shared yet, so no other mapping took the
+        // region of the file after the probe.
+        #[cfg(unix)]
+        if let Source::File { offset: next, .. } = &allocator.backing {
+            next.store(offset, Ordering::Relaxed);
         }
 
-        let layout = layout.align_to(page_size::get()).map_err(|_| AllocError)?;
+        Ok(allocator)
+    }
 
-        let new_mapping = unsafe {
-            libc::mmap(
-                ptr::null_mut(),
-                layout.size(),
-                libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_PRIVATE | libc::MAP_ANON,
-                -1,
-                0,
-            )
+    /// Creates an allocator backed by regular anonymous pages.
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(unix)]
+            backing: Source::Anonymous,
+            #[cfg(target_os = "linux")]
+            huge_pages: None,
+            #[cfg(unix)]
+            guard_pages: 0,
+            protection: Protection::READ.union(Protection::WRITE),
+            #[cfg(unix)]
+            populate: false,
+            #[cfg(unix)]
+            no_reserve: false,
+            #[cfg(unix)]
+            locked: false,
+            #[cfg(target_os = "linux")]
+            numa_node: None,
+            #[cfg(target_os = "linux")]
+            thp: false,
+            #[cfg(target_os = "linux")]
+            thp_alignment: false,
+            #[cfg(target_os = "linux")]
+            dontfork: false,
+            #[cfg(target_os = "linux")]
+            name: None,
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            map_32bit: false,
+            min_align: 1,
+            zero_on_free: false,
+            poison: None,
+            #[cfg(all(unix, feature = "page-size-override"))]
+            page_size_override: None,
+            stats: None,
+            #[cfg(all(unix, feature = "std"))]
+            thread_cache: None,
+            mapper: SystemMapper,
+        }
+    }
+
+    /// Creates an allocator backed by huge pages of the given size.
+    ///
+    /// Allocations are rounded to a multiple of the huge page size. If the
+    /// system has no huge pages available, allocating fails with
+    /// `AllocError`.
+    #[cfg(target_os = "linux")]
+    pub const fn with_huge_pages(size: HugePageSize) -> Self {
+        Self {
+            huge_pages: Some(size),
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that surrounds every allocation with `count`
+    /// inaccessible guard pages on each side.
+    ///
+    /// Accessing memory right before or after an allocation then faults
+    /// immediately, which helps catching buffer overflows.
+    #[cfg(unix)]
+    pub const fn with_guard_pages(count: usize) -> Self {
+        Self {
+            guard_pages: count,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that maps memory with the given protection.
+    ///
+    /// This can be used, for example, to allocate executable memory with
+    /// `READ | WRITE | EXEC`. Platforms enforcing W^X policies may refuse such
+    /// mappings, in which case allocating fails with `AllocError`.
+    pub const fn with_protection(protection: Protection) -> Self {
+        Self {
+            protection,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that prefaults the pages of every allocation when
+    /// it is mapped, if `populate` is `true`.
+    ///
+    /// This avoids paying for page faults on first access, at the cost of
+    /// slower allocations. Prefaulting is best-effort: if it fails, the
+    /// allocation still succeeds. On platforms without `MAP_POPULATE`, this
+    /// option is ignored.
+    #[cfg(unix)]
+    pub const fn with_populate(populate: bool) -> Self {
+        Self {
+            populate,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that does not reserve swap space for its
+    /// allocations, if `no_reserve` is `true`.
+    ///
+    /// This allows mapping sparse allocations much larger than the available
+    /// memory, as long as most of their pages are never touched. If the
+    /// system runs out of memory when a page is first written, the process
+    /// is killed instead of allocating failing. On platforms without
+    /// `MAP_NORESERVE`, this option is ignored.
+    #[cfg(unix)]
+    pub const fn with_no_reserve(no_reserve: bool) -> Self {
+        Self {
+            no_reserve,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that locks every allocation in memory with
+    /// `mlock`, if `locked` is `true`.
+    ///
+    /// Locked memory is never swapped out, which makes it suitable for
+    /// secrets. If the memory cannot be locked, for example because
+    /// `RLIMIT_MEMLOCK` would be exceeded, allocating fails with `AllocError`.
+    /// Memory is unlocked implicitly when it is unmapped.
+    #[cfg(unix)]
+    pub const fn with_locked(locked: bool) -> Self {
+        Self {
+            locked,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that binds every allocation to the NUMA node
+    /// `node` with `mbind`.
+    ///
+    /// The pages of an allocation are then only ever taken from that node.
+    /// If the allocation cannot be bound, for example because the node does
+    /// not exist, allocating fails with `AllocError`.
+    #[cfg(target_os = "linux")]
+    pub const fn with_numa_node(node: u32) -> Self {
+        Self {
+            numa_node: Some(node),
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that asks for large allocations to be backed by
+    /// transparent huge pages, if `thp` is `true`.
+    ///
+    /// Allocations of at least 2 MiB are advised with `MADV_HUGEPAGE` after
+    /// being mapped. Unlike `with_huge_pages`, this needs no huge pages to be
+    /// reserved, and the kernel is free to ignore the hint: allocating never
+    /// fails because of it.
+    #[cfg(target_os = "linux")]
+    pub const fn with_thp(thp: bool) -> Self {
+        Self { thp, ..Self::new() }
+    }
+
+    /// Creates an allocator that aligns large allocations to 2 MiB, so that
+    /// they can be backed by transparent huge pages, if `thp_alignment` is
+    /// `true`.
+    ///
+    /// Allocations of at least 2 MiB are over-aligned to 2 MiB, whatever
+    /// their layout, and advised with `MADV_HUGEPAGE` like with `with_thp`.
+    /// The kernel can only back the huge page ranges of a mapping that are
+    /// aligned to their size with transparent huge pages, so this makes the
+    /// hint effective for most of the allocation. The alignment is only
+    /// guaranteed when allocating: growing an allocation can move it to a
+    /// mapping that is only page-aligned.
+    #[cfg(target_os = "linux")]
+    pub const fn with_thp_alignment(thp_alignment: bool) -> Self {
+        Self {
+            thp_alignment,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator whose memory is not inherited by the child
+    /// processes, if `dontfork` is `true`.
+    ///
+    /// Every mapping is advised with `MADV_DONTFORK` right after being
+    /// mapped, so that it is absent from the address space of the processes
+    /// forked afterwards. This keeps sensitive buffers out of the children,
+    /// which fault if they access them.
+    #[cfg(target_os = "linux")]
+    pub const fn with_dontfork(dontfork: bool) -> Self {
+        Self {
+            dontfork,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that names every anonymous allocation `name`.
+    ///
+    /// The name shows up as `[anon:name]` in `/proc/self/maps`, which helps
+    /// telling the memory used by the allocator apart. Names longer than 79
+    /// bytes are truncated. Naming mappings needs Linux 5.17 or newer. If it
+    /// fails, for example on older kernels or because `name` contains
+    /// characters not allowed by the kernel, allocating still succeeds.
+    #[cfg(target_os = "linux")]
+    pub const fn with_name(name: &'static str) -> Self {
+        Self {
+            name: Some(name),
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that places every allocation in the low 2 GiB of
+    /// the address space with `MAP_32BIT`, if `map_32bit` is `true`.
+    ///
+    /// This is needed by code that refers to the allocations with 32-bit
+    /// addresses or offsets, such as some JIT compilers. Growing never moves
+    /// an allocation with `mremap`, which cannot keep it in that range. If no
+    /// room is left there, allocating fails with `AllocError`. Only
+    /// `allocate_fixed` may place an allocation elsewhere, at the address it
+    /// is given.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    pub const fn with_32bit(map_32bit: bool) -> Self {
+        Self {
+            map_32bit,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that aligns every allocation to at least
+    /// `min_align` bytes.
+    ///
+    /// Layouts with a smaller alignment are handled as if they asked for
+    /// `min_align`, which is useful for SIMD or DMA buffers. Allocations are
+    /// always page-aligned already, except for those made with
+    /// `allocate_with_offset`, where `page_offset` must then be a multiple of
+    /// `min_align`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_align` is not a power of two.
+    pub const fn with_min_align(min_align: usize) -> Self {
+        assert!(
+            min_align.is_power_of_two(),
+            "`min_align` must be a power of two"
+        );
+
+        Self {
+            min_align,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that overwrites memory with zeros before
+    /// unmapping it, if `zero_on_free` is `true`.
+    ///
+    /// Freed memory otherwise keeps its contents until the pages are reused,
+    /// which can leak secrets, for example in core dumps. Zeroing adds the
+    /// cost of a full `memset` to every deallocation, and to the memory
+    /// released when shrinking. The memory must still be writable when it is
+    /// freed.
+    pub const fn with_zero_on_free(zero_on_free: bool) -> Self {
+        Self {
+            zero_on_free,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that overwrites memory with `byte` before
+    /// unmapping it.
+    ///
+    /// This is meant for debugging: memory that is used after being freed,
+    /// for example through a mapping placed at the same address, is filled
+    /// with an easily recognizable pattern such as `0xde`. The memory
+    /// released when shrinking, and the old memory block when growing moves
+    /// it, are poisoned as well. The memory must still be writable when it is
+    /// freed. If zeroing on free is enabled as well, memory is zeroed
+    /// instead.
+    pub const fn with_poison(byte: u8) -> Self {
+        Self {
+            poison: Some(byte),
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator rounding its allocations to pages of `page_size`
+    /// bytes instead of the system page size.
+    ///
+    /// This is only meant for tests, which can then check the rounding of
+    /// allocations independently of the host. The mappings are still made of
+    /// system pages, so `page_size` is raised to the system page size if it
+    /// is smaller, and the allocations are aligned to it. This requires the
+    /// `page-size-override` feature.
+    ///
+    /// If `page_size` is not a power of two, allocating panics in debug
+    /// builds, and fails with `MMapError::PageSizeNotPowerOfTwo` otherwise.
+    #[cfg(all(unix, feature = "page-size-override"))]
+    pub const fn with_page_size_for_testing(page_size: usize) -> Self {
+        Self {
+            page_size_override: Some(page_size),
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that keeps track of the memory it maps.
+    ///
+    /// The counters are available through `live_allocations` and
+    /// `bytes_mapped`, which is useful to detect leaks in tests.
+    pub const fn with_stats() -> Self {
+        Self {
+            stats: Some(Stats::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator that keeps the single-page mappings it frees in a
+    /// cache local to the freeing thread.
+    ///
+    /// The next allocations of a single page on the same thread are served
+    /// from the cache, zeroed, without any system call, which avoids
+    /// contending on the locks the kernel takes to map and unmap memory. Each
+    /// thread caches at most 16 mappings, shared between all the allocators
+    /// with a thread cache, and unmaps them when it exits, or when
+    /// `flush_thread_cache` is called. The mappings cached by a thread are
+    /// thus kept until then, even if the allocator is dropped first.
+    ///
+    /// Cached mappings are no longer counted by `live_allocations` and
+    /// `bytes_mapped`. The memory blocks must still be readable and writable
+    /// when they are freed. This requires the `std` feature.
+    #[cfg(all(unix, feature = "std"))]
+    pub fn with_thread_cache() -> Self {
+        Self {
+            thread_cache: Some(thread_cache::new_owner()),
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator whose memory is shared with the child processes.
+    ///
+    /// Allocations are mapped with `MAP_SHARED | MAP_ANONYMOUS`, so that writes
+    /// made before or after a `fork` are visible to both processes. If an
+    /// allocation needs to be moved to grow or shrink it, the new mapping is
+    /// only shared with the processes forked afterwards.
+    #[cfg(unix)]
+    pub const fn shared() -> Self {
+        Self {
+            backing: Source::SharedAnonymous,
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator whose memory is backed by the file `fd`, starting
+    /// at `offset`.
+    ///
+    /// Every allocation maps the next unused region of the file with
+    /// `MAP_SHARED`, so that writes through the allocation reach the file.
+    /// Since `mmap` only maps whole pages of the file, `offset` is rounded up
+    /// to a multiple of the page size. If the file is a regular file, it must
+    /// be large enough to hold every allocation, otherwise allocating fails
+    /// with `AllocError`, or with `FileTooSmall` from `try_allocate`, rather
+    /// than faulting on the first access. The file descriptor is not
+    /// closed by the allocator, and must stay valid as long as the allocator
+    /// is in use.
+    #[cfg(unix)]
+    pub const fn file_backed(fd: libc::c_int, offset: u64) -> Self {
+        Self {
+            backing: Source::File {
+                fd,
+                offset: AtomicU64::new(offset),
+                private: false,
+                #[cfg(target_os = "linux")]
+                sync: false,
+            },
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator whose memory is initialized from the file `fd`,
+    /// starting at `offset`.
+    ///
+    /// Every allocation maps the next unused region of the file with
+    /// `MAP_PRIVATE`: reads are served from the file, while writes go to
+    /// private copy-on-write pages and never reach it. The same requirements
+    /// as `file_backed` apply to `offset` and `fd`. Since the memory is not
+    /// zeroed, `allocate_zeroed` has to clear it explicitly.
+    #[cfg(unix)]
+    pub const fn cow_file(fd: libc::c_int, offset: u64) -> Self {
+        Self {
+            backing: Source::File {
+                fd,
+                offset: AtomicU64::new(offset),
+                private: true,
+                #[cfg(target_os = "linux")]
+                sync: false,
+            },
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator whose memory is backed by the file `fd` on a
+    /// persistent memory (DAX) device, starting at `offset`.
+    ///
+    /// Every allocation maps the next unused region of the file with
+    /// `MAP_SHARED_VALIDATE | MAP_SYNC`, so that the stores made to an
+    /// allocation are durable as soon as they are flushed from the CPU caches
+    /// with `flush`, without any call to `msync`. `flush` is only supported
+    /// on x86_64. The same requirements as `file_backed` apply to `offset`
+    /// and `fd`. If the file does not support synchronous mappings,
+    /// allocating fails with `AllocError`, or with `MmapFailed(EOPNOTSUPP)`
+    /// from `try_allocate`.
+    #[cfg(target_os = "linux")]
+    pub const fn pmem_file(fd: libc::c_int, offset: u64) -> Self {
+        Self {
+            backing: Source::File {
+                fd,
+                offset: AtomicU64::new(offset),
+                private: false,
+                sync: true,
+            },
+            ..Self::new()
+        }
+    }
+
+    /// Creates an allocator mapping the sealed file `fd` read-only, typically
+    /// a `memfd` sealed with `F_SEAL_WRITE`.
+    ///
+    /// Every allocation maps the next unused region of the file with
+    /// `MAP_SHARED` and `PROT_READ`, starting from the beginning of the file,
+    /// so that immutable data can be shared between processes. The file
+    /// descriptor is not closed by the allocator, and must stay valid as long
+    /// as the allocator is in use. Since the memory is read-only, it cannot be
+    /// grown.
+    #[cfg(unix)]
+    pub const fn sealed_memfd(fd: libc::c_int) -> Self {
+        Self {
+            backing: Source::File {
+                fd,
+                offset: AtomicU64::new(0),
+                private: false,
+                #[cfg(target_os = "linux")]
+                sync: false,
+            },
+            protection: Protection::READ,
+            ..Self::new()
+        }
+    }
+
+    /// Turns the allocator into one making its mappings through `mapper`,
+    /// keeping every other option but the thread cache, which unmaps the
+    /// cached pages without going through the mapper.
+    pub fn with_mapper<M: MemoryMapper>(self, mapper: M) -> GenericMMapAllocator<M> {
+        let Self {
+            #[cfg(unix)]
+            backing,
+            #[cfg(target_os = "linux")]
+            huge_pages,
+            #[cfg(unix)]
+            guard_pages,
+            protection,
+            #[cfg(unix)]
+            populate,
+            #[cfg(unix)]
+            no_reserve,
+            #[cfg(unix)]
+            locked,
+            #[cfg(target_os = "linux")]
+            numa_node,
+            #[cfg(target_os = "linux")]
+            thp,
+            #[cfg(target_os = "linux")]
+            thp_alignment,
+            #[cfg(target_os = "linux")]
+            dontfork,
+            #[cfg(target_os = "linux")]
+            name,
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            map_32bit,
+            min_align,
+            zero_on_free,
+            poison,
+            #[cfg(all(unix, feature = "page-size-override"))]
+            page_size_override,
+            stats,
+            #[cfg(all(unix, feature = "std"))]
+                thread_cache: _,
+            mapper: _,
+        } = self;
+
+        GenericMMapAllocator {
+            #[cfg(unix)]
+            backing,
+            #[cfg(target_os = "linux")]
+            huge_pages,
+            #[cfg(unix)]
+            guard_pages,
+            protection,
+            #[cfg(unix)]
+            populate,
+            #[cfg(unix)]
+            no_reserve,
+            #[cfg(unix)]
+            locked,
+            #[cfg(target_os = "linux")]
+            numa_node,
+            #[cfg(target_os = "linux")]
+            thp,
+            #[cfg(target_os = "linux")]
+            thp_alignment,
+            #[cfg(target_os = "linux")]
+            dontfork,
+            #[cfg(target_os = "linux")]
+            name,
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            map_32bit,
+            min_align,
+            zero_on_free,
+            poison,
+            #[cfg(all(unix, feature = "page-size-override"))]
+            page_size_override,
+            stats,
+            #[cfg(all(unix, feature = "std"))]
+            thread_cache: None,
+            mapper,
+        }
+    }
+}
+
+impl MMapAllocator {
+    /// Grows the memory block referenced by `ptr` like `Allocator::grow`,
+    /// moving it to a new mapping with `MREMAP_DONTUNMAP` so that the old
+    /// address range stays reserved.
+    ///
+    /// The pages of the memory block are moved without copying, and the old
+    /// address range is left mapped with fresh zero-filled pages, so stale
+    /// pointers into it can never access memory later reused by another
+    /// mapping. The caller becomes responsible for the old range, which
+    /// spans `mapping_size(old_layout)` bytes at `ptr`, and must eventually
+    /// release it with `munmap` itself. The old range is not tracked by the
+    /// statistics of the allocator.
+    ///
+    /// `MREMAP_DONTUNMAP` is only supported by Linux 5.7 and later, and only
+    /// for private anonymous mappings without guard pages. Otherwise, or if
+    /// the kernel rejects the flag with `EINVAL`, this falls back to
+    /// `Allocator::grow`, which may release the old range. Whether the old
+    /// range was kept can be told by the returned memory block not starting
+    /// at `ptr`. If the moved memory block cannot be grown, its pages are
+    /// moved back to `ptr`, which then holds the memory block unchanged.
+    ///
+    /// This is only offered by `MMapAllocator`, as the pages are moved with
+    /// the system calls directly.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::grow`.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn grow_dontunmap(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        let aligned_layout = self.min_aligned(new_layout)?;
+
+        // The same restrictions as for `mremap` in `grow_report` apply.
+        if old_layout.size() == 0
+            || !matches!(self.backing, Source::Anonymous)
+            || self.guard_pages != 0
+            || aligned_layout.align().max(self.page_size()) > self.mmap_align()
+            || self.maps_32bit()
+        {
+            return self.grow(ptr, old_layout, new_layout);
+        }
+
+        // `MREMAP_DONTUNMAP` cannot resize the mapping while moving it, so
+        // the mapping is first moved as it is, and then grown from there.
+        let old_page_layout = self.page_layout(old_layout)?;
+        let res = sys::mremap(
+            ptr.as_ptr().cast::<c_void>(),
+            old_page_layout.size(),
+            old_page_layout.size(),
+            libc::MREMAP_MAYMOVE | libc::MREMAP_DONTUNMAP,
+        );
+        let moved_ptr = match res {
+            // SAFETY: `mremap` is guaranteed to return a valid pointer if it
+            // succeeds.
+            Ok(moved_mapping) => NonNull::new_unchecked(moved_mapping.cast::<u8>()),
+            Err(libc::EINVAL) => return self.grow(ptr, old_layout, new_layout),
+            Err(err) => return Err(MMapError::MremapFailed(err).into()),
         };
-        if new_mapping == libc::MAP_FAILED {
-            return Err(AllocError);
+
+        let res = self.grow(moved_ptr, old_layout, new_layout);
+
+        // The memory block must be left unchanged if it cannot be grown, so
+        // its pages are moved back over the fresh ones left at `ptr`.
+        if res.is_err() {
+            if let Err(errno) = sys::mremap_fixed(
+                moved_ptr.as_ptr().cast::<c_void>(),
+                old_page_layout.size(),
+                ptr.as_ptr().cast::<c_void>(),
+            ) {
+                report_unmap_failure(
+                    "leaking a moved allocation",
+                    MMapError::MremapFailed(errno),
+                    moved_ptr.as_ptr(),
+                    old_page_layout.size(),
+                );
+            }
         }
 
-        // SAFETY: `mmap` is guaranteed to return a valid pointer if it
-        // succeeds.
-        let new_mapping = unsafe { NonNull::new_unchecked(new_mapping.cast::<u8>()) };
+        res
+    }
+
+    /// Exchanges the pages backing the memory blocks at `a` and `b`, both
+    /// allocated with `layout`, so that each address then holds the contents
+    /// of the other memory block.
+    ///
+    /// The pages are moved with `mremap`, without copying their contents:
+    /// the memory block at `a` is first moved to a temporary range, the one
+    /// at `b` is moved to `a`, and the temporary range is moved to `b`. The
+    /// exchange is not atomic for other threads, which must not access either
+    /// memory block until it completes. If a step fails, the previous ones
+    /// are undone as far as possible, and `AllocError` is returned.
+    ///
+    /// This is only supported on Linux, and only by `MMapAllocator`, as the
+    /// moves are issued to the system directly.
+    ///
+    /// # Safety
+    ///
+    /// `a` and `b` must denote two distinct blocks of memory currently
+    /// allocated via this allocator, and `layout` must fit both of them.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn swap(
+        &self,
+        a: NonNull<u8>,
+        b: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), AllocError> {
+        let size = self.page_layout(layout)?.size();
+        if size == 0 {
+            return Ok(());
+        }
+
+        let a = a.as_ptr().cast::<c_void>();
+        let b = b.as_ptr().cast::<c_void>();
+
+        // The temporary range is reserved first, so that nothing else can be
+        // mapped there in the meantime.
+        let tmp = sys::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | ANON_FLAG,
+            -1,
+            0,
+        )
+        .map_err(MMapError::MmapFailed)?;
+
+        if let Err(errno) = sys::mremap_fixed(a, size, tmp) {
+            let _ = sys::munmap(tmp, size);
+            return Err(MMapError::MremapFailed(errno).into());
+        }
+
+        if let Err(errno) = sys::mremap_fixed(b, size, a) {
+            let _ = sys::mremap_fixed(tmp, size, a);
+            return Err(MMapError::MremapFailed(errno).into());
+        }
+
+        if let Err(errno) = sys::mremap_fixed(tmp, size, b) {
+            let _ = sys::mremap_fixed(a, size, b);
+            let _ = sys::mremap_fixed(tmp, size, a);
+            return Err(MMapError::MremapFailed(errno).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl<M: MemoryMapper> GenericMMapAllocator<M> {
+    /// Returns the mapper making the mappings of the allocator.
+    pub fn mapper(&self) -> &M {
+        &self.mapper
+    }
+
+    /// Allocates a block of memory like `allocate`, asking the system to place
+    /// it at `addr`.
+    ///
+    /// `addr` is only a hint, as with a `mmap` call without `MAP_FIXED`: the
+    /// system may place the mapping anywhere else, for example if `addr` is
+    /// already in use. The returned pointer must always be used instead of
+    /// `addr`.
+    ///
+    /// # Safety
+    ///
+    /// `addr` is never dereferenced, but it should be page-aligned for the
+    /// hint to be honored.
+    #[cfg(unix)]
+    pub unsafe fn allocate_at(
+        &self,
+        addr: *mut c_void,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let layout = self.min_aligned(layout)?;
+        let page_layout = self.page_layout(layout)?;
+
+        let new_mapping = self.map_near(addr, 0, page_layout.size(), layout.align())?;
+
+        if let Some(stats) = &self.stats {
+            stats.record_map(page_layout.size());
+        }
 
         Ok(NonNull::slice_from_raw_parts(
             new_mapping,
-            layout.pad_to_align().size(),
+            page_layout.size(),
         ))
     }
 
-    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
-        // `ptr` is assumed to be currently allocated, thus the memory it points
-        // to is currently mapped and also page-aligned.
-        //
-        // `layout.size()` fits the current memory block, so it always falls in
-        // the last page of the current mapping.
-        let res = libc::munmap(ptr.as_ptr().cast::<c_void>(), layout.size());
-        if res == -1 {
-            panic!("munmap failed");
+    /// Allocates a block of memory like `allocate`, placing it exactly at
+    /// `addr`.
+    ///
+    /// Unlike `allocate_at`, the mapping never replaces nor lands away from
+    /// `addr`: if the requested range overlaps an existing mapping,
+    /// `AllocError` is returned. `addr` must be aligned to the page size and
+    /// to `layout.align()`. This relies on `MAP_FIXED_NOREPLACE`, so
+    /// allocating always fails with `AllocError` on other platforms.
+    ///
+    /// # Safety
+    ///
+    /// `addr` is never dereferenced, but the caller must make sure that no
+    /// memory is expected to be mapped at `addr` by someone else later on.
+    #[cfg(unix)]
+    pub unsafe fn allocate_fixed(
+        &self,
+        addr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if MAP_FIXED_NOREPLACE == 0 {
+            return Err(AllocError);
+        }
+
+        let layout = self.min_aligned(layout)?;
+        let page_size = self.checked_page_size()?;
+        let align = layout.align().max(page_size);
+        if addr.as_ptr().align_offset(align) != 0 {
+            return Err(AllocError);
+        }
+
+        let page_layout = self.page_layout(layout)?;
+
+        // The leading guard pages are placed right before `addr`.
+        let guard_size = self.guard_size()?;
+        let region = (addr.as_ptr() as usize)
+            .checked_sub(guard_size)
+            .ok_or(AllocError)?;
+
+        // `addr` is already suitably aligned, so no trimming is needed.
+        let new_mapping = self.map_near(
+            region as *mut c_void,
+            MAP_FIXED_NOREPLACE,
+            page_layout.size(),
+            page_size,
+        )?;
+
+        // Kernels older than Linux 4.17 do not know `MAP_FIXED_NOREPLACE`,
+        // and treat `addr` as a hint instead.
+        if new_mapping != addr {
+            let _ = self.unmap(new_mapping, page_layout.size());
+            return Err(AllocError);
+        }
+
+        if let Some(stats) = &self.stats {
+            stats.record_map(page_layout.size());
         }
+
+        Ok(NonNull::slice_from_raw_parts(
+            new_mapping,
+            page_layout.size(),
+        ))
     }
 
-    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
-        // `mmap` always maps zeroed memory.
-        self.allocate(layout)
+    /// Allocates a block of memory like `allocate`, starting `page_offset`
+    /// bytes into its first page.
+    ///
+    /// This allows spreading allocations over different cache sets, which
+    /// page-aligned allocations would otherwise all share. Enough pages are
+    /// mapped to hold `page_offset + layout.size()` bytes, and the returned
+    /// memory block covers them from `page_offset` to their end. If
+    /// `page_offset` is not smaller than the page size, or is not a multiple
+    /// of `layout.align()`, `AllocError` is returned.
+    ///
+    /// # Safety
+    ///
+    /// The memory block must only be released with `deallocate`, as `grow`
+    /// and `shrink` expect memory blocks starting at the beginning of a page.
+    pub unsafe fn allocate_with_offset(
+        &self,
+        layout: Layout,
+        page_offset: usize,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let layout = self.min_aligned(layout)?;
+        let page_size = self.checked_page_size()?;
+        if page_offset >= page_size || page_offset & (layout.align() - 1) != 0 {
+            return Err(AllocError);
+        }
+
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+
+        // `layout.size()` is at most `isize::MAX`, so this cannot overflow.
+        let size = round_to_pages(layout.size() + page_offset, page_size)?;
+
+        let new_mapping = self.map_region(size, layout.align())?;
+
+        if let Some(stats) = &self.stats {
+            stats.record_map(size);
+        }
+
+        Ok(NonNull::slice_from_raw_parts(
+            new_mapping.add(page_offset),
+            size - page_offset,
+        ))
     }
 
-    unsafe fn grow(
+    /// Grows the memory block referenced by `ptr` like `Allocator::grow`,
+    /// without ever moving it.
+    ///
+    /// This only succeeds if `new_layout` fits in the pages already mapped
+    /// for `old_layout`, and if `ptr` satisfies the alignment of
+    /// `new_layout`. Otherwise, `AllocError` is returned and the memory block
+    /// is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::grow`.
+    pub unsafe fn grow_in_place(
         &self,
         ptr: NonNull<u8>,
         old_layout: Layout,
@@ -73,91 +1134,2007 @@ unsafe impl Allocator for MMapAllocator {
             "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
         );
 
-        if new_layout.align() > page_size::get() {
-            // `mmap` can only map memory page-aligned.
+        let new_layout = self.min_aligned(new_layout)?;
+        let old_page_layout = self.page_layout(old_layout)?;
+        let new_page_layout = self.page_layout(new_layout)?;
+
+        // `old_page_layout` gives the full size of the previous allocation, so
+        // we check if there is enough space on the last page to fit
+        // `new_layout`. A stricter alignment can only be kept if the current
+        // mapping happens to satisfy it already.
+        if old_page_layout != new_page_layout || ptr.as_ptr().align_offset(new_layout.align()) != 0
+        {
             return Err(AllocError);
         }
 
-        let old_layout = old_layout
-            .align_to(page_size::get())
-            .map_err(|_| AllocError)?;
-        let new_layout = new_layout
-            .align_to(page_size::get())
-            .map_err(|_| AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, new_page_layout.size()))
+    }
+
+    /// Grows the memory block referenced by `ptr` like `Allocator::grow`,
+    /// also reporting whether it was relocated.
+    ///
+    /// The returned flag is `true` whenever the memory block no longer
+    /// starts at `ptr`, in which case any pointer into the old memory block
+    /// must be updated. This happens both when the contents are copied to a
+    /// new mapping and when `mremap` moves the mapping without copying it.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::grow`.
+    #[cfg(unix)]
+    pub unsafe fn grow_report(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(NonNull<[u8]>, bool), AllocError> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        let new_layout = self.min_aligned(new_layout)?;
+
+        // Zero-sized allocations are not mapped, so there is nothing to keep.
+        if old_layout.size() == 0 {
+            return Ok((self.allocate(new_layout)?, true));
+        }
+
+        if let Ok(new_ptr) = self.grow_in_place(ptr, old_layout, new_layout) {
+            return Ok((new_ptr, false));
+        }
+
+        // On Linux, the kernel can extend the mapping or move it somewhere
+        // else without copying its contents. `mremap` cannot be used if the
+        // mapping is surrounded by guard pages, since they would be left
+        // behind, or if the new mapping needs a stricter alignment. File
+        // mappings cannot be extended either, as the following region of the
+        // file might belong to another allocation, and neither can shared
+        // anonymous mappings, whose backing object has a fixed size. Moved
+        // mappings could also land outside the range requested by
+        // `MAP_32BIT`, or be less aligned than an overridden page size.
+        #[cfg(target_os = "linux")]
+        if matches!(self.backing, Source::Anonymous)
+            && self.guard_pages == 0
+            && new_layout.align().max(self.page_size()) <= self.mmap_align()
+            && !self.maps_32bit()
+        {
+            let old_page_layout = self.page_layout(old_layout)?;
+            let new_page_layout = self.page_layout(new_layout)?;
 
-        // When padded to alignment, `old_layout` gives the full size of the
-        // previous allocation, so we check if there is enough space on the last
-        // page to fit `new_layout`.
-        if old_layout.pad_to_align() == new_layout.pad_to_align() {
-            return Ok(NonNull::slice_from_raw_parts(
-                ptr,
-                new_layout.pad_to_align().size(),
-            ));
+            let res = self.mapper.remap(
+                ptr.as_ptr().cast::<c_void>(),
+                old_page_layout.size(),
+                new_page_layout.size(),
+                libc::MREMAP_MAYMOVE,
+            );
+            if let Ok(new_mapping) = res {
+                // SAFETY: `mremap` is guaranteed to return a valid pointer if
+                // it succeeds.
+                let new_mapping = NonNull::new_unchecked(new_mapping.cast::<u8>());
+
+                // The kernel keeps the advice when moving the mapping, but
+                // the pages added to it are advised again to be sure they are
+                // not inherited either. The mapping is valid at this point,
+                // so a failure is not propagated.
+                if self.dontfork {
+                    let _ = self.advise_dontfork(new_mapping.as_ptr(), new_page_layout.size());
+                }
+
+                if let Some(stats) = &self.stats {
+                    stats.record_resize(old_page_layout.size(), new_page_layout.size());
+                }
+
+                return Ok((
+                    NonNull::slice_from_raw_parts(new_mapping, new_page_layout.size()),
+                    new_mapping != ptr,
+                ));
+            }
         }
 
+        if !self
+            .protection
+            .contains(Protection::READ | Protection::WRITE)
+        {
+            // The contents of the allocation cannot be moved to a new mapping.
+            return Err(AllocError);
+        }
+
+        let old_size = self.mapping_size(old_layout)?;
         let new_ptr = self.allocate(new_layout)?;
 
+        // The whole old memory block was usable, including the end of its last
+        // page, so all of it is moved to the new mapping.
+        let old_size = old_size.min(new_ptr.len());
+
         // SAFETY: because `new_layout.size()` must be greater than or equal to
         // `old_layout.size()`, both the old and new memory allocation are valid for reads and
-        // writes for `old_layout.size()` bytes. Also, because the old allocation wasn't yet
+        // writes for `old_size` bytes. Also, because the old allocation wasn't yet
         // deallocated, it cannot overlap `new_ptr`. Thus, the call to `copy_nonoverlapping` is
         // safe. The safety contract for `dealloc` must be upheld by the caller.
-        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), old_layout.size());
-        self.deallocate(ptr, old_layout);
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), old_size);
+        self.munmap_or_leak(ptr, old_layout);
+
+        Ok((new_ptr, true))
+    }
+
+    /// Copies the memory block referenced by `ptr` into a new, independent
+    /// memory block allocated for the same layout.
+    ///
+    /// The whole old memory block is copied, including the end of its last
+    /// page. The memory must be readable and writable, otherwise
+    /// `AllocError` is returned. The copy must be deallocated separately,
+    /// with the same layout.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    pub unsafe fn clone_mapping(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if !self
+            .protection
+            .contains(Protection::READ | Protection::WRITE)
+        {
+            return Err(AllocError);
+        }
+
+        let new_ptr = self.allocate(layout)?;
+
+        // SAFETY: both memory blocks are valid for `new_ptr.len()` bytes, and
+        // the new one was just mapped, so they cannot overlap.
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), new_ptr.len());
 
         Ok(new_ptr)
     }
 
-    unsafe fn grow_zeroed(
+    /// Changes the alignment of the memory block referenced by `ptr` to
+    /// `new_align`, keeping its size.
+    ///
+    /// If `ptr` is already aligned to `new_align`, which always holds up to
+    /// the page size, the memory block is returned as it is. Otherwise, it is
+    /// moved to a new mapping with the stricter alignment, like
+    /// `Allocator::grow` would. In both cases, the memory block must then be
+    /// released with a layout of `layout.size()` bytes aligned to
+    /// `new_align`. If `new_align` is not a power of two, `AllocError` is
+    /// returned and the memory block is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    pub unsafe fn realign(
         &self,
         ptr: NonNull<u8>,
-        old_layout: Layout,
-        new_layout: Layout,
+        layout: Layout,
+        new_align: usize,
     ) -> Result<NonNull<[u8]>, AllocError> {
-        // When growing on the same page, the new memory area is not required to
-        // be zeroed because it falls within the size returned for the old
-        // allocation, which is always page-aligned.
-        self.grow(ptr, old_layout, new_layout)
+        let new_layout =
+            Layout::from_size_align(layout.size(), new_align).map_err(|_| AllocError)?;
+
+        // Growing to the same size keeps the pages in place whenever `ptr`
+        // satisfies the new alignment, and copies them otherwise.
+        self.grow(ptr, layout, new_layout)
     }
 
-    unsafe fn shrink(
+    /// Resizes the memory block referenced by `ptr` to `new_layout`, growing
+    /// or shrinking it depending on how the sizes compare.
+    ///
+    /// This behaves like `Allocator::grow` if `new_layout` is not smaller
+    /// than `old_layout`, and like `Allocator::shrink` otherwise, which both
+    /// delegate to it. The returned `Resize` also tells whether the memory
+    /// block was relocated, so that callers such as heap profilers can keep
+    /// track of it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, `old_layout` must fit that block of memory, and the
+    /// alignment of `new_layout` must be within what `Allocator::grow` and
+    /// `Allocator::shrink` accept.
+    pub unsafe fn resize(
         &self,
         ptr: NonNull<u8>,
         old_layout: Layout,
         new_layout: Layout,
-    ) -> Result<NonNull<[u8]>, AllocError> {
-        debug_assert!(
-            new_layout.size() <= old_layout.size(),
-            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
-        );
+    ) -> Result<Resize, AllocError> {
+        // Moving the memory block deallocates the old one, which drops its
+        // tag, so it is looked up beforehand.
+        #[cfg(feature = "tags")]
+        let entry = tags::remove(ptr);
 
-        if new_layout.align() > page_size::get() {
-            // `mmap` can only map memory page-aligned.
-            return Err(AllocError);
+        let res = if new_layout.size() >= old_layout.size() {
+            self.grow_report(ptr, old_layout, new_layout)
+        } else {
+            self.shrink_report(ptr, old_layout, new_layout)
+        };
+
+        #[cfg(feature = "tags")]
+        if let Some(entry) = entry {
+            match res {
+                Ok((new_ptr, _)) => tags::insert(new_ptr, entry.tag),
+                // A failed resize leaves the old memory block unchanged.
+                Err(_) => tags::restore(ptr, entry),
+            }
         }
 
-        let old_layout = old_layout
-            .align_to(page_size::get())
-            .map_err(|_| AllocError)?;
-        let new_layout = new_layout
-            .align_to(page_size::get())
-            .map_err(|_| AllocError)?;
+        let (new_ptr, moved) = res?;
 
-        // Unmap the pages at the end of the current mapping to avoid memory
-        // leaks. The first portion of the current mapping can then just be
-        // reused.
+        Ok(Resize {
+            ptr: new_ptr,
+            moved,
+        })
+    }
+
+    /// Shrinks the memory block referenced by `ptr` like `Allocator::shrink`,
+    /// also reporting whether it was relocated.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::shrink`.
+    #[cfg(unix)]
+    unsafe fn shrink_report(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(NonNull<[u8]>, bool), AllocError> {
+        let new_layout = self.min_aligned(new_layout)?;
 
-        let retained_area_size = new_layout.pad_to_align().size();
-        let truncated_area_ptr = ptr.as_ptr().add(retained_area_size);
-        let truncated_area_size = old_layout.pad_to_align().size() - retained_area_size;
+        // Zero-sized allocations are never mapped, so the whole mapping can be
+        // released.
+        if new_layout.size() == 0 {
+            self.munmap_or_leak(ptr, old_layout);
+            return Ok((dangling(new_layout), true));
+        }
 
-        if truncated_area_size > 0 {
-            let res = libc::munmap(truncated_area_ptr.cast::<c_void>(), truncated_area_size);
-            if res == -1 {
-                panic!("munmap failed");
+        if ptr.as_ptr().align_offset(new_layout.align()) != 0 {
+            // The current mapping does not satisfy the new alignment, so the
+            // retained data has to be moved to a new mapping.
+            if !self
+                .protection
+                .contains(Protection::READ | Protection::WRITE)
+            {
+                return Err(AllocError);
+            }
+
+            let new_ptr = self.allocate(new_layout)?;
+
+            // SAFETY: the old allocation is valid for reads of
+            // `new_layout.size()` bytes and cannot overlap `new_ptr`, as it
+            // was not yet deallocated.
+            ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), new_layout.size());
+            self.munmap_or_leak(ptr, old_layout);
+
+            return Ok((new_ptr, true));
+        }
+
+        Ok((self.shrink_in_place(ptr, old_layout, new_layout)?, false))
+    }
+
+    /// Shrinks the memory block referenced by `ptr` like `Allocator::shrink`,
+    /// without ever moving it.
+    ///
+    /// The pages that are no longer needed are released. This fails with
+    /// `AllocError` if `ptr` does not satisfy the alignment of `new_layout`,
+    /// or if `new_layout` is zero-sized, since zero-sized allocations are
+    /// never mapped.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::shrink`.
+    pub unsafe fn shrink_in_place(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
+        );
+
+        let new_layout = self.min_aligned(new_layout)?;
+        if (new_layout.size() == 0 && old_layout.size() != 0)
+            || ptr.as_ptr().align_offset(new_layout.align()) != 0
+        {
+            return Err(AllocError);
+        }
+
+        let old_page_layout = self.page_layout(old_layout)?;
+        let new_page_layout = self.page_layout(new_layout)?;
+
+        Ok(self.release_tail(ptr, old_page_layout, new_page_layout)?)
+    }
+
+    /// Splits the memory block referenced by `ptr` at the offset `at`, into
+    /// two memory blocks that can be deallocated independently.
+    ///
+    /// The first memory block holds the `at` bytes before the split, while
+    /// the second one holds the rest of the pages backing the original memory
+    /// block. `at` must be a non-zero multiple of the page size of the
+    /// allocator that falls within those pages, and `ptr` must be page-aligned.
+    /// Since each memory block needs guard pages of its own, this fails with
+    /// `AllocError` when guard pages are used.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. The original
+    /// memory block must no longer be used, even though the two new ones
+    /// cover it.
+    #[cfg(unix)]
+    #[allow(clippy::type_complexity)]
+    pub unsafe fn split(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        at: usize,
+    ) -> Result<(NonNull<[u8]>, NonNull<[u8]>), AllocError> {
+        let page_size = self.checked_page_size()?;
+        let page_layout = self.page_layout(layout)?;
+
+        if self.guard_pages > 0
+            || ptr.as_ptr().align_offset(page_size) != 0
+            || at == 0
+            || at & (page_size - 1) != 0
+            || at >= page_layout.size()
+        {
+            return Err(AllocError);
+        }
+
+        // Mappings can be unmapped piecewise, so each part of the mapping can
+        // be released on its own.
+        if let Some(stats) = &self.stats {
+            stats.record_split();
+        }
+
+        Ok((
+            NonNull::slice_from_raw_parts(ptr, at),
+            NonNull::slice_from_raw_parts(ptr.add(at), page_layout.size() - at),
+        ))
+    }
+
+    /// Merges the memory blocks referenced by `a` and `b` into a single memory
+    /// block, which can be deallocated at once with the returned layout.
+    ///
+    /// The pages backing `b` must start right where those backing `a` end,
+    /// as after `split` or after allocating them with `allocate_fixed`. `a`
+    /// must be page-aligned, and the returned layout keeps the alignment of
+    /// `a_layout`. Since guard pages would end up in the middle of the memory
+    /// block, this fails with `AllocError` when guard pages are used. No
+    /// system call is needed, as the mappings already share their options.
+    ///
+    /// # Safety
+    ///
+    /// `a` and `b` must denote blocks of memory currently allocated via this
+    /// allocator, and `a_layout` and `b_layout` must fit them respectively.
+    /// Neither of them must be used once the merged memory block is returned.
+    #[cfg(unix)]
+    pub unsafe fn merge(
+        &self,
+        a: NonNull<u8>,
+        a_layout: Layout,
+        b: NonNull<u8>,
+        b_layout: Layout,
+    ) -> Result<(NonNull<[u8]>, Layout), AllocError> {
+        let a_page_layout = self.page_layout(a_layout)?;
+        let b_page_layout = self.page_layout(b_layout)?;
+
+        if self.guard_pages > 0
+            || a.as_ptr().align_offset(self.checked_page_size()?) != 0
+            || (a.as_ptr() as usize).checked_add(a_page_layout.size()) != Some(b.as_ptr() as usize)
+        {
+            return Err(AllocError);
+        }
+
+        // Only the pages of `b` need to be accounted for in the size, as
+        // those of `a` are all used up to `b`.
+        let size = a_page_layout
+            .size()
+            .checked_add(b_layout.size())
+            .ok_or(AllocError)?;
+        let layout = Layout::from_size_align(size, a_layout.align()).map_err(|_| AllocError)?;
+
+        if let Some(stats) = &self.stats {
+            stats.record_merge();
+        }
+
+        Ok((
+            NonNull::slice_from_raw_parts(a, a_page_layout.size() + b_page_layout.size()),
+            layout,
+        ))
+    }
+
+    /// Allocates an array of `len` elements of type `T`.
+    ///
+    /// The elements of the array are uninitialized. If the array is too
+    /// large, or if the alignment of `T` cannot be satisfied, `AllocError` is
+    /// returned. The
+    /// array must be released with `deallocate_array`, or with `deallocate`
+    /// and `Layout::array::<T>(len)`.
+    pub fn allocate_array<T>(&self, len: usize) -> Result<NonNull<[T]>, AllocError> {
+        let layout = Layout::array::<T>(len).map_err(|_| MMapError::SizeOverflow)?;
+        let new_ptr = self.try_allocate(layout)?;
+
+        Ok(NonNull::slice_from_raw_parts(
+            new_ptr.as_non_null_ptr().cast::<T>(),
+            len,
+        ))
+    }
+
+    /// Deallocates an array allocated with `allocate_array`.
+    ///
+    /// # Safety
+    ///
+    /// `array` must have been returned by `allocate_array` on this allocator,
+    /// and must not have been deallocated yet.
+    pub unsafe fn deallocate_array<T>(&self, array: NonNull<[T]>) {
+        // SAFETY: the same layout was successfully built by `allocate_array`.
+        let layout = Layout::array::<T>(array.len()).unwrap_unchecked();
+        self.deallocate(array.as_non_null_ptr().cast::<u8>(), layout);
+    }
+
+    /// Returns the layout of `count` whole pages.
+    fn pages_layout(&self, count: usize) -> Result<Layout, MMapError> {
+        let page_size = self.checked_page_size()?;
+        let size = count
+            .checked_mul(page_size)
+            .ok_or(MMapError::SizeOverflow)?;
+        Layout::from_size_align(size, page_size).map_err(|_| MMapError::SizeOverflow)
+    }
+
+    /// Allocates exactly `count` pages, without building a `Layout`.
+    ///
+    /// The returned memory block spans `count` times the page size of the
+    /// allocator, which accounts for huge pages, and is aligned to it. It is
+    /// the same memory block as `allocate` returns for that layout, so it
+    /// can also be released with `deallocate` and that layout, or grown and
+    /// shrunk like any other memory block.
+    pub fn allocate_pages(&self, count: usize) -> Result<NonNull<[u8]>, AllocError> {
+        let layout = self.pages_layout(count)?;
+        Ok(self.try_allocate(layout)?)
+    }
+
+    /// Deallocates the `count` pages allocated with `allocate_pages`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `allocate_pages` on this allocator
+    /// with the same `count`, and must not have been deallocated yet.
+    pub unsafe fn deallocate_pages(&self, ptr: NonNull<u8>, count: usize) {
+        // SAFETY: the same layout was successfully built by `allocate_pages`.
+        let layout = self.pages_layout(count).unwrap_unchecked();
+        self.deallocate(ptr, layout);
+    }
+
+    /// Creates an empty vector whose first allocation fills exactly one page
+    /// of `T`s, so that it does not reallocate before growing past it.
+    ///
+    /// The capacity is the number of `T`s fitting in a page of the allocator,
+    /// or 1 if `T` is larger than a page. Zero-sized types never allocate, so
+    /// their vector has the usual unbounded capacity. Failures to allocate are
+    /// handled like `Vec::with_capacity_in` does. This requires the `std`
+    /// feature.
+    #[cfg(feature = "std")]
+    pub fn vec_with_page_capacity<T>(&self) -> std::vec::Vec<T, &Self> {
+        let capacity = match mem::size_of::<T>() {
+            0 => 0,
+            size => (self.page_size() / size).max(1),
+        };
+
+        std::vec::Vec::with_capacity_in(capacity, self)
+    }
+
+    /// Returns the layout of the mapping holding `count` chunks of `chunk`.
+    #[cfg(feature = "std")]
+    fn chunks_layout(chunk: Layout, count: usize) -> Result<Layout, MMapError> {
+        let stride = chunk.pad_to_align().size();
+        let size = stride.checked_mul(count).ok_or(MMapError::SizeOverflow)?;
+        Layout::from_size_align(size, chunk.align()).map_err(|_| MMapError::SizeOverflow)
+    }
+
+    /// Allocates `count` chunks of `chunk` with a single mapping.
+    ///
+    /// The chunks are laid out one after the other, each padded to the
+    /// alignment of `chunk`. The base of the mapping is returned together
+    /// with one memory block per chunk. The chunks cannot be released
+    /// individually: the whole mapping must be released with
+    /// `deallocate_chunks`.
+    #[cfg(feature = "std")]
+    #[allow(clippy::type_complexity)]
+    pub fn allocate_chunks(
+        &self,
+        chunk: Layout,
+        count: usize,
+    ) -> Result<(NonNull<u8>, std::vec::Vec<NonNull<[u8]>>), AllocError> {
+        let layout = Self::chunks_layout(chunk, count)?;
+        let base = self.try_allocate(layout)?.as_non_null_ptr();
+
+        let stride = chunk.pad_to_align().size();
+        let chunks = (0..count)
+            .map(|index| {
+                // SAFETY: every chunk lies within the mapping.
+                let chunk_ptr =
+                    unsafe { NonNull::new_unchecked(base.as_ptr().add(index * stride)) };
+                NonNull::slice_from_raw_parts(chunk_ptr, chunk.size())
+            })
+            .collect();
+
+        Ok((base, chunks))
+    }
+
+    /// Deallocates the chunks allocated with `allocate_chunks`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must have been returned by `allocate_chunks` on this allocator
+    /// with the same `chunk` and `count`, and must not have been deallocated
+    /// yet.
+    #[cfg(feature = "std")]
+    pub unsafe fn deallocate_chunks(&self, base: NonNull<u8>, chunk: Layout, count: usize) {
+        // SAFETY: the same layout was successfully built by `allocate_chunks`.
+        let layout = Self::chunks_layout(chunk, count).unwrap_unchecked();
+        self.deallocate(base, layout);
+    }
+
+    /// Returns the size of the memory block that `allocate` returns for
+    /// `layout`, without allocating anything.
+    ///
+    /// This is `layout.size()` rounded to the page size of the allocator,
+    /// which accounts for huge pages. The alignment of `layout` never
+    /// affects the size of the memory block, and guard pages are not
+    /// included. If the page size is not a power of two, which can only
+    /// happen with `with_page_size_for_testing`, `AllocError` is returned.
+    pub fn mapping_size(&self, layout: Layout) -> Result<usize, AllocError> {
+        Ok(round_to_pages(layout.size(), self.checked_page_size()?)?)
+    }
+
+    /// Returns the largest alignment that `allocate` can satisfy.
+    ///
+    /// On Unix systems, stricter alignments than the page size are obtained
+    /// by mapping extra pages and unmapping the ones around the aligned
+    /// memory block, so there is no limit other than the address space,
+    /// which is reported as `usize::MAX`. Allocating may still fail if the
+    /// padding pages do not fit in the address space. On Windows,
+    /// `VirtualAlloc` only aligns to the page size.
+    pub fn max_supported_align(&self) -> usize {
+        #[cfg(unix)]
+        return usize::MAX;
+        #[cfg(windows)]
+        return cached_page_size();
+    }
+
+    /// Returns an estimate of the largest memory block that a single mapping
+    /// can currently provide, rounded down to the page size.
+    ///
+    /// The size is found by a binary search over the sizes that `mmap`
+    /// accepts, unmapping every probe as soon as it succeeds. The probes are
+    /// anonymous mappings with the configured protection and reservation, and
+    /// are neither populated nor locked, so they only measure the address
+    /// space and the commit limit. Other threads and processes keep changing
+    /// what is available, so allocating the returned size may still fail.
+    /// Nothing can be mapped, and 0 is returned, if the page size is not a
+    /// power of two.
+    #[cfg(unix)]
+    pub fn largest_available(&self) -> usize {
+        let page_size = match self.checked_page_size() {
+            Ok(page_size) => page_size,
+            Err(_) => return 0,
+        };
+        let mut flags = match self.backing {
+            Source::SharedAnonymous => libc::MAP_SHARED,
+            _ => libc::MAP_PRIVATE,
+        } | ANON_FLAG;
+        if self.no_reserve {
+            flags |= MAP_NORESERVE;
+        }
+
+        // Mapping `available` pages is known to succeed, while mapping
+        // `unavailable` pages is known to fail.
+        let mut available = 0;
+        let mut unavailable = MAX_MAPPING_SIZE / page_size + 1;
+        while unavailable - available > 1 {
+            let pages = available + (unavailable - available) / 2;
+            let size = pages * page_size;
+
+            let res = unsafe {
+                self.mapper.map(
+                    ptr::null_mut(),
+                    size,
+                    self.protection.to_prot(),
+                    flags,
+                    -1,
+                    0,
+                )
+            };
+            match res {
+                Ok(probe) => {
+                    if let Err(err) = unsafe { self.unmap_pages(probe.cast::<u8>(), size) } {
+                        report_unmap_failure("leaking a probe", err, probe.cast::<u8>(), size);
+                    }
+                    available = pages;
+                }
+                Err(_) => unavailable = pages,
+            }
+        }
+
+        available * page_size
+    }
+
+    /// Returns an iterator over the start of each page backing the memory
+    /// block at `ptr`.
+    ///
+    /// The memory block is rounded in the same way as `mapping_size` does,
+    /// and the pointers are spaced by the page size of the allocator, which
+    /// accounts for huge pages. The pointers are never dereferenced. The same
+    /// errors as for `mapping_size` are returned.
+    pub fn pages(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<impl Iterator<Item = NonNull<u8>>, AllocError> {
+        let size = self.mapping_size(layout)?;
+
+        Ok((0..size)
+            .step_by(self.page_size())
+            .map_while(move |offset| NonNull::new(ptr.as_ptr().wrapping_add(offset))))
+    }
+
+    /// Returns the number of allocations currently mapped.
+    ///
+    /// This is always 0 if the allocator was not created with `with_stats`.
+    pub fn live_allocations(&self) -> usize {
+        self.stats.as_ref().map_or(0, Stats::live_allocations)
+    }
+
+    /// Returns the number of bytes currently mapped for allocations.
+    ///
+    /// Allocations are accounted with their size rounded to whole pages, as
+    /// they are mapped. Guard pages are not included. This is always 0 if
+    /// the allocator was not created with `with_stats`.
+    pub fn bytes_mapped(&self) -> usize {
+        self.stats.as_ref().map_or(0, Stats::bytes_mapped)
+    }
+
+    /// Unmaps the mappings that the current thread cached for this allocator.
+    ///
+    /// This does nothing if the allocator was not created with
+    /// `with_thread_cache`.
+    #[cfg(all(unix, feature = "std"))]
+    pub fn flush_thread_cache(&self) {
+        if let Some(owner) = self.thread_cache {
+            thread_cache::flush(owner);
+        }
+    }
+
+    /// Changes the protection of the memory block at `ptr`.
+    ///
+    /// The new protection is applied to all the pages backing the memory
+    /// block, which is rounded in the same way as `allocate` does. This is
+    /// typically used to make memory executable after writing code into it.
+    /// If the protection cannot be changed, `AllocError` is returned.
+    ///
+    /// Growing or shrinking a memory block might need to move its contents,
+    /// which faults if the block is no longer readable and writable.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    #[cfg(unix)]
+    pub unsafe fn protect(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        protection: Protection,
+    ) -> Result<(), AllocError> {
+        let page_layout = self.page_layout(layout)?;
+
+        Ok(self.protect_pages(ptr.as_ptr(), page_layout.size(), protection.to_prot())?)
+    }
+
+    /// Allocates a read-only memory block holding a copy of `data`.
+    ///
+    /// The pages are mapped writable, filled with `data`, then protected with
+    /// `PROT_READ`, so that accidental writes fault. The returned memory block
+    /// covers all those pages, and anonymous mappings are filled with zeros
+    /// past the end of `data`. It can be deallocated as usual, with the
+    /// layout of `data`. Since overwriting the memory block on deallocation
+    /// would fault as well, this fails with `AllocError` if the allocator
+    /// zeroes or poisons freed memory.
+    #[cfg(unix)]
+    pub fn allocate_readonly_from(&self, data: &[u8]) -> Result<NonNull<[u8]>, AllocError> {
+        if self.freed_fill().is_some() {
+            return Err(AllocError);
+        }
+
+        let layout = Layout::for_value(data);
+        let new_ptr = self.allocate(layout)?;
+        if data.is_empty() {
+            return Ok(new_ptr);
+        }
+
+        // SAFETY: the new mapping covers `new_ptr.len()` bytes, which are at
+        // least `data.len()`, and is writable once the first `mprotect`
+        // succeeds.
+        let res = unsafe {
+            let make_writable = if self.protection.contains(Protection::WRITE) {
+                Ok(())
+            } else {
+                self.protect_pages(
+                    new_ptr.as_mut_ptr(),
+                    new_ptr.len(),
+                    libc::PROT_READ | libc::PROT_WRITE,
+                )
+            };
+
+            make_writable.and_then(|_| {
+                ptr::copy_nonoverlapping(data.as_ptr(), new_ptr.as_mut_ptr(), data.len());
+                self.protect_pages(new_ptr.as_mut_ptr(), new_ptr.len(), libc::PROT_READ)
+            })
+        };
+        if let Err(err) = res {
+            unsafe { self.munmap_or_leak(new_ptr.as_non_null_ptr(), layout) };
+            return Err(err.into());
+        }
+
+        Ok(new_ptr)
+    }
+
+    /// Advises the system about how the memory block at `ptr` is going to be
+    /// used.
+    ///
+    /// The advice covers all the pages backing the memory block, which is
+    /// rounded in the same way as `allocate` does. If the advice cannot be
+    /// given, `AllocError` is returned.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. After
+    /// `Advice::DontNeed`, the contents of the memory block are lost.
+    #[cfg(unix)]
+    pub unsafe fn advise(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        advice: Advice,
+    ) -> Result<(), AllocError> {
+        let page_layout = self.page_layout(layout)?;
+
+        sys::madvise(
+            ptr.as_ptr().cast::<c_void>(),
+            page_layout.size(),
+            advice.to_madvise(),
+        )
+        .map_err(MMapError::MadviseFailed)?;
+
+        Ok(())
+    }
+
+    /// Collapses the pages backing the memory block at `ptr` into transparent
+    /// huge pages with `MADV_COLLAPSE`.
+    ///
+    /// Unlike the hint given by `with_thp`, the collapse happens before
+    /// returning, so it can be paid upfront, for example during startup. Only
+    /// the huge pages fully covered by the memory block are collapsed, which
+    /// `with_thp_alignment` helps with. `AllocError` is returned if the pages
+    /// cannot be collapsed, including on kernels older than 6.1, which reject
+    /// the advice with `EINVAL`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn collapse(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), AllocError> {
+        let page_layout = self.page_layout(layout)?;
+
+        sys::madvise(
+            ptr.as_ptr().cast::<c_void>(),
+            page_layout.size(),
+            MADV_COLLAPSE,
+        )
+        .map_err(MMapError::MadviseFailed)?;
+
+        Ok(())
+    }
+
+    /// Allocates a memory block like `allocate`, then advises the system that
+    /// it is going to be accessed sequentially with `Advice::Sequential`.
+    ///
+    /// This makes the system read further ahead when the memory block is
+    /// streamed front to back, and reclaim the pages behind it sooner. The
+    /// advice is only a hint, so the allocation still succeeds if it cannot be
+    /// given.
+    #[cfg(unix)]
+    pub fn allocate_sequential(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(layout)?;
+
+        // Zero-sized allocations are never mapped, so there is nothing to
+        // advise.
+        if !new_ptr.is_empty() {
+            let _ = unsafe { self.advise(new_ptr.as_non_null_ptr(), layout, Advice::Sequential) };
+        }
+
+        Ok(new_ptr)
+    }
+
+    /// Allocates a memory block like `allocate`, recording it as a live
+    /// allocation for `tag` until it is deallocated.
+    ///
+    /// The tag is an arbitrary number, typically identifying the subsystem
+    /// making the allocation, and `dump_live_tagged` lists the live
+    /// allocations by tag. This requires the `tags` feature.
+    #[cfg(feature = "tags")]
+    pub fn allocate_tagged(&self, layout: Layout, tag: u32) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(layout)?;
+        tags::insert(new_ptr, tag);
+        Ok(new_ptr)
+    }
+
+    /// Releases the physical memory backing the memory block at `ptr`, while
+    /// keeping its address range mapped.
+    ///
+    /// This is `advise` with `Advice::DontNeed`: anonymous private pages are
+    /// filled with zeros on their next access, while the other mappings are
+    /// reloaded from their backing. On other platforms, the memory block
+    /// cannot be accessed until `recommit` is called, so it should always be
+    /// called before reusing the memory block.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. The contents of
+    /// the memory block are lost.
+    #[cfg(unix)]
+    pub unsafe fn decommit(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), AllocError> {
+        self.advise(ptr, layout, Advice::DontNeed)
+    }
+
+    /// Lets the system reclaim the physical memory backing the memory block
+    /// at `ptr` whenever it needs it, while keeping its address range mapped.
+    ///
+    /// This is `advise` with `Advice::Free`, which is cheaper than `decommit`
+    /// when the memory block is soon reused, as the pages are only reclaimed
+    /// under memory pressure. Writing to a page before that cancels its
+    /// reclamation. The contents of the memory block are undefined until it is
+    /// written again, since each page may or may not have been reclaimed. If
+    /// the mapping does not support `MADV_FREE`, as older Linux kernels and
+    /// the file mappings do not, it is decommitted instead. On Windows, the
+    /// pages are reset, with the same semantics.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. The contents of
+    /// the memory block must not be read before being written again.
+    #[cfg(unix)]
+    pub unsafe fn decommit_lazy(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), AllocError> {
+        self.advise(ptr, layout, Advice::Free)
+            .or_else(|_| self.decommit(ptr, layout))
+    }
+
+    /// Makes the memory block at `ptr` usable again after `decommit`.
+    ///
+    /// The memory is faulted in on demand anyway, so this only advises the
+    /// system that the pages will be needed soon.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    #[cfg(unix)]
+    pub unsafe fn recommit(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), AllocError> {
+        self.advise(ptr, layout, Advice::WillNeed)
+    }
+
+    /// Returns the number of pages of the memory block at `ptr` that are
+    /// currently resident in memory.
+    ///
+    /// The pages backing the memory block are rounded in the same way as
+    /// `allocate` does, then queried with `mincore`. They are always counted
+    /// in units of the system page size, even for huge pages. If `mincore`
+    /// fails, `AllocError` is returned.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    #[cfg(unix)]
+    pub unsafe fn resident_pages(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<usize, AllocError> {
+        let page_size = cached_page_size();
+        let size = self.page_layout(layout)?.size();
+
+        // The residency of the pages is queried in chunks, to avoid
+        // allocating a buffer as large as the whole memory block.
+        let mut residency = [0u8; 64];
+        let chunk_size = residency.len() * page_size;
+
+        let mut resident_pages = 0;
+        for offset in (0..size).step_by(chunk_size) {
+            let len = chunk_size.min(size - offset);
+            sys::mincore(
+                ptr.as_ptr().add(offset).cast::<c_void>(),
+                len,
+                residency.as_mut_ptr(),
+            )
+            .map_err(MMapError::MincoreFailed)?;
+
+            resident_pages += residency[..len / page_size]
+                .iter()
+                .filter(|&&page| page & 1 != 0)
+                .count();
+        }
+
+        Ok(resident_pages)
+    }
+
+    /// Faults in all the pages backing the memory block at `ptr`, so that
+    /// later accesses do not incur page faults.
+    ///
+    /// This has the same effect as allocating with `with_populate`, but can
+    /// be done at any time, for example right before a latency-critical
+    /// section. The system is first advised that the pages will be needed,
+    /// then one byte of each page is read. Nothing is done if the allocator
+    /// does not map readable memory.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. The memory
+    /// block must not have been made unreadable with `protect`.
+    pub unsafe fn prefault(&self, ptr: NonNull<u8>, layout: Layout) {
+        let size = match self.mapping_size(layout) {
+            Ok(size) if self.protection.contains(Protection::READ) => size,
+            _ => return,
+        };
+
+        // The advice is only a hint, so the pages are read even if it fails.
+        #[cfg(unix)]
+        let _ = sys::madvise(ptr.as_ptr().cast::<c_void>(), size, libc::MADV_WILLNEED);
+
+        for offset in (0..size).step_by(self.page_size()) {
+            ptr::read_volatile(ptr.as_ptr().add(offset));
+        }
+    }
+
+    /// Writes the memory block at `ptr` back to the file backing it with
+    /// `msync`.
+    ///
+    /// The write-back covers all the pages backing the memory block, which is
+    /// rounded in the same way as `allocate` does. If `sync` is `true`, the
+    /// call waits for the writes to reach the file with `MS_SYNC`, so that
+    /// they are durable once it returns. Otherwise, the writes are only
+    /// scheduled with `MS_ASYNC`. This is only meaningful for allocators
+    /// created by `file_backed`, since the other mappings have no file to be
+    /// written to. If the memory block cannot be written back, `AllocError`
+    /// is returned. This is not called `flush`, which is the name of the
+    /// cache-line flush for `pmem_file` allocators.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    #[cfg(unix)]
+    pub unsafe fn sync_to_file(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        sync: bool,
+    ) -> Result<(), AllocError> {
+        let page_layout = self.page_layout(layout)?;
+        let flags = if sync { libc::MS_SYNC } else { libc::MS_ASYNC };
+
+        sys::msync(ptr.as_ptr().cast::<c_void>(), page_layout.size(), flags)
+            .map_err(MMapError::MsyncFailed)?;
+
+        Ok(())
+    }
+
+    /// Writes back the cache lines holding the memory block at `ptr`, then
+    /// waits for the writes to complete.
+    ///
+    /// With an allocator created by `pmem_file`, the stores made to the
+    /// memory block before the call are durable once it returns. Each cache
+    /// line is flushed with `clflush`, followed by a store fence. This is only
+    /// supported on x86_64.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. The memory
+    /// block must not have been made inaccessible with `protect`.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    pub unsafe fn flush(&self, ptr: NonNull<u8>, layout: Layout) {
+        // Every x86_64 processor flushes lines of 64 bytes.
+        const LINE_SIZE: usize = 64;
+
+        let start = ptr.as_ptr() as usize & !(LINE_SIZE - 1);
+        let end = ptr.as_ptr() as usize + layout.size();
+        for line in (start..end).step_by(LINE_SIZE) {
+            core::arch::x86_64::_mm_clflush(line as *const u8);
+        }
+
+        core::arch::x86_64::_mm_sfence();
+    }
+
+    /// Returns the size of the pages backing the allocations.
+    fn page_size(&self) -> usize {
+        #[cfg(all(unix, feature = "page-size-override"))]
+        if let Some(page_size) = self.page_size_override {
+            return page_size.max(self.mmap_align());
+        }
+
+        self.mmap_align()
+    }
+
+    /// Returns the size of the pages backing the allocations, checking that it
+    /// is a power of two, as rounding sizes to whole pages relies on it.
+    ///
+    /// This is a bug in the configuration of the allocator, which is asserted
+    /// in debug builds.
+    fn checked_page_size(&self) -> Result<usize, MMapError> {
+        let page_size = self.page_size();
+        debug_assert!(
+            page_size.is_power_of_two(),
+            "the page size is not a power of two"
+        );
+        if !page_size.is_power_of_two() {
+            return Err(MMapError::PageSizeNotPowerOfTwo);
+        }
+
+        Ok(page_size)
+    }
+
+    /// Returns the alignment of the mappings returned by `mmap`, which is the
+    /// size of the pages backing them.
+    fn mmap_align(&self) -> usize {
+        #[cfg(target_os = "linux")]
+        if let Some(huge_pages) = self.huge_pages {
+            return huge_pages.size();
+        }
+
+        cached_page_size()
+    }
+
+    /// Returns the flags passed to `mmap` for new mappings.
+    #[cfg(unix)]
+    fn map_flags(&self) -> libc::c_int {
+        let mut flags = match self.backing {
+            Source::Anonymous => libc::MAP_PRIVATE | ANON_FLAG,
+            Source::SharedAnonymous => libc::MAP_SHARED | ANON_FLAG,
+            #[cfg(target_os = "linux")]
+            Source::File { sync: true, .. } => libc::MAP_SHARED_VALIDATE | libc::MAP_SYNC,
+            Source::File { private: false, .. } => libc::MAP_SHARED,
+            Source::File { private: true, .. } => libc::MAP_PRIVATE,
+        };
+
+        #[cfg(target_os = "linux")]
+        if let (Source::Anonymous | Source::SharedAnonymous, Some(huge_pages)) =
+            (&self.backing, self.huge_pages)
+        {
+            flags |= huge_pages.map_flags();
+        }
+
+        if self.populate {
+            flags |= MAP_POPULATE;
+        }
+
+        if self.no_reserve {
+            flags |= MAP_NORESERVE;
+        }
+
+        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+        if self.map_32bit {
+            flags |= libc::MAP_32BIT;
+        }
+
+        flags
+    }
+
+    /// Returns whether new mappings are placed in the low 2 GiB of the
+    /// address space.
+    #[cfg(target_os = "linux")]
+    fn maps_32bit(&self) -> bool {
+        #[cfg(target_arch = "x86_64")]
+        return self.map_32bit;
+        #[cfg(not(target_arch = "x86_64"))]
+        return false;
+    }
+
+    /// Returns the owner of the thread cache holding the mappings of `size`
+    /// bytes aligned to `align`, if they are cached.
+    ///
+    /// Only single pages without guard pages are cached, and they have to be
+    /// anonymous and writable, so that they can be zeroed when reused.
+    #[cfg(all(unix, feature = "std"))]
+    fn thread_cache_owner(&self, size: usize, align: usize) -> Option<NonZeroUsize> {
+        let page_size = self.checked_page_size().ok()?;
+        let cached = size == page_size
+            && align <= page_size
+            && self.guard_pages == 0
+            && self.maps_zeroed_memory()
+            && self.protection.contains(Protection::WRITE);
+        self.thread_cache.filter(|_| cached)
+    }
+
+    /// Returns the byte overwriting memory before it is unmapped, if any.
+    fn freed_fill(&self) -> Option<u8> {
+        if self.zero_on_free {
+            Some(0)
+        } else {
+            self.poison
+        }
+    }
+
+    /// Returns whether new mappings are always filled with zeros.
+    ///
+    /// Anonymous mappings are, while file mappings start with the contents of
+    /// the file.
+    #[cfg(unix)]
+    fn maps_zeroed_memory(&self) -> bool {
+        matches!(self.backing, Source::Anonymous | Source::SharedAnonymous)
+    }
+
+    /// Returns the layout of the pages backing an allocation of `layout`.
+    ///
+    /// Allocations are always trimmed to the smallest number of pages that can
+    /// hold `layout.size()` bytes, independently of the requested alignment.
+    /// `Layout` rejects sizes that exceed `isize::MAX` once rounded, so the
+    /// returned size is never larger than `MAX_MAPPING_SIZE`.
+    fn page_layout(&self, layout: Layout) -> Result<Layout, MMapError> {
+        Layout::from_size_align(layout.size(), self.checked_page_size()?)
+            .map(|layout| layout.pad_to_align())
+            .map_err(|_| MMapError::SizeOverflow)
+    }
+
+    /// Returns `layout` with its alignment raised to the minimum alignment of
+    /// the allocator.
+    fn min_aligned(&self, layout: Layout) -> Result<Layout, MMapError> {
+        layout
+            .align_to(self.min_align)
+            .map_err(|_| MMapError::SizeOverflow)
+    }
+
+    /// Attempts to allocate a block of memory like `Allocator::allocate`,
+    /// returning the cause of the failure if it does not succeed.
+    pub fn try_allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, MMapError> {
+        let layout = self.min_aligned(layout)?;
+
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+
+        // The memory block spans the pages holding `layout`, which are all
+        // usable. The guard pages and the alignment padding are mapped around
+        // them by `map_region`, so the size of the mapping released by
+        // `try_deallocate` can differ, but is always recomputed from the
+        // layout rather than from the length of the memory block.
+        let page_layout = self.page_layout(layout)?;
+        let usable_len = page_layout.size();
+
+        #[cfg(all(unix, feature = "std"))]
+        if let Some(owner) = self.thread_cache_owner(usable_len, layout.align()) {
+            if let Some(new_mapping) = thread_cache::take(owner, usable_len) {
+                // Cached mappings keep the contents they were freed with.
+                unsafe { ptr::write_bytes(new_mapping.as_ptr(), 0, usable_len) };
+
+                if let Some(stats) = &self.stats {
+                    stats.record_map(usable_len);
+                }
+
+                return Ok(NonNull::slice_from_raw_parts(new_mapping, usable_len));
+            }
+        }
+
+        // Over-aligning for transparent huge pages only changes the padding,
+        // not the pages of the memory block.
+        #[cfg(target_os = "linux")]
+        let layout = if self.thp_alignment && usable_len >= THP_THRESHOLD {
+            layout
+                .align_to(THP_THRESHOLD)
+                .map_err(|_| MMapError::SizeOverflow)?
+        } else {
+            layout
+        };
+
+        let new_mapping = self.map_region(usable_len, layout.align())?;
+
+        if let Some(stats) = &self.stats {
+            stats.record_map(usable_len);
+        }
+
+        Ok(NonNull::slice_from_raw_parts(new_mapping, usable_len))
+    }
+
+    /// Allocates a block of memory like `Allocator::allocate`, returning
+    /// `None` if it does not succeed.
+    ///
+    /// Layouts with an alignment larger than `max_supported_align`, or whose
+    /// mapping could not fit in the address space once padded for alignment
+    /// and surrounded by its guard pages, are rejected without making any
+    /// system call. Any other layout is attempted, and `None` is
+    /// also returned if the system runs out of memory.
+    pub fn try_allocate_opt(&self, layout: Layout) -> Option<NonNull<[u8]>> {
+        if layout.align() > self.max_supported_align() {
+            return None;
+        }
+
+        // The alignment is unbounded on Unix systems, so the layouts that
+        // cannot be satisfied are the ones whose padded mapping overflows.
+        #[cfg(unix)]
+        {
+            let layout = self.min_aligned(layout).ok()?;
+            if layout.size() != 0 {
+                let usable_len = self.page_layout(layout).ok()?.size();
+                self.padded_mapping_size(usable_len, layout.align()).ok()?;
+            }
+        }
+
+        self.try_allocate(layout).ok()
+    }
+
+    /// Attempts to deallocate the memory referenced by `ptr` like
+    /// `Allocator::deallocate`, returning the cause of the failure if it does
+    /// not succeed.
+    ///
+    /// Unmapping pages that are not mapped is not an error for `munmap`, so a
+    /// double free is not guaranteed to be detected.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    pub unsafe fn try_deallocate(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), MMapError> {
+        // Zero-sized allocations are never mapped.
+        if layout.size() == 0 {
+            return Ok(());
+        }
+
+        #[cfg(feature = "tags")]
+        tags::remove(ptr);
+
+        // `ptr` is assumed to be currently allocated, thus the memory it points
+        // to is currently mapped. Over-aligned mappings are trimmed when
+        // allocated, so the page containing `ptr` is always the mapping base.
+        // `ptr` itself is page-aligned, unless the memory block was allocated
+        // with `allocate_with_offset`.
+        //
+        // `layout.size()` fits the current memory block, so rounding it to the
+        // page size, together with the offset of `ptr` in the first page, gives
+        // the size of the current mapping. Huge page mappings require the full
+        // size to be passed to `munmap`, and so do the guard pages following
+        // the mapping.
+        let page_size = self.checked_page_size()?;
+        let page_offset = ptr.as_ptr() as usize & (page_size - 1);
+        let base = NonNull::new_unchecked(ptr.as_ptr().sub(page_offset));
+        let size = round_to_pages(layout.size() + page_offset, page_size)?;
+
+        #[cfg(all(unix, feature = "std"))]
+        if let Some(owner) = self.thread_cache_owner(size, page_size) {
+            // The memory block may have been made read-only, by
+            // `allocate_readonly_from` or `protect`, while the next allocation
+            // taking it from the cache writes to it. Its configured protection
+            // is restored first, and it is unmapped if that fails.
+            let restored = self
+                .protect_pages(base.as_ptr(), size, self.protection.to_prot())
+                .is_ok();
+
+            if restored {
+                if let Some(byte) = self.freed_fill() {
+                    scrub(base.as_ptr(), size, byte);
+                }
+            }
+
+            if restored && thread_cache::put(owner, base, size) {
+                if let Some(stats) = &self.stats {
+                    stats.record_unmap(size);
+                }
+
+                return Ok(());
+            }
+        }
+
+        self.unmap(base, size)?;
+
+        if let Some(stats) = &self.stats {
+            stats.record_unmap(size);
+        }
+
+        Ok(())
+    }
+
+    /// Deallocates the memory referenced by `ptr`, leaking it if it cannot be
+    /// unmapped.
+    ///
+    /// Used once the contents of an allocation have been moved to a new
+    /// mapping, where panicking would lose the new allocation as well.
+    ///
+    /// # Safety
+    ///
+    /// Same as `try_deallocate`.
+    unsafe fn munmap_or_leak(&self, ptr: NonNull<u8>, layout: Layout) {
+        if let Err(err) = self.try_deallocate(ptr, layout) {
+            report_unmap_failure(
+                "leaking a moved allocation",
+                err,
+                ptr.as_ptr(),
+                layout.size(),
+            );
+        }
+    }
+
+    /// Releases the pages of the mapping at `ptr` that are past
+    /// `new_page_layout`, keeping the mapping in place.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be the base of a mapping of `old_page_layout` made by this
+    /// allocator, and `new_page_layout` must not be larger.
+    #[cfg(unix)]
+    unsafe fn release_tail(
+        &self,
+        ptr: NonNull<u8>,
+        old_page_layout: Layout,
+        new_page_layout: Layout,
+    ) -> Result<NonNull<[u8]>, MMapError> {
+        // Unmap the pages at the end of the current mapping to avoid memory
+        // leaks. The first portion of the current mapping can then just be
+        // reused. If the mapping has guard pages, the first truncated pages
+        // become the new trailing guard, and the old guard is unmapped
+        // instead. Over-aligned mappings are trimmed when allocated, so `ptr`
+        // is always the base of the mapping, whatever its alignment.
+
+        let guard_size = self.guard_size()?;
+        let retained_area_size = new_page_layout.size();
+        let truncated_area_size = old_page_layout
+            .size()
+            .checked_sub(retained_area_size)
+            .ok_or(MMapError::ShrinkWouldGrow)?;
+
+        // The truncated area starts past the new trailing guard, which is
+        // within the old mapping and its own trailing guard.
+        let truncated_area_offset = retained_area_size
+            .checked_add(guard_size)
+            .ok_or(MMapError::SizeOverflow)?;
+        let truncated_area_ptr = ptr.as_ptr().add(truncated_area_offset);
+
+        if truncated_area_size > 0 {
+            if let Some(byte) = self.freed_fill() {
+                scrub(
+                    ptr.as_ptr().add(retained_area_size),
+                    truncated_area_size,
+                    byte,
+                );
+            }
+
+            // On Linux, the kernel can release the tail of the mapping by
+            // itself. This is not possible with guard pages, since the old
+            // trailing guard would be left behind.
+            #[cfg(target_os = "linux")]
+            if guard_size == 0 {
+                let res = self.mapper.remap(
+                    ptr.as_ptr().cast::<c_void>(),
+                    old_page_layout.size(),
+                    retained_area_size,
+                    0,
+                );
+                if let Ok(new_mapping) = res {
+                    // SAFETY: `mremap` is guaranteed to return a valid
+                    // pointer if it succeeds.
+                    let new_mapping = NonNull::new_unchecked(new_mapping.cast::<u8>());
+
+                    if let Some(stats) = &self.stats {
+                        stats.record_resize(old_page_layout.size(), retained_area_size);
+                    }
+
+                    return Ok(NonNull::slice_from_raw_parts(
+                        new_mapping,
+                        retained_area_size,
+                    ));
+                }
+            }
+
+            if guard_size > 0 {
+                self.protect_pages(
+                    ptr.as_ptr().add(retained_area_size),
+                    guard_size,
+                    libc::PROT_NONE,
+                )?;
+            }
+
+            // The retained area is still valid if the tail cannot be
+            // unmapped, so the tail is leaked rather than failing the shrink.
+            match self.unmap_pages(truncated_area_ptr, truncated_area_size) {
+                Ok(()) => {
+                    if let Some(stats) = &self.stats {
+                        stats.record_resize(old_page_layout.size(), retained_area_size);
+                    }
+                }
+                Err(err) => report_unmap_failure(
+                    "leaking the tail of a shrunk allocation",
+                    err,
+                    truncated_area_ptr,
+                    truncated_area_size,
+                ),
             }
         }
 
         Ok(NonNull::slice_from_raw_parts(ptr, retained_area_size))
     }
+
+    /// Maps `size` bytes of fresh memory from the configured backing, with the
+    /// configured protection, preferably at `hint`.
+    #[cfg(unix)]
+    fn map_pages(
+        &self,
+        hint: *mut c_void,
+        extra_flags: libc::c_int,
+        size: usize,
+    ) -> Result<NonNull<u8>, MMapError> {
+        let (fd, offset) = match &self.backing {
+            Source::Anonymous | Source::SharedAnonymous => (-1, 0),
+            Source::File { fd, offset, .. } => (*fd, claim_file_region(*fd, offset, size)?),
+        };
+
+        let new_mapping = unsafe {
+            self.mapper.map(
+                hint,
+                size,
+                self.protection.to_prot(),
+                self.map_flags() | extra_flags,
+                fd,
+                offset,
+            )
+        }
+        .map_err(MMapError::MmapFailed)?;
+
+        // SAFETY: `mmap` is guaranteed to return a valid pointer if it
+        // succeeds.
+        Ok(unsafe { NonNull::new_unchecked(new_mapping.cast::<u8>()) })
+    }
+
+    /// Returns the size of the guard area on each side of an allocation.
+    #[cfg(unix)]
+    fn guard_size(&self) -> Result<usize, MMapError> {
+        self.guard_pages
+            .checked_mul(self.checked_page_size()?)
+            .ok_or(MMapError::SizeOverflow)
+    }
+
+    /// Maps `size` bytes of fresh memory, aligned to at least `align`, and
+    /// surrounded by the configured guard pages.
+    ///
+    /// `size` must be a non-zero multiple of the page size.
+    #[cfg(unix)]
+    fn map_region(&self, size: usize, align: usize) -> Result<NonNull<u8>, MMapError> {
+        self.map_near(ptr::null_mut(), 0, size, align)
+    }
+
+    /// Returns the size of the mapping that `map_near` creates to hold `size`
+    /// bytes aligned to `align`, guard pages and alignment padding included,
+    /// along with the flags asking `mmap` to align it.
+    ///
+    /// Nothing is mapped, so this is also used to reject the layouts that
+    /// could never fit in the address space before any system call.
+    #[cfg(unix)]
+    fn padded_mapping_size(
+        &self,
+        size: usize,
+        align: usize,
+    ) -> Result<(usize, libc::c_int), MMapError> {
+        // Allocations are always aligned to the page size of the allocator,
+        // which is larger than the alignment of `mmap` if it is overridden.
+        let align = align.max(self.checked_page_size()?);
+        let page_size = self.mmap_align();
+        let guard_size = self.guard_size()?;
+
+        let region_size = guard_size
+            .checked_mul(2)
+            .and_then(|guards_size| guards_size.checked_add(size))
+            .ok_or(MMapError::SizeOverflow)?;
+
+        // `mmap` always returns page-aligned memory, so no trimming is needed
+        // in the common case. Some systems can also align the mapping by
+        // themselves, as long as the guard pages keep the allocation aligned.
+        // Otherwise, map enough pages to be sure that an aligned block of
+        // `size` bytes fits somewhere inside the mapping, then unmap what
+        // surrounds it.
+        let aligned_flags = if align > page_size && guard_size & (align - 1) == 0 {
+            map_aligned(align)
+        } else {
+            0
+        };
+        let padding_size = if aligned_flags != 0 {
+            0
+        } else {
+            align.saturating_sub(page_size)
+        };
+        let mapping_size = region_size
+            .checked_add(padding_size)
+            .ok_or(MMapError::AlignmentTooLarge)?;
+        if mapping_size > MAX_MAPPING_SIZE {
+            return Err(if region_size > MAX_MAPPING_SIZE {
+                MMapError::SizeOverflow
+            } else {
+                MMapError::AlignmentTooLarge
+            });
+        }
+
+        Ok((mapping_size, aligned_flags))
+    }
+
+    /// Same as `map_region`, but passes `hint` to `mmap` as the preferred
+    /// address of the mapping, with `extra_flags` on top of the configured
+    /// ones.
+    #[cfg(unix)]
+    fn map_near(
+        &self,
+        hint: *mut c_void,
+        extra_flags: libc::c_int,
+        size: usize,
+        align: usize,
+    ) -> Result<NonNull<u8>, MMapError> {
+        let (mapping_size, aligned_flags) = self.padded_mapping_size(size, align)?;
+        let align = align.max(self.checked_page_size()?);
+        let guard_size = self.guard_size()?;
+        let region_size = size + 2 * guard_size;
+        let mapping = self.map_pages(hint, extra_flags | aligned_flags, mapping_size)?;
+
+        // SAFETY: all the offsets computed below are within the mapping that
+        // was just created.
+        unsafe {
+            let head_size = mapping.as_ptr().add(guard_size).align_offset(align);
+            let tail_size = mapping_size - head_size - region_size;
+
+            if head_size > 0 {
+                self.unmap_pages(mapping.as_ptr(), head_size)?;
+            }
+            if tail_size > 0 {
+                self.unmap_pages(mapping.as_ptr().add(head_size + region_size), tail_size)?;
+            }
+
+            let region = mapping.as_ptr().add(head_size);
+
+            if guard_size > 0 {
+                let leading_guard = region;
+                let trailing_guard = region.add(guard_size + size);
+                let res = self
+                    .protect_pages(leading_guard, guard_size, libc::PROT_NONE)
+                    .and_then(|_| self.protect_pages(trailing_guard, guard_size, libc::PROT_NONE));
+                if let Err(err) = res {
+                    let _ = self.unmap_pages(region, region_size);
+                    return Err(err);
+                }
+            }
+
+            let allocation = region.add(guard_size);
+
+            // Binding fails if the node does not exist or is not allowed for
+            // this process.
+            #[cfg(target_os = "linux")]
+            if let Some(node) = self.numa_node {
+                if let Err(errno) = bind_to_node(allocation, size, node) {
+                    let _ = self.unmap_pages(region, region_size);
+                    return Err(MMapError::MbindFailed(errno));
+                }
+            }
+
+            #[cfg(target_os = "linux")]
+            if let Some(name) = self.name {
+                set_mapping_name(allocation, size, name);
+            }
+
+            // The advice covers the guard pages too, so that the whole region
+            // stays a single mapping.
+            #[cfg(target_os = "linux")]
+            if self.dontfork {
+                if let Err(err) = self.advise_dontfork(region, region_size) {
+                    let _ = self.unmap_pages(region, region_size);
+                    return Err(err);
+                }
+            }
+
+            // The hint is best-effort, and is only given before the pages
+            // are faulted in by locking them.
+            #[cfg(target_os = "linux")]
+            if (self.thp || self.thp_alignment) && size >= THP_THRESHOLD {
+                let _ = sys::madvise(allocation.cast::<c_void>(), size, libc::MADV_HUGEPAGE);
+            }
+
+            // Locking fails if `RLIMIT_MEMLOCK` would be exceeded. The whole
+            // mapping is then released to avoid leaking it.
+            if self.locked {
+                if let Err(errno) = sys::mlock(allocation.cast::<c_void>(), size) {
+                    let _ = self.unmap_pages(region, region_size);
+                    return Err(MMapError::MlockFailed(errno));
+                }
+            }
+
+            Ok(NonNull::new_unchecked(allocation))
+        }
+    }
+
+    /// Unmaps the `size` bytes at `ptr` together with their guard pages.
+    ///
+    /// `ptr` must have been returned by `map_region`, and `size` must be the
+    /// current size of the mapping.
+    #[cfg(unix)]
+    unsafe fn unmap(&self, ptr: NonNull<u8>, size: usize) -> Result<(), MMapError> {
+        if let Some(byte) = self.freed_fill() {
+            scrub(ptr.as_ptr(), size, byte);
+        }
+
+        // The guard size was already computed successfully by `map_region`.
+        let guard_size = self.guard_size()?;
+
+        self.unmap_pages(ptr.as_ptr().sub(guard_size), size + 2 * guard_size)
+    }
+
+    /// Keeps the `size` bytes at `ptr` out of the child processes forked
+    /// afterwards with `MADV_DONTFORK`.
+    #[cfg(target_os = "linux")]
+    unsafe fn advise_dontfork(&self, ptr: *mut u8, size: usize) -> Result<(), MMapError> {
+        sys::madvise(ptr.cast::<c_void>(), size, libc::MADV_DONTFORK)
+            .map_err(MMapError::MadviseFailed)
+    }
+
+    /// Unmaps the `size` bytes at `ptr` through the mapper.
+    #[cfg(unix)]
+    unsafe fn unmap_pages(&self, ptr: *mut u8, size: usize) -> Result<(), MMapError> {
+        self.mapper
+            .unmap(ptr.cast::<c_void>(), size)
+            .map_err(MMapError::MunmapFailed)
+    }
+
+    /// Changes the protection of the `size` bytes at `ptr` through the
+    /// mapper.
+    #[cfg(unix)]
+    unsafe fn protect_pages(
+        &self,
+        ptr: *mut u8,
+        size: usize,
+        prot: libc::c_int,
+    ) -> Result<(), MMapError> {
+        self.mapper
+            .protect(ptr.cast::<c_void>(), size, prot)
+            .map_err(MMapError::MprotectFailed)
+    }
+}
+
+/// Returns the size of a regular page, querying the system only the first time
+/// it is needed.
+fn cached_page_size() -> usize {
+    static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+    // Concurrent callers may all query the system, which is harmless since
+    // they get the same value.
+    let size = PAGE_SIZE.load(Ordering::Relaxed);
+    if size != 0 {
+        return size;
+    }
+
+    let size = page_size::get();
+    debug_assert!(
+        size.is_power_of_two(),
+        "the system page size must be a power of two"
+    );
+    PAGE_SIZE.store(size, Ordering::Relaxed);
+    size
+}
+
+/// Names the anonymous mapping `[ptr, ptr + size)` after `name`, ignoring any
+/// failure.
+#[cfg(target_os = "linux")]
+unsafe fn set_mapping_name(ptr: *mut u8, size: usize, name: &str) {
+    // The kernel expects a NUL-terminated string, which `name` is not.
+    let mut buf = [0u8; MAX_VMA_NAME_LEN];
+    let len = name.len().min(MAX_VMA_NAME_LEN - 1);
+    buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+    let _ = sys::set_vma_anon_name(ptr.cast::<c_void>(), size, buf.as_ptr().cast());
+}
+
+/// Returns the flags asking the system to align a new mapping to `align`,
+/// which must be a power of two, or 0 if it cannot.
+#[cfg(any(target_os = "freebsd", target_os = "netbsd"))]
+fn map_aligned(align: usize) -> libc::c_int {
+    (align.trailing_zeros() as libc::c_int) << libc::MAP_ALIGNMENT_SHIFT
+}
+#[cfg(all(unix, not(any(target_os = "freebsd", target_os = "netbsd"))))]
+fn map_aligned(_align: usize) -> libc::c_int {
+    0
+}
+
+/// Largest size of a mapping.
+///
+/// No object can be larger than `isize::MAX` bytes, so neither can the
+/// mappings holding them, guard pages and padding included.
+const MAX_MAPPING_SIZE: usize = isize::MAX as usize;
+
+/// Rounds `size` up to a multiple of `page_size`.
+fn round_to_pages(size: usize, page_size: usize) -> Result<usize, MMapError> {
+    size.checked_add(page_size - 1)
+        .map(|size| size & !(page_size - 1))
+        .filter(|&size| size <= MAX_MAPPING_SIZE)
+        .ok_or(MMapError::SizeOverflow)
+}
+
+/// Claims the next `size` bytes of the file `fd` from `cursor`, returning
+/// their offset.
+///
+/// `mmap` only maps whole pages of the file, so the region starts at the first
+/// page boundary past `cursor`. Regular files must be large enough to hold the
+/// region, counting the partial page at their end, since accessing the pages
+/// past it would raise `SIGBUS`. Other files, such as devices, report no
+/// meaningful size and are never checked. Every mapping gets its own region of
+/// the file, even if `mmap` fails afterwards.
+#[cfg(unix)]
+fn claim_file_region(
+    fd: libc::c_int,
+    cursor: &AtomicU64,
+    size: usize,
+) -> Result<libc::off_t, MMapError> {
+    let page_size = cached_page_size() as u64;
+    let stat = sys::fstat(fd).map_err(MMapError::FstatFailed)?;
+    let file_end = if stat.st_mode & libc::S_IFMT == libc::S_IFREG {
+        let file_size = u64::try_from(stat.st_size).unwrap_or(0);
+        Some(file_size.saturating_add(page_size - 1) & !(page_size - 1))
+    } else {
+        None
+    };
+
+    let overflow = MMapError::MmapFailed(libc::EOVERFLOW);
+    let mut region = Err(MMapError::FileTooSmall);
+    let _ = cursor.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+        region = current
+            .checked_add(page_size - 1)
+            .map(|current| current & !(page_size - 1))
+            .and_then(|start| Some((start, start.checked_add(size as u64)?)))
+            .ok_or(overflow);
+
+        match region {
+            Ok((_, end)) if file_end.is_none_or(|file_end| end <= file_end) => Some(end),
+            Ok(_) => {
+                region = Err(MMapError::FileTooSmall);
+                None
+            }
+            Err(_) => None,
+        }
+    });
+    let (start, _) = region?;
+
+    libc::off_t::try_from(start).map_err(|_| overflow)
+}
+
+/// Returns an empty memory block suitably aligned for `layout`, used for
+/// zero-sized allocations.
+fn dangling(layout: Layout) -> NonNull<[u8]> {
+    // SAFETY: alignments are never zero.
+    let ptr = unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())) };
+    NonNull::slice_from_raw_parts(ptr, 0)
+}
+
+/// Prints why unmapping the `size` bytes at `ptr` failed while `what`, in
+/// debug builds.
+///
+/// The `errno` of a failed `munmap` was captured right after the call, so it
+/// is reported together with the range that could not be unmapped.
+#[cold]
+fn report_unmap_failure(what: &str, err: MMapError, ptr: *mut u8, size: usize) {
+    #[cfg(all(feature = "std", debug_assertions))]
+    match err {
+        MMapError::MunmapFailed(errno) => std::eprintln!(
+            "mmap-allocator: {}: munmap failed (errno={}, ptr={:p}, size={})",
+            what,
+            errno,
+            ptr,
+            size
+        ),
+        err => std::eprintln!(
+            "mmap-allocator: {}: {} (ptr={:p}, size={})",
+            what,
+            err,
+            ptr,
+            size
+        ),
+    }
+    #[cfg(not(all(feature = "std", debug_assertions)))]
+    let _ = (what, err, ptr, size);
+}
+
+/// Handles the failure to deallocate the `size` bytes at `ptr`.
+///
+/// `Allocator::deallocate` cannot report errors, and unwinding out of it would
+/// unwind out of `Drop` implementations as well, so the process is aborted.
+/// With the `no-panic-dealloc` feature, the memory is leaked instead, for
+/// contexts where terminating is not acceptable either. Debug builds print the
+/// cause of the failure first.
+#[cold]
+fn deallocation_failed(err: MMapError, ptr: NonNull<u8>, size: usize) {
+    #[cfg(not(feature = "no-panic-dealloc"))]
+    report_unmap_failure("deallocation failed", err, ptr.as_ptr(), size);
+    #[cfg(feature = "no-panic-dealloc")]
+    report_unmap_failure("leaking a deallocated allocation", err, ptr.as_ptr(), size);
+
+    #[cfg(all(feature = "std", not(feature = "no-panic-dealloc")))]
+    std::process::abort();
+    #[cfg(all(not(feature = "std"), not(feature = "no-panic-dealloc")))]
+    unsafe {
+        libc::abort()
+    }
+}
+
+/// Overwrites the `size` bytes at `ptr` with `byte`, in a way that cannot be
+/// optimized out.
+///
+/// `ptr` must be page-aligned and `size` must be a multiple of the page size.
+unsafe fn scrub(ptr: *mut u8, size: usize, byte: u8) {
+    let pattern = usize::from_ne_bytes([byte; mem::size_of::<usize>()]);
+    let words = ptr.cast::<usize>();
+    for i in 0..size / mem::size_of::<usize>() {
+        ptr::write_volatile(words.add(i), pattern);
+    }
+}
+
+impl Default for MMapAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Changes the protection of the pages overlapping `[ptr, ptr + size)`.
+#[cfg(unix)]
+unsafe fn protect_pages(ptr: *mut u8, size: usize, prot: libc::c_int) -> Result<(), MMapError> {
+    sys::mprotect(ptr.cast::<c_void>(), size, prot).map_err(MMapError::MprotectFailed)
+}
+
+/// Binds the pages overlapping `[ptr, ptr + size)` to the NUMA node `node`.
+#[cfg(target_os = "linux")]
+unsafe fn bind_to_node(ptr: *mut u8, size: usize, node: u32) -> Result<(), i32> {
+    const BITS: usize = libc::c_ulong::BITS as usize;
+
+    let node = node as usize;
+    if node >= MAX_NUMA_NODES {
+        return Err(libc::EINVAL);
+    }
+
+    let mut node_mask = [0 as libc::c_ulong; MAX_NUMA_NODES / BITS];
+    node_mask[node / BITS] |= 1 << (node % BITS);
+
+    // The kernel ignores the last bit of the mask, so one more node than the
+    // actual size of the mask has to be passed.
+    sys::mbind(
+        ptr.cast::<c_void>(),
+        size,
+        MPOL_BIND,
+        node_mask.as_ptr(),
+        (MAX_NUMA_NODES + 1) as libc::c_ulong,
+        0,
+    )
+}
+
+/// Unmaps the pages overlapping `[ptr, ptr + size)`.
+#[cfg(unix)]
+unsafe fn unmap_pages(ptr: *mut u8, size: usize) -> Result<(), MMapError> {
+    sys::munmap(ptr.cast::<c_void>(), size).map_err(MMapError::MunmapFailed)
+}
+
+#[cfg(unix)]
+unsafe impl<M: MemoryMapper> Allocator for GenericMMapAllocator<M> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let res = self.try_allocate(layout);
+        trace!(
+            "allocate(size={}, align={}) -> {}",
+            layout.size(),
+            layout.align(),
+            crate::trace::Outcome(&res)
+        );
+        Ok(res?)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        trace!("deallocate(ptr={:p}, size={})", ptr, layout.size());
+        if let Err(err) = self.try_deallocate(ptr, layout) {
+            deallocation_failed(err, ptr, layout.size());
+        }
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = self.allocate(layout)?;
+
+        if !self.maps_zeroed_memory() {
+            if !self.protection.contains(Protection::WRITE) {
+                unsafe { self.deallocate(new_ptr.as_non_null_ptr(), layout) };
+                return Err(AllocError);
+            }
+
+            // SAFETY: the new mapping is writable for its whole length.
+            unsafe { ptr::write_bytes(new_ptr.as_mut_ptr(), 0, new_ptr.len()) };
+        }
+
+        Ok(new_ptr)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // `grow_report` checks that the size does not decrease.
+        let res = self
+            .resize(ptr, old_layout, new_layout)
+            .map(|resize| resize.ptr);
+        trace!(
+            "grow(ptr={:p}, old_size={}, new_size={}) -> {}",
+            ptr,
+            old_layout.size(),
+            new_layout.size(),
+            crate::trace::Outcome(&res)
+        );
+        res
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // When growing on the same page, the new memory area is not required to
+        // be zeroed because it falls within the size returned for the old
+        // allocation, which is always page-aligned.
+        let new_ptr = self.grow(ptr, old_layout, new_layout)?;
+
+        // File mappings start with the contents of the file, so the memory
+        // past the old allocation has to be cleared explicitly. File mappings
+        // only grow by moving to a new mapping, which requires them to be
+        // writable.
+        if !self.maps_zeroed_memory() {
+            let old_page_size = self.mapping_size(old_layout)?;
+            if new_ptr.len() > old_page_size {
+                ptr::write_bytes(
+                    new_ptr.as_mut_ptr().add(old_page_size),
+                    0,
+                    new_ptr.len() - old_page_size,
+                );
+            }
+        }
+
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
+        );
+
+        let res = self
+            .resize(ptr, old_layout, new_layout)
+            .map(|resize| resize.ptr);
+        trace!(
+            "shrink(ptr={:p}, old_size={}, new_size={}) -> {}",
+            ptr,
+            old_layout.size(),
+            new_layout.size(),
+            crate::trace::Outcome(&res)
+        );
+        res
+    }
 }
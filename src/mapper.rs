@@ -0,0 +1,129 @@
+//! Operations on the address space made by the allocator.
+//!
+//! `GenericMMapAllocator` performs every mapping operation it relies on for
+//! its bookkeeping through a `MemoryMapper`, which makes it possible to run it
+//! on top of a simulated address space, for example in unit tests.
+
+#[cfg(unix)]
+use core::ffi::c_void;
+
+#[cfg(unix)]
+use crate::sys;
+
+/// Layer creating and releasing the mappings of a `GenericMMapAllocator`.
+///
+/// Every method mirrors the system call it is named after, taking the same
+/// arguments and returning `errno` on failure. Only `map` chooses addresses,
+/// so a simulated address space only needs to hand out page-aligned ranges
+/// of memory that stay valid until they are unmapped. The hints and flags
+/// passed to `map` can be ignored, except for `MAP_FIXED_NOREPLACE`, which
+/// must fail if the range is not exactly at the hint.
+///
+/// The other operations of the allocator, such as `madvise`, `mlock` or
+/// `mbind`, are always issued to the system. Options relying on them cannot
+/// be used with a simulated address space.
+///
+/// On Windows, the allocator always uses `VirtualAlloc`, so this trait has
+/// no methods there.
+///
+/// # Safety
+///
+/// `map` and `remap` must return ranges of at least the requested length
+/// that are valid for the requested protection and do not overlap any range
+/// still mapped. `unmap` and `protect` must act on the whole requested range
+/// whenever they succeed.
+pub unsafe trait MemoryMapper {
+    /// Maps `len` bytes like `mmap`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `mmap`.
+    #[cfg(unix)]
+    unsafe fn map(
+        &self,
+        addr: *mut c_void,
+        len: usize,
+        prot: libc::c_int,
+        flags: libc::c_int,
+        fd: libc::c_int,
+        offset: libc::off_t,
+    ) -> Result<*mut c_void, i32>;
+
+    /// Unmaps the `len` bytes at `addr` like `munmap`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `munmap`.
+    #[cfg(unix)]
+    unsafe fn unmap(&self, addr: *mut c_void, len: usize) -> Result<(), i32>;
+
+    /// Resizes or moves the mapping at `addr` like `mremap`.
+    ///
+    /// This is only attempted on Linux. The default implementation fails
+    /// with `ENOSYS`, in which case the allocator falls back to mapping new
+    /// memory and copying the contents, or to unmapping the tail pages.
+    ///
+    /// # Safety
+    ///
+    /// Same as `mremap`.
+    #[cfg(unix)]
+    unsafe fn remap(
+        &self,
+        addr: *mut c_void,
+        old_len: usize,
+        new_len: usize,
+        flags: libc::c_int,
+    ) -> Result<*mut c_void, i32> {
+        let _ = (addr, old_len, new_len, flags);
+        Err(libc::ENOSYS)
+    }
+
+    /// Changes the protection of the `len` bytes at `addr` like
+    /// `mprotect`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `mprotect`.
+    #[cfg(unix)]
+    unsafe fn protect(&self, addr: *mut c_void, len: usize, prot: libc::c_int) -> Result<(), i32>;
+}
+
+/// `MemoryMapper` issuing the system calls, used by `MMapAllocator`.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SystemMapper;
+
+unsafe impl MemoryMapper for SystemMapper {
+    #[cfg(unix)]
+    unsafe fn map(
+        &self,
+        addr: *mut c_void,
+        len: usize,
+        prot: libc::c_int,
+        flags: libc::c_int,
+        fd: libc::c_int,
+        offset: libc::off_t,
+    ) -> Result<*mut c_void, i32> {
+        sys::mmap(addr, len, prot, flags, fd, offset)
+    }
+
+    #[cfg(unix)]
+    unsafe fn unmap(&self, addr: *mut c_void, len: usize) -> Result<(), i32> {
+        sys::munmap(addr, len)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn remap(
+        &self,
+        addr: *mut c_void,
+        old_len: usize,
+        new_len: usize,
+        flags: libc::c_int,
+    ) -> Result<*mut c_void, i32> {
+        sys::mremap(addr, old_len, new_len, flags)
+    }
+
+    #[cfg(unix)]
+    unsafe fn protect(&self, addr: *mut c_void, len: usize, prot: libc::c_int) -> Result<(), i32> {
+        sys::mprotect(addr, len, prot)
+    }
+}
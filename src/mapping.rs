@@ -0,0 +1,116 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    fmt, mem,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use crate::{GenericMMapAllocator, MemoryMapper, Protection, SystemMapper};
+
+/// Memory block allocated by `MMapAllocator::map`, deallocated when dropped.
+///
+/// The mapping dereferences to the whole memory block, which spans whole
+/// pages and is thus usually larger than the layout it was allocated for.
+pub struct Mapping<'a, M: MemoryMapper = SystemMapper> {
+    allocator: &'a GenericMMapAllocator<M>,
+    ptr: NonNull<[u8]>,
+    layout: Layout,
+}
+
+impl<M: MemoryMapper> GenericMMapAllocator<M> {
+    /// Allocates a memory block fitting `layout`, which is deallocated when
+    /// the returned `Mapping` is dropped.
+    ///
+    /// This is a safe alternative to `allocate` and `deallocate`, which
+    /// always uses the same layout for both. The memory must be readable and
+    /// writable, otherwise `AllocError` is returned.
+    pub fn map(&self, layout: Layout) -> Result<Mapping<'_, M>, AllocError> {
+        if !self
+            .protection
+            .contains(Protection::READ | Protection::WRITE)
+        {
+            return Err(AllocError);
+        }
+
+        Ok(Mapping {
+            allocator: self,
+            ptr: self.allocate(layout)?,
+            layout,
+        })
+    }
+}
+
+impl<M: MemoryMapper> Mapping<'_, M> {
+    /// Returns a pointer to the start of the memory block.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_mut_ptr()
+    }
+
+    /// Returns the size of the memory block.
+    pub fn len(&self) -> usize {
+        self.ptr.len()
+    }
+
+    /// Returns whether the memory block is empty, which only happens for
+    /// zero-sized layouts.
+    pub fn is_empty(&self) -> bool {
+        self.ptr.len() == 0
+    }
+
+    /// Returns the layout the memory block was allocated for.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Consumes the mapping without deallocating it, returning the memory
+    /// block and its layout.
+    ///
+    /// The memory block can later be released with `deallocate` and the
+    /// returned layout.
+    pub fn leak(self) -> (NonNull<[u8]>, Layout) {
+        let parts = (self.ptr, self.layout);
+        mem::forget(self);
+        parts
+    }
+}
+
+impl<M: MemoryMapper> Deref for Mapping<'_, M> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the memory block stays allocated, readable and writable
+        // until the mapping is dropped.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<M: MemoryMapper> DerefMut for Mapping<'_, M> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: same as `deref`, and the mapping is borrowed mutably.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<M: MemoryMapper> fmt::Debug for Mapping<'_, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mapping")
+            .field("ptr", &self.ptr)
+            .field("layout", &self.layout)
+            .finish()
+    }
+}
+
+impl<M: MemoryMapper> Drop for Mapping<'_, M> {
+    fn drop(&mut self) {
+        // SAFETY: the memory block was allocated by `map` with this layout.
+        unsafe {
+            self.allocator
+                .deallocate(self.ptr.as_non_null_ptr(), self.layout)
+        };
+    }
+}
+
+// The mapping behaves like an owned allocation, as long as the allocator can
+// be shared with the thread it is sent to.
+unsafe impl<M: MemoryMapper + Sync> Send for Mapping<'_, M> {}
+unsafe impl<M: MemoryMapper + Sync> Sync for Mapping<'_, M> {}
@@ -0,0 +1,181 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ptr::{self, NonNull},
+};
+use std::sync::Mutex;
+use std::vec::Vec;
+
+use crate::{cached_page_size, scrub, MMapAllocator};
+
+/// Allocator recycling the small mappings of an `MMapAllocator`.
+///
+/// Deallocated mappings of at most `max_pages` pages are kept in a free list
+/// instead of being unmapped, and handed out again by the next allocation of
+/// the same size. Only the mappings that do not fit in the free list, which
+/// holds at most `capacity` of them, are unmapped. The free list is released
+/// when the pool is dropped. This requires the `std` feature.
+#[derive(Debug)]
+pub struct PagePool {
+    mmap: MMapAllocator,
+    max_size: usize,
+    capacity: usize,
+    free: Mutex<Vec<FreeMapping>>,
+}
+
+/// Mapping kept in the free list of a `PagePool`.
+#[derive(Debug)]
+struct FreeMapping {
+    ptr: NonNull<u8>,
+    size: usize,
+}
+
+// Free mappings are owned by the pool, and can be reused by any thread.
+unsafe impl Send for FreeMapping {}
+
+impl PagePool {
+    /// Creates a pool recycling up to `capacity` mappings of at most
+    /// `max_pages` pages, mapped with regular anonymous pages.
+    pub fn new(max_pages: usize, capacity: usize) -> Self {
+        Self::with_allocator(MMapAllocator::new(), max_pages, capacity)
+    }
+
+    /// Creates a pool recycling up to `capacity` mappings of at most
+    /// `max_pages` pages, mapped with `mmap`.
+    pub fn with_allocator(mmap: MMapAllocator, max_pages: usize, capacity: usize) -> Self {
+        Self {
+            max_size: max_pages.saturating_mul(mmap.page_size()),
+            mmap,
+            capacity,
+            free: Mutex::new(Vec::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns the allocator used to map new memory.
+    pub fn mmap_allocator(&self) -> &MMapAllocator {
+        &self.mmap
+    }
+
+    /// Returns the number of mappings currently kept in the free list.
+    pub fn free_mappings(&self) -> usize {
+        self.free_list().len()
+    }
+
+    /// Returns the free list, even if another thread panicked while holding
+    /// it, as it is always left in a consistent state.
+    fn free_list(&self) -> std::sync::MutexGuard<'_, Vec<FreeMapping>> {
+        self.free.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the size of the mapping backing `layout`, if such mappings are
+    /// recycled.
+    fn recycled_size(&self, layout: Layout) -> Option<usize> {
+        // Mappings are only guaranteed to be page-aligned.
+        if layout.size() == 0 || layout.align() > cached_page_size() {
+            return None;
+        }
+
+        let size = self.mmap.mapping_size(layout).ok()?;
+        (size <= self.max_size).then_some(size)
+    }
+
+    /// Takes a free mapping of `size` bytes out of the free list.
+    fn take(&self, size: usize) -> Option<NonNull<[u8]>> {
+        let mut free = self.free_list();
+
+        // The most recently freed mappings are the most likely to be cached.
+        let index = free.iter().rposition(|mapping| mapping.size == size)?;
+        let mapping = free.swap_remove(index);
+
+        Some(NonNull::slice_from_raw_parts(mapping.ptr, mapping.size))
+    }
+}
+
+unsafe impl Allocator for PagePool {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if let Some(new_ptr) = self.recycled_size(layout).and_then(|size| self.take(size)) {
+            return Ok(new_ptr);
+        }
+
+        self.mmap.allocate(layout)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if let Some(new_ptr) = self.recycled_size(layout).and_then(|size| self.take(size)) {
+            // SAFETY: the recycled mapping is valid for writes of its whole
+            // length.
+            unsafe { ptr::write_bytes(new_ptr.as_mut_ptr(), 0, new_ptr.len()) };
+            return Ok(new_ptr);
+        }
+
+        self.mmap.allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if let Some(size) = self.recycled_size(layout) {
+            let mut free = self.free_list();
+            if free.len() < self.capacity {
+                // Recycled memory should not leak secrets either, and should
+                // reveal its uses after being freed.
+                if let Some(byte) = self.mmap.freed_fill() {
+                    scrub(ptr.as_ptr(), size, byte);
+                }
+
+                free.push(FreeMapping { ptr, size });
+                return;
+            }
+        }
+
+        self.mmap.deallocate(ptr, layout)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // Recycled mappings are regular mappings of `mmap`.
+        self.mmap.grow(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // `mmap` expects the end of the last page to be untouched, which does
+        // not hold for recycled mappings.
+        let new_ptr = self.mmap.grow(ptr, old_layout, new_layout)?;
+        ptr::write_bytes(
+            new_ptr.as_mut_ptr().add(old_layout.size()),
+            0,
+            new_layout.size() - old_layout.size(),
+        );
+
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.mmap.shrink(ptr, old_layout, new_layout)
+    }
+}
+
+impl Drop for PagePool {
+    fn drop(&mut self) {
+        let free = self.free.get_mut().unwrap_or_else(|err| err.into_inner());
+        for mapping in free.drain(..) {
+            // SAFETY: the mapping was allocated by `mmap` with this size, and
+            // any size with the same number of pages fits it.
+            unsafe {
+                let layout = Layout::from_size_align_unchecked(mapping.size, 1);
+                self.mmap.deallocate(mapping.ptr, layout);
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Memory protection of the pages backing an allocation.
+    ///
+    /// Some platforms enforce W^X policies and refuse to map memory that is
+    /// both writable and executable. On those platforms, requesting
+    /// `WRITE | EXEC` makes allocating fail with `AllocError`.
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+    pub struct Protection: u32 {
+        /// The pages can be read.
+        const READ = 1 << 0;
+        /// The pages can be written.
+        const WRITE = 1 << 1;
+        /// The pages can be executed.
+        const EXEC = 1 << 2;
+    }
+}
+
+impl Protection {
+    /// Returns the equivalent `PROT_*` flags for `mmap` and `mprotect`.
+    #[cfg(unix)]
+    pub(crate) fn to_prot(self) -> libc::c_int {
+        let mut prot = libc::PROT_NONE;
+        if self.contains(Protection::READ) {
+            prot |= libc::PROT_READ;
+        }
+        if self.contains(Protection::WRITE) {
+            prot |= libc::PROT_WRITE;
+        }
+        if self.contains(Protection::EXEC) {
+            prot |= libc::PROT_EXEC;
+        }
+        prot
+    }
+}
+
+impl Default for Protection {
+    fn default() -> Self {
+        Protection::READ | Protection::WRITE
+    }
+}
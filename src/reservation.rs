@@ -0,0 +1,349 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ffi::c_void,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::{protect_pages, round_to_pages, sys, unmap_pages, MMapAllocator, MMapError, ANON_FLAG};
+
+/// Range of virtual memory reserved by `MMapAllocator::reserve`.
+///
+/// The range is initially inaccessible, and its pages are made readable and
+/// writable on demand with `commit`. The whole range is unmapped when the
+/// reservation is dropped.
+#[derive(Debug)]
+pub struct Reservation {
+    ptr: NonNull<u8>,
+    size: usize,
+    page_size: usize,
+}
+
+impl MMapAllocator {
+    /// Reserves `size` bytes of virtual memory, without making it accessible.
+    ///
+    /// `size` is rounded to the page size of the allocator. The reservation is
+    /// always backed by anonymous memory, independently of the configured
+    /// backing, and only the huge page setting of the allocator is applied to
+    /// it.
+    pub fn reserve(&self, size: usize) -> Result<Reservation, AllocError> {
+        let page_size = self.checked_page_size()?;
+        let size = round_to_pages(size, page_size)?;
+
+        #[allow(unused_mut)]
+        let mut flags = libc::MAP_PRIVATE | ANON_FLAG;
+        #[cfg(target_os = "linux")]
+        if let Some(huge_pages) = self.huge_pages {
+            flags |= huge_pages.map_flags();
+        }
+
+        let ptr = unsafe { sys::mmap(ptr::null_mut(), size, libc::PROT_NONE, flags, -1, 0) }
+            .map_err(MMapError::MmapFailed)?;
+
+        Ok(Reservation {
+            // SAFETY: `mmap` is guaranteed to return a valid pointer if it
+            // succeeds.
+            ptr: unsafe { NonNull::new_unchecked(ptr.cast::<u8>()) },
+            size,
+            page_size,
+        })
+    }
+
+    /// Reserves a region of `max` pages growing one page at a time, whose
+    /// first `initial` pages are readable and writable.
+    ///
+    /// The page right past the committed pages is always inaccessible, so
+    /// that accessing it faults. A `SIGSEGV` handler can then commit it with
+    /// `GrowableRegion::commit_next` and resume the faulting access. An extra
+    /// guard page is reserved past the last page, so that the region keeps
+    /// faulting once all of its pages are committed.
+    pub fn allocate_growable(
+        &self,
+        initial: usize,
+        max: usize,
+    ) -> Result<GrowableRegion, AllocError> {
+        if initial > max {
+            return Err(AllocError);
+        }
+
+        let page_size = self.checked_page_size()?;
+        let size = max
+            .checked_add(1)
+            .and_then(|pages| pages.checked_mul(page_size))
+            .ok_or(MMapError::SizeOverflow)?;
+        let reservation = self.reserve(size)?;
+        reservation.commit(0, initial * reservation.page_size)?;
+
+        Ok(GrowableRegion {
+            reservation,
+            committed: AtomicUsize::new(initial),
+            max,
+        })
+    }
+}
+
+impl Reservation {
+    /// Returns a pointer to the start of the reserved range.
+    pub fn as_ptr(&self) -> NonNull<u8> {
+        self.ptr
+    }
+
+    /// Returns the size of the reserved range.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Makes the pages overlapping `[offset, offset + len)` readable and
+    /// writable.
+    ///
+    /// Pages that were already committed keep their contents. If the range
+    /// exceeds the reservation, `AllocError` is returned.
+    pub fn commit(&self, offset: usize, len: usize) -> Result<(), AllocError> {
+        let end = offset
+            .checked_add(len)
+            .filter(|end| *end <= self.size)
+            .ok_or(AllocError)?;
+
+        let start = offset & !(self.page_size - 1);
+        let end = (end + self.page_size - 1) & !(self.page_size - 1);
+        if start == end {
+            return Ok(());
+        }
+
+        // SAFETY: the range was checked to be within the reservation.
+        unsafe {
+            protect_pages(
+                self.ptr.as_ptr().add(start),
+                end - start,
+                libc::PROT_READ | libc::PROT_WRITE,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes the pages within `[offset, offset + len)` inaccessible again,
+    /// releasing their contents.
+    ///
+    /// `offset` must be page-aligned, and the range must be within the
+    /// reservation.
+    fn decommit(&self, offset: usize, len: usize) -> Result<(), MMapError> {
+        if len == 0 {
+            return Ok(());
+        }
+
+        // SAFETY: the range is within the reservation.
+        unsafe {
+            let ptr = self.ptr.as_ptr().add(offset);
+            protect_pages(ptr, len, libc::PROT_NONE)?;
+            sys::madvise(ptr.cast::<c_void>(), len, libc::MADV_DONTNEED)
+                .map_err(MMapError::MadviseFailed)
+        }
+    }
+
+    /// Returns the size of `len` bytes rounded to whole pages.
+    fn page_rounded(&self, len: usize) -> usize {
+        (len + self.page_size - 1) & !(self.page_size - 1)
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        // The reservation cannot be used afterwards, so it is leaked if it
+        // cannot be unmapped.
+        let _ = unsafe { unmap_pages(self.ptr.as_ptr(), self.size) };
+    }
+}
+
+// The reservation behaves like an owned allocation.
+unsafe impl Send for Reservation {}
+unsafe impl Sync for Reservation {}
+
+/// Region reserved by `MMapAllocator::allocate_growable`, committed one page
+/// at a time.
+///
+/// The committed pages start at the beginning of the region, and are followed
+/// by an inaccessible guard page. The whole region is unmapped when it is
+/// dropped.
+#[derive(Debug)]
+pub struct GrowableRegion {
+    reservation: Reservation,
+    /// Number of pages currently committed.
+    committed: AtomicUsize,
+    /// Number of pages that can be committed.
+    max: usize,
+}
+
+impl GrowableRegion {
+    /// Returns a pointer to the start of the region.
+    pub fn as_ptr(&self) -> NonNull<u8> {
+        self.reservation.ptr
+    }
+
+    /// Returns the number of bytes currently readable and writable.
+    pub fn committed_size(&self) -> usize {
+        self.committed.load(Ordering::Acquire) * self.reservation.page_size
+    }
+
+    /// Returns the number of bytes that the region can grow to.
+    pub fn max_size(&self) -> usize {
+        self.max * self.reservation.page_size
+    }
+
+    /// Returns a pointer to the guard page right past the committed pages.
+    pub fn guard_page(&self) -> NonNull<u8> {
+        // SAFETY: the guard page is always within the reservation.
+        unsafe { NonNull::new_unchecked(self.reservation.ptr.as_ptr().add(self.committed_size())) }
+    }
+
+    /// Makes the guard page containing `ptr` readable and writable, so that
+    /// the following page becomes the guard page.
+    ///
+    /// `ptr` is typically the faulting address received by a `SIGSEGV`
+    /// handler. If it is not within the current guard page, or all the pages
+    /// of the region are already committed, `AllocError` is returned and the
+    /// fault should be handled otherwise. If the page was committed by
+    /// another thread in the meantime, this succeeds without doing anything,
+    /// so that the faulting access can be retried. This only makes a
+    /// `mprotect` call, so it is safe to call from a signal handler.
+    pub fn commit_next(&self, ptr: NonNull<u8>) -> Result<(), AllocError> {
+        let page_size = self.reservation.page_size;
+        let committed = self.committed.load(Ordering::Acquire);
+        let offset = (ptr.as_ptr() as usize).wrapping_sub(self.reservation.ptr.as_ptr() as usize);
+
+        if offset < committed * page_size {
+            return Ok(());
+        }
+        if committed == self.max || offset >= (committed + 1) * page_size {
+            return Err(AllocError);
+        }
+
+        // Committing the same page twice is harmless, so concurrent calls
+        // only need to agree on the number of committed pages.
+        self.reservation.commit(committed * page_size, page_size)?;
+        let _ = self.committed.compare_exchange(
+            committed,
+            committed + 1,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+
+        Ok(())
+    }
+}
+
+/// Allocator handing out a single memory block at the start of a
+/// `Reservation`, which grows in place by committing the following pages.
+///
+/// The memory block never moves, so pointers into it stay valid across
+/// `grow` and `shrink`, as long as they are within the current size. Growing
+/// only fails once the reservation is exhausted. Shrinking and deallocating
+/// decommit the pages that are no longer needed, and another memory block
+/// can be allocated once the current one is deallocated.
+#[derive(Debug)]
+pub struct ReservedAllocator {
+    reservation: Reservation,
+    /// Whether the memory block at the start of the reservation is currently
+    /// allocated.
+    allocated: AtomicBool,
+}
+
+impl ReservedAllocator {
+    /// Creates an allocator handing out the memory of `reservation`.
+    pub fn new(reservation: Reservation) -> Self {
+        Self {
+            reservation,
+            allocated: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the reservation backing the allocator.
+    pub fn reservation(&self) -> &Reservation {
+        &self.reservation
+    }
+
+    /// Commits the pages needed by `layout` at the start of the reservation.
+    fn commit(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        // The reservation is page-aligned, so only larger alignments may not
+        // be satisfied.
+        if self.reservation.ptr.as_ptr().align_offset(layout.align()) != 0 {
+            return Err(AllocError);
+        }
+
+        self.reservation.commit(0, layout.size())?;
+
+        Ok(NonNull::slice_from_raw_parts(
+            self.reservation.ptr,
+            self.reservation.page_rounded(layout.size()),
+        ))
+    }
+}
+
+unsafe impl Allocator for ReservedAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if self
+            .allocated
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(AllocError);
+        }
+
+        self.commit(layout)
+            .inspect_err(|_| self.allocated.store(false, Ordering::Release))
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, layout: Layout) {
+        // The pages stay reserved if they cannot be decommitted, and are
+        // reused as they are by the next memory block.
+        let _ = self
+            .reservation
+            .decommit(0, self.reservation.page_rounded(layout.size()));
+        self.allocated.store(false, Ordering::Release);
+    }
+
+    unsafe fn grow(
+        &self,
+        _ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        self.commit(new_layout)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // Committed pages are always zeroed, and the end of the last page of
+        // the old memory block falls within the size returned for it.
+        self.grow(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn shrink(
+        &self,
+        _ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
+        );
+
+        let new_size = self.reservation.page_rounded(new_layout.size());
+        let old_size = self.reservation.page_rounded(old_layout.size());
+
+        // The memory block is still valid if the tail cannot be decommitted.
+        let _ = self.reservation.decommit(new_size, old_size - new_size);
+
+        self.commit(new_layout)
+    }
+}
@@ -0,0 +1,420 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    mem,
+    ptr::{self, NonNull},
+};
+use std::sync::Mutex;
+use std::vec::Vec;
+
+use crate::{scrub, MMapAllocator, Protection};
+
+/// Size of the smallest cells, which must be able to hold a free list link.
+const MIN_CELL_SIZE: usize = 16;
+
+/// Allocator packing small allocations into slabs mapped with an
+/// `MMapAllocator`.
+///
+/// Allocations are rounded up to a power of two, their size class. Each slab
+/// is a single page, split into cells of one size class, whose free cells
+/// are linked together in an intrusive free list. Deallocated cells are
+/// handed out again by the next allocation of the same size class, and a
+/// slab is unmapped as soon as all of its cells are free. Allocations larger
+/// than a quarter of a page, or whose memory cannot be written, are mapped
+/// directly with the `MMapAllocator`. This requires the `std` feature.
+#[derive(Debug)]
+pub struct SlabAllocator {
+    mmap: MMapAllocator,
+    slab_size: usize,
+    classes: Mutex<Vec<SizeClass>>,
+}
+
+/// Slabs of a single size class.
+#[derive(Debug, Default)]
+struct SizeClass {
+    /// Every slab of the size class.
+    slabs: Vec<Slab>,
+    /// Slabs with at least one free cell.
+    partial: Vec<Slab>,
+}
+
+/// Slab, pointing to the header at its base.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Slab(NonNull<SlabHeader>);
+
+// Slabs are owned by the allocator, and can be used by any thread.
+unsafe impl Send for Slab {}
+
+/// Header at the base of every slab, followed by its cells.
+struct SlabHeader {
+    /// First free cell that was already handed out once.
+    free: Option<NonNull<FreeCell>>,
+    /// Offset of the first cell that was never handed out.
+    untouched: usize,
+    /// Number of cells currently allocated.
+    used: usize,
+    /// Index of the slab in `SizeClass::slabs`.
+    index: usize,
+    /// Index of the slab in `SizeClass::partial`, if it has free cells.
+    partial_index: Option<usize>,
+}
+
+/// Free cell, linked to the next free cell of its slab.
+struct FreeCell {
+    next: Option<NonNull<FreeCell>>,
+}
+
+impl SlabAllocator {
+    /// Creates an allocator mapping slabs with regular anonymous pages.
+    pub fn new() -> Self {
+        Self::with_allocator(MMapAllocator::new())
+    }
+
+    /// Creates an allocator mapping slabs, and the allocations too large for
+    /// them, with `mmap`.
+    pub fn with_allocator(mmap: MMapAllocator) -> Self {
+        let slab_size = mmap.page_size();
+
+        let mut classes = Vec::new();
+        let mut cell_size = MIN_CELL_SIZE;
+        while cell_size <= slab_size / 4 {
+            classes.push(SizeClass::default());
+            cell_size *= 2;
+        }
+
+        Self {
+            mmap,
+            slab_size,
+            classes: Mutex::new(classes),
+        }
+    }
+
+    /// Returns the allocator used to map slabs and large allocations.
+    pub fn mmap_allocator(&self) -> &MMapAllocator {
+        &self.mmap
+    }
+
+    /// Returns the size of the largest allocations served from slabs.
+    pub fn max_cell_size(&self) -> usize {
+        self.size_classes()
+            .len()
+            .checked_sub(1)
+            .map_or(0, |index| MIN_CELL_SIZE << index)
+    }
+
+    /// Returns the number of slabs currently mapped.
+    pub fn slabs(&self) -> usize {
+        self.size_classes()
+            .iter()
+            .map(|class| class.slabs.len())
+            .sum()
+    }
+
+    /// Returns the size classes, even if another thread panicked while
+    /// holding them, as they are always left in a consistent state.
+    fn size_classes(&self) -> std::sync::MutexGuard<'_, Vec<SizeClass>> {
+        self.classes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the index of the size class of `layout`, if it is served from
+    /// slabs.
+    fn class_index(&self, layout: Layout) -> Option<usize> {
+        if !self
+            .mmap
+            .protection
+            .contains(Protection::READ | Protection::WRITE)
+        {
+            return None;
+        }
+
+        // Cells are aligned to their size, as slabs are page-aligned.
+        let cell_size = layout
+            .size()
+            .max(layout.align())
+            .max(self.mmap.min_align)
+            .max(MIN_CELL_SIZE)
+            .checked_next_power_of_two()?;
+        if cell_size > self.slab_size / 4 {
+            return None;
+        }
+
+        Some((cell_size / MIN_CELL_SIZE).trailing_zeros() as usize)
+    }
+
+    /// Returns the offset of the first cell of a slab, past its header.
+    fn first_cell(cell_size: usize) -> usize {
+        // `cell_size` is a power of two.
+        (mem::size_of::<SlabHeader>() + cell_size - 1) & !(cell_size - 1)
+    }
+
+    /// Hands out a free cell of the size class at `index`, mapping a new slab
+    /// if none has free cells.
+    fn allocate_cell(&self, index: usize) -> Result<NonNull<[u8]>, AllocError> {
+        let cell_size = MIN_CELL_SIZE << index;
+        let mut classes = self.size_classes();
+        let class = &mut classes[index];
+
+        let slab = match class.partial.last() {
+            Some(&slab) => slab,
+            None => self.map_slab(class, cell_size)?,
+        };
+
+        // SAFETY: the slab is mapped until all of its cells are free, and is
+        // only accessed with the size classes locked.
+        unsafe {
+            let header = &mut *slab.0.as_ptr();
+            let cell = match header.free {
+                Some(cell) => {
+                    header.free = cell.as_ref().next;
+                    cell.cast::<u8>()
+                }
+                None => {
+                    let cell = slab.0.cast::<u8>().add(header.untouched);
+                    header.untouched += cell_size;
+                    cell
+                }
+            };
+            header.used += 1;
+
+            if header.free.is_none() && header.untouched + cell_size > self.slab_size {
+                class.remove_partial(slab);
+            }
+
+            Ok(NonNull::slice_from_raw_parts(cell, cell_size))
+        }
+    }
+
+    /// Maps a new slab for `class`, with all of its cells free.
+    fn map_slab(&self, class: &mut SizeClass, cell_size: usize) -> Result<Slab, AllocError> {
+        let layout = self.slab_layout();
+        let slab = Slab(self.mmap.allocate(layout)?.as_non_null_ptr().cast());
+
+        // SAFETY: the slab was just mapped, and its header is aligned.
+        unsafe {
+            slab.0.as_ptr().write(SlabHeader {
+                free: None,
+                untouched: Self::first_cell(cell_size),
+                used: 0,
+                index: class.slabs.len(),
+                partial_index: None,
+            })
+        };
+        class.slabs.push(slab);
+        class.push_partial(slab);
+
+        Ok(slab)
+    }
+
+    /// Returns the cell at `ptr` to the free list of its slab.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a cell currently allocated from the size class at
+    /// `index`.
+    unsafe fn deallocate_cell(&self, ptr: NonNull<u8>, index: usize) {
+        let cell_size = MIN_CELL_SIZE << index;
+        let mut classes = self.size_classes();
+        let class = &mut classes[index];
+
+        // Slabs are aligned to their size, so the header of the slab is at
+        // the base of the slab holding the cell.
+        let base = ptr.as_ptr() as usize & !(self.slab_size - 1);
+        let slab = Slab(NonNull::new_unchecked(base as *mut SlabHeader));
+        let header = &mut *slab.0.as_ptr();
+
+        header.used -= 1;
+        if header.used == 0 {
+            class.remove(slab);
+            self.mmap
+                .deallocate(slab.0.cast::<u8>(), self.slab_layout());
+            return;
+        }
+
+        // Recycled memory should not leak secrets either, and should reveal
+        // its uses after being freed.
+        if let Some(byte) = self.mmap.freed_fill() {
+            scrub(ptr.as_ptr(), cell_size, byte);
+        }
+
+        let cell = ptr.cast::<FreeCell>();
+        cell.as_ptr().write(FreeCell { next: header.free });
+        header.free = Some(cell);
+
+        if header.partial_index.is_none() {
+            class.push_partial(slab);
+        }
+    }
+
+    /// Returns the layout of the mapping of a slab.
+    fn slab_layout(&self) -> Layout {
+        // SAFETY: the page size is a power of two, and a page fits in the
+        // address space.
+        unsafe { Layout::from_size_align_unchecked(self.slab_size, self.slab_size) }
+    }
+
+    /// Moves an allocation to a new memory block of `new_layout`, zeroing the
+    /// new memory if `zeroed` is `true`.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::grow` or `Allocator::shrink`.
+    unsafe fn relocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+        zeroed: bool,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = if zeroed {
+            self.allocate_zeroed(new_layout)?
+        } else {
+            self.allocate(new_layout)?
+        };
+
+        // SAFETY: both allocations are valid for reads and writes of the
+        // smallest of the two sizes, and they are distinct, as the old one
+        // was not yet deallocated.
+        let size = old_layout.size().min(new_layout.size());
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), size);
+        self.deallocate(ptr, old_layout);
+
+        Ok(new_ptr)
+    }
+}
+
+impl Default for SlabAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SizeClass {
+    /// Records that `slab` has free cells.
+    fn push_partial(&mut self, slab: Slab) {
+        // SAFETY: the slab is mapped, and the size classes are locked.
+        unsafe { (*slab.0.as_ptr()).partial_index = Some(self.partial.len()) };
+        self.partial.push(slab);
+    }
+
+    /// Records that `slab` has no free cells left.
+    fn remove_partial(&mut self, slab: Slab) {
+        // SAFETY: the slabs are mapped, and the size classes are locked.
+        unsafe {
+            if let Some(index) = (*slab.0.as_ptr()).partial_index.take() {
+                self.partial.swap_remove(index);
+                if let Some(moved) = self.partial.get(index) {
+                    (*moved.0.as_ptr()).partial_index = Some(index);
+                }
+            }
+        }
+    }
+
+    /// Forgets `slab`, before it is unmapped.
+    fn remove(&mut self, slab: Slab) {
+        self.remove_partial(slab);
+
+        // SAFETY: the slabs are mapped, and the size classes are locked.
+        unsafe {
+            let index = (*slab.0.as_ptr()).index;
+            self.slabs.swap_remove(index);
+            if let Some(moved) = self.slabs.get(index) {
+                (*moved.0.as_ptr()).index = index;
+            }
+        }
+    }
+}
+
+unsafe impl Allocator for SlabAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        match self.class_index(layout) {
+            Some(index) => self.allocate_cell(index),
+            None => self.mmap.allocate(layout),
+        }
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        match self.class_index(layout) {
+            Some(index) => {
+                let new_ptr = self.allocate_cell(index)?;
+                // SAFETY: the cell is valid for writes of its whole length.
+                unsafe { ptr::write_bytes(new_ptr.as_mut_ptr(), 0, new_ptr.len()) };
+                Ok(new_ptr)
+            }
+            None => self.mmap.allocate_zeroed(layout),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        match self.class_index(layout) {
+            Some(index) => self.deallocate_cell(ptr, index),
+            None => self.mmap.deallocate(ptr, layout),
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (self.class_index(old_layout), self.class_index(new_layout)) {
+            (None, None) => self.mmap.grow(ptr, old_layout, new_layout),
+            (Some(old), Some(new)) if old == new => {
+                Ok(NonNull::slice_from_raw_parts(ptr, MIN_CELL_SIZE << new))
+            }
+            _ => self.relocate(ptr, old_layout, new_layout, false),
+        }
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (self.class_index(old_layout), self.class_index(new_layout)) {
+            (None, None) => self.mmap.grow_zeroed(ptr, old_layout, new_layout),
+            (Some(old), Some(new)) if old == new => {
+                // Recycled cells are not zeroed past the old allocation.
+                let new_ptr = NonNull::slice_from_raw_parts(ptr, MIN_CELL_SIZE << new);
+                ptr::write_bytes(
+                    ptr.as_ptr().add(old_layout.size()),
+                    0,
+                    new_ptr.len() - old_layout.size(),
+                );
+                Ok(new_ptr)
+            }
+            _ => self.relocate(ptr, old_layout, new_layout, true),
+        }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match (self.class_index(old_layout), self.class_index(new_layout)) {
+            (None, None) => self.mmap.shrink(ptr, old_layout, new_layout),
+            (Some(old), Some(new)) if old == new => {
+                Ok(NonNull::slice_from_raw_parts(ptr, MIN_CELL_SIZE << new))
+            }
+            _ => self.relocate(ptr, old_layout, new_layout, false),
+        }
+    }
+}
+
+impl Drop for SlabAllocator {
+    fn drop(&mut self) {
+        let layout = self.slab_layout();
+        let classes = self
+            .classes
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner());
+        for class in classes.iter_mut() {
+            for slab in class.slabs.drain(..) {
+                // SAFETY: the slab was mapped by `map_slab` with this layout.
+                unsafe { self.mmap.deallocate(slab.0.cast::<u8>(), layout) };
+            }
+        }
+    }
+}
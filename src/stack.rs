@@ -0,0 +1,222 @@
+use core::{alloc::AllocError, ptr, ptr::NonNull};
+
+use crate::{
+    cached_page_size, protect_pages, round_to_pages, sys, unmap_pages, MMapAllocator, MMapError,
+    ANON_FLAG, MAP_STACK,
+};
+
+/// Thread stack mapped by `MMapAllocator::allocate_stack`.
+///
+/// The stack is readable and writable between `base` and `top`, and a guard
+/// page right below `base` makes overflows fault immediately. The whole
+/// mapping, including the guard page, is unmapped when the stack is dropped.
+#[derive(Debug)]
+pub struct Stack {
+    ptr: NonNull<u8>,
+    size: usize,
+    guard_size: usize,
+}
+
+/// Region mapped by `MMapAllocator::allocate_growsdown`, which the kernel
+/// extends downwards when the pages right below it are accessed.
+///
+/// The whole region, including the pages added by the kernel, is unmapped
+/// when it is dropped, as long as its extent can be read, see `base`.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct GrowsDownRegion {
+    top: NonNull<u8>,
+    size: usize,
+}
+
+impl MMapAllocator {
+    /// Maps a stack of `size` bytes, with a guard page at its low end.
+    ///
+    /// `size` is rounded to the system page size. The stack is always backed
+    /// by regular anonymous pages mapped with `MAP_STACK`, where available,
+    /// independently of the configuration of the allocator.
+    pub fn allocate_stack(&self, size: usize) -> Result<Stack, AllocError> {
+        let page_size = cached_page_size();
+        let size = round_to_pages(size, page_size)?
+            .checked_add(page_size)
+            .ok_or(MMapError::SizeOverflow)?;
+
+        let ptr = unsafe {
+            sys::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | ANON_FLAG | MAP_STACK,
+                -1,
+                0,
+            )
+        }
+        .map_err(MMapError::MmapFailed)?;
+
+        // SAFETY: `mmap` is guaranteed to return a valid pointer if it
+        // succeeds.
+        let ptr = unsafe { NonNull::new_unchecked(ptr.cast::<u8>()) };
+
+        // Stacks grow downwards, so only the lowest page is needed as guard.
+        if let Err(err) = unsafe { protect_pages(ptr.as_ptr(), page_size, libc::PROT_NONE) } {
+            let _ = unsafe { unmap_pages(ptr.as_ptr(), size) };
+            return Err(err.into());
+        }
+
+        Ok(Stack {
+            ptr,
+            size,
+            guard_size: page_size,
+        })
+    }
+
+    /// Maps a region of `size` bytes with `MAP_GROWSDOWN`, whose pages are
+    /// usable from `top` downwards.
+    ///
+    /// `size` is rounded to the system page size. Accessing the page right
+    /// below the lowest mapped one makes the kernel extend the mapping to
+    /// include it, as it does for the main thread stack, as long as the
+    /// whole mapping stays within `RLIMIT_STACK`. The kernel also refuses to
+    /// grow the mapping within `stack_guard_gap` of the mapping below it,
+    /// which is 256 pages by default and only configurable with the
+    /// `stack_guard_gap=` boot parameter, not under `/proc/sys/vm`. Accesses
+    /// past either limit fault with `SIGSEGV`, so the usable size depends on
+    /// what else is mapped nearby.
+    ///
+    /// The region is always backed by regular anonymous pages, independently
+    /// of the configuration of the allocator. This is only supported on
+    /// Linux.
+    #[cfg(target_os = "linux")]
+    pub fn allocate_growsdown(&self, size: usize) -> Result<GrowsDownRegion, AllocError> {
+        let size = round_to_pages(size, cached_page_size())?;
+
+        let ptr = unsafe {
+            sys::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | ANON_FLAG | libc::MAP_GROWSDOWN,
+                -1,
+                0,
+            )
+        }
+        .map_err(MMapError::MmapFailed)?;
+
+        // SAFETY: `mmap` is guaranteed to return a valid pointer if it
+        // succeeds, and the top is one past the end of the mapping.
+        let top = unsafe { NonNull::new_unchecked(ptr.cast::<u8>().add(size)) };
+
+        Ok(GrowsDownRegion { top, size })
+    }
+}
+
+impl Stack {
+    /// Returns a pointer to the lowest usable byte of the stack, right above
+    /// the guard page.
+    pub fn base(&self) -> NonNull<u8> {
+        // SAFETY: the guard page is part of the mapping.
+        unsafe { NonNull::new_unchecked(self.ptr.as_ptr().add(self.guard_size)) }
+    }
+
+    /// Returns a pointer one past the highest usable byte of the stack, which
+    /// is where the stack pointer starts.
+    pub fn top(&self) -> NonNull<u8> {
+        // SAFETY: this is one past the end of the mapping.
+        unsafe { NonNull::new_unchecked(self.ptr.as_ptr().add(self.size)) }
+    }
+
+    /// Returns the usable size of the stack, excluding the guard page.
+    pub fn size(&self) -> usize {
+        self.size - self.guard_size
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        // The stack cannot be used afterwards, so it is leaked if it cannot
+        // be unmapped.
+        let _ = unsafe { unmap_pages(self.ptr.as_ptr(), self.size) };
+    }
+}
+
+// The stack behaves like an owned allocation.
+unsafe impl Send for Stack {}
+unsafe impl Sync for Stack {}
+
+#[cfg(target_os = "linux")]
+impl GrowsDownRegion {
+    /// Returns a pointer one past the highest usable byte of the region.
+    pub fn top(&self) -> NonNull<u8> {
+        self.top
+    }
+
+    /// Returns the size the region was mapped with, which does not account
+    /// for the pages added by the kernel since.
+    pub fn initial_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns a pointer to the lowest byte currently mapped in the region,
+    /// including the pages added by the kernel.
+    ///
+    /// The current extent of the region is read from `/proc/self/maps`,
+    /// which requires the `std` feature. Without it, or if it cannot be read,
+    /// this is the base the region was mapped with, so the pages added by the
+    /// kernel are leaked when the region is dropped.
+    pub fn base(&self) -> NonNull<u8> {
+        let initial_base = self.top.as_ptr().wrapping_sub(self.size);
+
+        // Another mapping may be right below the region, so the region ends
+        // where its own mapping does, whatever is mapped below it.
+        let base = mapping_start(self.top.as_ptr() as usize - 1)
+            .filter(|&start| start <= initial_base as usize)
+            .map_or(initial_base, |start| start as *mut u8);
+
+        // SAFETY: the base is within the mapping.
+        unsafe { NonNull::new_unchecked(base) }
+    }
+}
+
+/// Returns the start of the mapping containing `addr`, as listed in
+/// `/proc/self/maps`.
+#[cfg(all(target_os = "linux", feature = "std"))]
+fn mapping_start(addr: usize) -> Option<usize> {
+    use std::io::BufRead;
+
+    let maps = std::fs::File::open("/proc/self/maps").ok()?;
+    for line in std::io::BufReader::new(maps).lines() {
+        let line = line.ok()?;
+        let (range, _) = line.split_once(' ')?;
+        let (start, end) = range.split_once('-')?;
+        let start = usize::from_str_radix(start, 16).ok()?;
+        let end = usize::from_str_radix(end, 16).ok()?;
+        if (start..end).contains(&addr) {
+            return Some(start);
+        }
+    }
+
+    None
+}
+
+#[cfg(all(target_os = "linux", not(feature = "std")))]
+fn mapping_start(_addr: usize) -> Option<usize> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for GrowsDownRegion {
+    fn drop(&mut self) {
+        let base = self.base().as_ptr();
+        let size = self.top.as_ptr() as usize - base as usize;
+
+        // The region cannot be used afterwards, so it is leaked if it cannot
+        // be unmapped.
+        let _ = unsafe { unmap_pages(base, size) };
+    }
+}
+
+// The region behaves like an owned allocation.
+#[cfg(target_os = "linux")]
+unsafe impl Send for GrowsDownRegion {}
+#[cfg(target_os = "linux")]
+unsafe impl Sync for GrowsDownRegion {}
@@ -0,0 +1,61 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counters describing the memory currently mapped by an allocator.
+#[derive(Debug)]
+pub(crate) struct Stats {
+    live_allocations: AtomicUsize,
+    bytes_mapped: AtomicUsize,
+}
+
+impl Stats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            live_allocations: AtomicUsize::new(0),
+            bytes_mapped: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn live_allocations(&self) -> usize {
+        self.live_allocations.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn bytes_mapped(&self) -> usize {
+        self.bytes_mapped.load(Ordering::Relaxed)
+    }
+
+    /// Records a new mapping of `size` bytes.
+    pub(crate) fn record_map(&self, size: usize) {
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        self.bytes_mapped.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Records the release of a mapping of `size` bytes.
+    pub(crate) fn record_unmap(&self, size: usize) {
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+        self.bytes_mapped.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Records a mapping being split into two mappings.
+    #[cfg(unix)]
+    pub(crate) fn record_split(&self) {
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records two adjacent mappings being merged into one.
+    #[cfg(unix)]
+    pub(crate) fn record_merge(&self) {
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Records a mapping changing its size from `old_size` to `new_size`
+    /// bytes.
+    pub(crate) fn record_resize(&self, old_size: usize, new_size: usize) {
+        if new_size >= old_size {
+            self.bytes_mapped
+                .fetch_add(new_size - old_size, Ordering::Relaxed);
+        } else {
+            self.bytes_mapped
+                .fetch_sub(old_size - new_size, Ordering::Relaxed);
+        }
+    }
+}
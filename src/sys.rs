@@ -0,0 +1,225 @@
+//! Wrappers around the system calls made by the allocator.
+//!
+//! Every wrapper captures `errno` right after a failed call, before anything
+//! else can clobber it. With the `syscall-hooks` feature, the calls can be
+//! intercepted through the hooks installed in [`crate::hooks`]. Otherwise,
+//! they directly call into `libc`, or, with the `raw-syscalls` feature on
+//! Linux for x86_64 and aarch64, issue the system calls themselves.
+
+use core::ffi::c_void;
+
+#[cfg_attr(
+    all(
+        feature = "raw-syscalls",
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ),
+    path = "sys/raw.rs"
+)]
+#[cfg_attr(
+    not(all(
+        feature = "raw-syscalls",
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )),
+    path = "sys/libc_calls.rs"
+)]
+mod calls;
+
+/// Returns the current value of `errno` for the calling thread.
+///
+/// The raw system calls report errors without it, so it is then only needed
+/// for the hooks.
+#[cfg_attr(not(feature = "syscall-hooks"), allow(dead_code))]
+pub(crate) fn errno() -> i32 {
+    unsafe {
+        #[cfg(any(target_os = "linux", target_os = "emscripten", target_os = "redox"))]
+        let errno = *libc::__errno_location();
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly"
+        ))]
+        let errno = *libc::__error();
+        #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+        let errno = *libc::__errno();
+        #[cfg(any(target_os = "solaris", target_os = "illumos"))]
+        let errno = *libc::___errno();
+
+        errno
+    }
+}
+
+/// Calls `f` again as long as it fails with `EINTR`.
+///
+/// Neither `mmap` nor `munmap` is expected to be interrupted by signals in
+/// practice, but retrying is cheap insurance against systems where they are.
+fn retry_on_eintr<T>(mut f: impl FnMut() -> Result<T, i32>) -> Result<T, i32> {
+    loop {
+        match f() {
+            Err(libc::EINTR) => continue,
+            res => return res,
+        }
+    }
+}
+
+/// Calls `mmap`, or the hook installed in its place, returning the new mapping
+/// or `errno` on failure.
+pub(crate) unsafe fn mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: libc::c_int,
+    flags: libc::c_int,
+    fd: libc::c_int,
+    offset: libc::off_t,
+) -> Result<*mut c_void, i32> {
+    retry_on_eintr(|| {
+        #[cfg(feature = "syscall-hooks")]
+        {
+            crate::hooks::count(|counts| counts.mmap += 1);
+            if let Some(hook) = crate::hooks::mmap_hook() {
+                return match hook(addr, len, prot, flags, fd, offset) {
+                    libc::MAP_FAILED => Err(errno()),
+                    res => Ok(res),
+                };
+            }
+        }
+
+        calls::mmap(addr, len, prot, flags, fd, offset)
+    })
+}
+
+/// Calls `munmap`, or the hook installed in its place, returning `errno` on
+/// failure.
+pub(crate) unsafe fn munmap(addr: *mut c_void, len: usize) -> Result<(), i32> {
+    retry_on_eintr(|| {
+        #[cfg(feature = "syscall-hooks")]
+        {
+            crate::hooks::count(|counts| counts.munmap += 1);
+            if let Some(hook) = crate::hooks::munmap_hook() {
+                return match hook(addr, len) {
+                    -1 => Err(errno()),
+                    _ => Ok(()),
+                };
+            }
+        }
+
+        calls::munmap(addr, len)
+    })
+}
+
+/// Calls `mremap`, or the hook installed in its place, returning the resized
+/// mapping or `errno` on failure.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn mremap(
+    addr: *mut c_void,
+    old_len: usize,
+    new_len: usize,
+    flags: libc::c_int,
+) -> Result<*mut c_void, i32> {
+    #[cfg(feature = "syscall-hooks")]
+    {
+        crate::hooks::count(|counts| counts.mremap += 1);
+        if let Some(hook) = crate::hooks::mremap_hook() {
+            return match hook(addr, old_len, new_len, flags) {
+                libc::MAP_FAILED => Err(errno()),
+                res => Ok(res),
+            };
+        }
+    }
+
+    calls::mremap(addr, old_len, new_len, flags, core::ptr::null_mut())
+}
+
+/// Moves the mapping at `addr` to `new_addr` with `MREMAP_FIXED`, replacing
+/// anything mapped there, and returning `errno` on failure.
+///
+/// The call is counted as a call to `mremap`, but cannot be intercepted by
+/// its hook, which takes no new address.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn mremap_fixed(
+    addr: *mut c_void,
+    len: usize,
+    new_addr: *mut c_void,
+) -> Result<(), i32> {
+    #[cfg(feature = "syscall-hooks")]
+    crate::hooks::count(|counts| counts.mremap += 1);
+
+    calls::mremap(
+        addr,
+        len,
+        len,
+        libc::MREMAP_MAYMOVE | libc::MREMAP_FIXED,
+        new_addr,
+    )
+    .map(drop)
+}
+
+/// Calls `mprotect`, returning `errno` on failure.
+pub(crate) unsafe fn mprotect(addr: *mut c_void, len: usize, prot: libc::c_int) -> Result<(), i32> {
+    calls::mprotect(addr, len, prot)
+}
+
+/// Calls `mlock`, returning `errno` on failure.
+pub(crate) unsafe fn mlock(addr: *const c_void, len: usize) -> Result<(), i32> {
+    calls::mlock(addr, len)
+}
+
+/// Calls `madvise`, returning `errno` on failure.
+pub(crate) unsafe fn madvise(
+    addr: *mut c_void,
+    len: usize,
+    advice: libc::c_int,
+) -> Result<(), i32> {
+    calls::madvise(addr, len, advice)
+}
+
+/// Calls `mincore`, returning `errno` on failure.
+///
+/// `vec` must have room for one byte per page of `[addr, addr + len)`.
+pub(crate) unsafe fn mincore(addr: *mut c_void, len: usize, vec: *mut u8) -> Result<(), i32> {
+    calls::mincore(addr, len, vec)
+}
+
+/// Calls `msync`, returning `errno` on failure.
+pub(crate) unsafe fn msync(addr: *mut c_void, len: usize, flags: libc::c_int) -> Result<(), i32> {
+    calls::msync(addr, len, flags)
+}
+
+/// Calls `fstat`, returning the status of `fd` or `errno` on failure.
+pub(crate) fn fstat(fd: libc::c_int) -> Result<libc::stat, i32> {
+    calls::fstat(fd)
+}
+
+/// Names the anonymous mapping `[addr, addr + len)` with `prctl`, returning
+/// `errno` on failure.
+///
+/// `name` must be NUL-terminated.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn set_vma_anon_name(
+    addr: *mut c_void,
+    len: usize,
+    name: *const libc::c_char,
+) -> Result<(), i32> {
+    calls::prctl(
+        libc::PR_SET_VMA,
+        libc::PR_SET_VMA_ANON_NAME as libc::c_ulong,
+        addr as libc::c_ulong,
+        len as libc::c_ulong,
+        name as libc::c_ulong,
+    )
+}
+
+/// Calls `mbind`, returning `errno` on failure.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn mbind(
+    addr: *mut c_void,
+    len: usize,
+    mode: libc::c_int,
+    node_mask: *const libc::c_ulong,
+    max_node: libc::c_ulong,
+    flags: libc::c_uint,
+) -> Result<(), i32> {
+    calls::mbind(addr, len, mode, node_mask, max_node, flags)
+}
@@ -0,0 +1,131 @@
+//! System calls made through the `libc` functions wrapping them.
+
+use core::{ffi::c_void, mem::MaybeUninit};
+
+use super::errno;
+
+pub(super) unsafe fn mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: libc::c_int,
+    flags: libc::c_int,
+    fd: libc::c_int,
+    offset: libc::off_t,
+) -> Result<*mut c_void, i32> {
+    let res = libc::mmap(addr, len, prot, flags, fd, offset);
+    if res == libc::MAP_FAILED {
+        return Err(errno());
+    }
+
+    Ok(res)
+}
+
+pub(super) unsafe fn munmap(addr: *mut c_void, len: usize) -> Result<(), i32> {
+    if libc::munmap(addr, len) == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub(super) unsafe fn mremap(
+    addr: *mut c_void,
+    old_len: usize,
+    new_len: usize,
+    flags: libc::c_int,
+    new_addr: *mut c_void,
+) -> Result<*mut c_void, i32> {
+    let res = libc::mremap(addr, old_len, new_len, flags, new_addr);
+    if res == libc::MAP_FAILED {
+        return Err(errno());
+    }
+
+    Ok(res)
+}
+
+pub(super) unsafe fn mprotect(addr: *mut c_void, len: usize, prot: libc::c_int) -> Result<(), i32> {
+    if libc::mprotect(addr, len, prot) == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
+
+pub(super) unsafe fn mlock(addr: *const c_void, len: usize) -> Result<(), i32> {
+    if libc::mlock(addr, len) == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
+
+pub(super) unsafe fn madvise(
+    addr: *mut c_void,
+    len: usize,
+    advice: libc::c_int,
+) -> Result<(), i32> {
+    if libc::madvise(addr, len, advice) == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
+
+pub(super) unsafe fn mincore(addr: *mut c_void, len: usize, vec: *mut u8) -> Result<(), i32> {
+    if libc::mincore(addr, len, vec.cast()) == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
+
+pub(super) unsafe fn msync(addr: *mut c_void, len: usize, flags: libc::c_int) -> Result<(), i32> {
+    if libc::msync(addr, len, flags) == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
+
+pub(super) fn fstat(fd: libc::c_int) -> Result<libc::stat, i32> {
+    let mut stat = MaybeUninit::uninit();
+    if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } == -1 {
+        return Err(errno());
+    }
+
+    // SAFETY: `fstat` filled the buffer, since it succeeded.
+    Ok(unsafe { stat.assume_init() })
+}
+
+#[cfg(target_os = "linux")]
+pub(super) unsafe fn prctl(
+    option: libc::c_int,
+    arg2: libc::c_ulong,
+    arg3: libc::c_ulong,
+    arg4: libc::c_ulong,
+    arg5: libc::c_ulong,
+) -> Result<(), i32> {
+    if libc::prctl(option, arg2, arg3, arg4, arg5) == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub(super) unsafe fn mbind(
+    addr: *mut c_void,
+    len: usize,
+    mode: libc::c_int,
+    node_mask: *const libc::c_ulong,
+    max_node: libc::c_ulong,
+    flags: libc::c_uint,
+) -> Result<(), i32> {
+    let res = libc::syscall(libc::SYS_mbind, addr, len, mode, node_mask, max_node, flags);
+    if res == -1 {
+        return Err(errno());
+    }
+
+    Ok(())
+}
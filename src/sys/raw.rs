@@ -0,0 +1,202 @@
+//! System calls issued directly with `svc`/`syscall` instructions.
+//!
+//! Used in place of the `libc` functions with the `raw-syscalls` feature, on
+//! the Linux targets it supports. The kernel returns `-errno` on failure
+//! instead of setting `errno`, so these calls never touch the `errno` of the
+//! calling thread.
+
+use core::{arch::asm, ffi::c_void, mem::MaybeUninit};
+
+/// Issues the system call `nr` with six arguments, returning its result or
+/// the error number it failed with.
+#[inline]
+unsafe fn syscall6(
+    nr: libc::c_long,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+    a5: usize,
+    a6: usize,
+) -> Result<usize, i32> {
+    let res: isize;
+
+    #[cfg(target_arch = "x86_64")]
+    asm!(
+        "syscall",
+        inlateout("rax") nr as isize => res,
+        in("rdi") a1,
+        in("rsi") a2,
+        in("rdx") a3,
+        in("r10") a4,
+        in("r8") a5,
+        in("r9") a6,
+        lateout("rcx") _,
+        lateout("r11") _,
+        options(nostack),
+    );
+    #[cfg(target_arch = "aarch64")]
+    asm!(
+        "svc 0",
+        in("x8") nr as isize,
+        inlateout("x0") a1 as isize => res,
+        in("x1") a2,
+        in("x2") a3,
+        in("x3") a4,
+        in("x4") a5,
+        in("x5") a6,
+        options(nostack),
+    );
+
+    // Errors are reported as values in `[-4095, -1]`, which are never valid
+    // addresses either.
+    if (-4095..0).contains(&res) {
+        return Err(-res as i32);
+    }
+
+    Ok(res as usize)
+}
+
+pub(super) unsafe fn mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: libc::c_int,
+    flags: libc::c_int,
+    fd: libc::c_int,
+    offset: libc::off_t,
+) -> Result<*mut c_void, i32> {
+    syscall6(
+        libc::SYS_mmap,
+        addr as usize,
+        len,
+        prot as usize,
+        flags as usize,
+        fd as usize,
+        offset as usize,
+    )
+    .map(|res| res as *mut c_void)
+}
+
+pub(super) unsafe fn munmap(addr: *mut c_void, len: usize) -> Result<(), i32> {
+    syscall6(libc::SYS_munmap, addr as usize, len, 0, 0, 0, 0).map(drop)
+}
+
+pub(super) unsafe fn mremap(
+    addr: *mut c_void,
+    old_len: usize,
+    new_len: usize,
+    flags: libc::c_int,
+    new_addr: *mut c_void,
+) -> Result<*mut c_void, i32> {
+    syscall6(
+        libc::SYS_mremap,
+        addr as usize,
+        old_len,
+        new_len,
+        flags as usize,
+        new_addr as usize,
+        0,
+    )
+    .map(|res| res as *mut c_void)
+}
+
+pub(super) unsafe fn mprotect(addr: *mut c_void, len: usize, prot: libc::c_int) -> Result<(), i32> {
+    syscall6(
+        libc::SYS_mprotect,
+        addr as usize,
+        len,
+        prot as usize,
+        0,
+        0,
+        0,
+    )
+    .map(drop)
+}
+
+pub(super) unsafe fn mlock(addr: *const c_void, len: usize) -> Result<(), i32> {
+    syscall6(libc::SYS_mlock, addr as usize, len, 0, 0, 0, 0).map(drop)
+}
+
+pub(super) unsafe fn madvise(
+    addr: *mut c_void,
+    len: usize,
+    advice: libc::c_int,
+) -> Result<(), i32> {
+    syscall6(
+        libc::SYS_madvise,
+        addr as usize,
+        len,
+        advice as usize,
+        0,
+        0,
+        0,
+    )
+    .map(drop)
+}
+
+pub(super) unsafe fn mincore(addr: *mut c_void, len: usize, vec: *mut u8) -> Result<(), i32> {
+    syscall6(libc::SYS_mincore, addr as usize, len, vec as usize, 0, 0, 0).map(drop)
+}
+
+pub(super) unsafe fn msync(addr: *mut c_void, len: usize, flags: libc::c_int) -> Result<(), i32> {
+    syscall6(libc::SYS_msync, addr as usize, len, flags as usize, 0, 0, 0).map(drop)
+}
+
+pub(super) fn fstat(fd: libc::c_int) -> Result<libc::stat, i32> {
+    // The `stat` of `libc` has the layout of the kernel structure on both
+    // architectures.
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    unsafe {
+        syscall6(
+            libc::SYS_fstat,
+            fd as usize,
+            stat.as_mut_ptr() as usize,
+            0,
+            0,
+            0,
+            0,
+        )
+    }?;
+
+    // SAFETY: the kernel filled the buffer, since the call succeeded.
+    Ok(unsafe { stat.assume_init() })
+}
+
+pub(super) unsafe fn prctl(
+    option: libc::c_int,
+    arg2: libc::c_ulong,
+    arg3: libc::c_ulong,
+    arg4: libc::c_ulong,
+    arg5: libc::c_ulong,
+) -> Result<(), i32> {
+    syscall6(
+        libc::SYS_prctl,
+        option as usize,
+        arg2 as usize,
+        arg3 as usize,
+        arg4 as usize,
+        arg5 as usize,
+        0,
+    )
+    .map(drop)
+}
+
+pub(super) unsafe fn mbind(
+    addr: *mut c_void,
+    len: usize,
+    mode: libc::c_int,
+    node_mask: *const libc::c_ulong,
+    max_node: libc::c_ulong,
+    flags: libc::c_uint,
+) -> Result<(), i32> {
+    syscall6(
+        libc::SYS_mbind,
+        addr as usize,
+        len,
+        mode as usize,
+        node_mask as usize,
+        max_node as usize,
+        flags as usize,
+    )
+    .map(drop)
+}
@@ -0,0 +1,101 @@
+//! Registry of the live allocations made with `allocate_tagged`, grouped by
+//! tag to attribute them to the part of a program that made them.
+
+use core::{cell::Cell, ptr::NonNull};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::vec::Vec;
+
+/// Live allocation made with `MMapAllocator::allocate_tagged`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TaggedAllocation {
+    /// The start of the memory block.
+    pub ptr: NonNull<u8>,
+    /// The length of the memory block, as returned when it was allocated or
+    /// last resized.
+    pub size: usize,
+}
+
+/// Size and tag of the live tagged allocations, by address.
+///
+/// The registry is shared by all the allocators, as the addresses of their
+/// live mappings never overlap.
+static REGISTRY: Mutex<BTreeMap<usize, Entry>> = Mutex::new(BTreeMap::new());
+
+/// Size and tag of a live tagged allocation.
+#[derive(Clone, Copy)]
+pub(crate) struct Entry {
+    size: usize,
+    pub(crate) tag: u32,
+}
+
+std::thread_local! {
+    /// Whether the current thread is using the registry.
+    static BUSY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` on the registry, unless the current thread is already using it
+/// further up the stack.
+///
+/// The registry allocates its entries, possibly through this allocator if it
+/// is the global one. Those allocations are never tagged, so releasing them
+/// while the registry is in use can skip it instead of deadlocking.
+fn with_registry<T>(f: impl FnOnce(&mut BTreeMap<usize, Entry>) -> T) -> Option<T> {
+    if BUSY.with(|busy| busy.replace(true)) {
+        return None;
+    }
+
+    // The registry is never left in an inconsistent state, so it can still
+    // be used after a thread panicked while holding it.
+    let res = f(&mut REGISTRY.lock().unwrap_or_else(|err| err.into_inner()));
+    BUSY.with(|busy| busy.set(false));
+    Some(res)
+}
+
+/// Records the memory block at `ptr` as a live allocation for `tag`.
+pub(crate) fn insert(ptr: NonNull<[u8]>, tag: u32) {
+    // Zero-sized allocations are never mapped, and share their addresses.
+    if ptr.is_empty() {
+        return;
+    }
+
+    let entry = Entry {
+        size: ptr.len(),
+        tag,
+    };
+    restore(ptr.as_non_null_ptr(), entry);
+}
+
+/// Records the memory block at `ptr` as a live allocation again, as it was
+/// before being removed with `remove`.
+pub(crate) fn restore(ptr: NonNull<u8>, entry: Entry) {
+    with_registry(|registry| registry.insert(ptr.as_ptr() as usize, entry));
+}
+
+/// Removes the memory block at `ptr` from the live allocations, returning how
+/// it was recorded.
+pub(crate) fn remove(ptr: NonNull<u8>) -> Option<Entry> {
+    with_registry(|registry| registry.remove(&(ptr.as_ptr() as usize))).flatten()
+}
+
+/// Returns the live allocations made with `MMapAllocator::allocate_tagged` by
+/// any allocator, grouped by tag and sorted by address.
+///
+/// Allocations keep their tag when they are grown or shrunk, and are removed
+/// when they are deallocated. This requires the `tags` feature.
+pub fn dump_live_tagged() -> BTreeMap<u32, Vec<TaggedAllocation>> {
+    let mut dump = BTreeMap::<u32, Vec<TaggedAllocation>>::new();
+
+    with_registry(|registry| {
+        for (&address, entry) in registry.iter() {
+            // Only the addresses of mapped memory blocks are recorded.
+            let ptr = NonNull::new(address as *mut u8).expect("null address in the registry");
+            dump.entry(entry.tag).or_default().push(TaggedAllocation {
+                ptr,
+                size: entry.size,
+            });
+        }
+    });
+
+    dump
+}
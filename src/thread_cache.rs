@@ -0,0 +1,105 @@
+use core::{
+    cell::RefCell,
+    ffi::c_void,
+    num::NonZeroUsize,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::sys;
+
+/// Number of single-page mappings each thread keeps, across all allocators.
+const CAPACITY: usize = 16;
+
+/// Identifier of the next allocator created with a thread cache.
+static NEXT_OWNER: AtomicUsize = AtomicUsize::new(1);
+
+/// Freed single-page mapping kept by a thread for the allocator `owner`.
+struct CachedPage {
+    owner: NonZeroUsize,
+    ptr: NonNull<u8>,
+    size: usize,
+}
+
+/// Mappings cached by the current thread, which are unmapped when it exits.
+///
+/// The cache has a fixed capacity, so that caching a mapping never allocates,
+/// even if the allocator is used by the global allocator.
+struct ThreadCache {
+    pages: [Option<CachedPage>; CAPACITY],
+}
+
+std::thread_local! {
+    static CACHE: RefCell<ThreadCache> = const {
+        RefCell::new(ThreadCache {
+            pages: [const { None }; CAPACITY],
+        })
+    };
+}
+
+/// Returns a new identifier for the pages cached by an allocator.
+pub(crate) fn new_owner() -> NonZeroUsize {
+    let owner = NEXT_OWNER.fetch_add(1, Ordering::Relaxed);
+    NonZeroUsize::new(owner).expect("too many allocators with a thread cache")
+}
+
+/// Runs `f` on the cache of the current thread, unless it is already in use
+/// further up the stack or was already destroyed.
+fn with_cache<T>(f: impl FnOnce(&mut ThreadCache) -> Option<T>) -> Option<T> {
+    CACHE
+        .try_with(|cache| f(&mut *cache.try_borrow_mut().ok()?))
+        .ok()
+        .flatten()
+}
+
+/// Takes a mapping of `size` bytes cached for `owner` out of the cache of the
+/// current thread.
+pub(crate) fn take(owner: NonZeroUsize, size: usize) -> Option<NonNull<u8>> {
+    with_cache(|cache| {
+        let page = cache.pages.iter_mut().find(|page| {
+            page.as_ref()
+                .is_some_and(|page| page.owner == owner && page.size == size)
+        })?;
+        page.take().map(|page| page.ptr)
+    })
+}
+
+/// Caches the mapping of `size` bytes at `ptr` for `owner` in the cache of
+/// the current thread, returning whether there was room for it.
+pub(crate) fn put(owner: NonZeroUsize, ptr: NonNull<u8>, size: usize) -> bool {
+    with_cache(|cache| {
+        let slot = cache.pages.iter_mut().find(|page| page.is_none())?;
+        *slot = Some(CachedPage { owner, ptr, size });
+        Some(())
+    })
+    .is_some()
+}
+
+/// Unmaps the mappings cached for `owner` by the current thread.
+pub(crate) fn flush(owner: NonZeroUsize) {
+    with_cache(|cache| {
+        for slot in &mut cache.pages {
+            if matches!(slot, Some(page) if page.owner == owner) {
+                unmap(slot.take());
+            }
+        }
+        Some(())
+    });
+}
+
+/// Unmaps `page`, leaking it if it cannot be unmapped.
+fn unmap(page: Option<CachedPage>) {
+    if let Some(page) = page {
+        // SAFETY: the mapping was handed over to the cache, so nothing else
+        // refers to it.
+        let _ = unsafe { sys::munmap(page.ptr.as_ptr().cast::<c_void>(), page.size) };
+    }
+}
+
+impl Drop for ThreadCache {
+    fn drop(&mut self) {
+        for slot in &mut self.pages {
+            unmap(slot.take());
+        }
+    }
+}
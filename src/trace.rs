@@ -0,0 +1,55 @@
+//! Tracing of the operations of the allocator, enabled at runtime by the
+//! `MMAP_ALLOC_TRACE` environment variable.
+
+use core::{
+    fmt,
+    ptr::NonNull,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// The environment was not read yet.
+const UNKNOWN: u8 = 0;
+/// The environment is being read.
+const CHECKING: u8 = 1;
+const DISABLED: u8 = 2;
+const ENABLED: u8 = 3;
+
+static STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// Returns whether tracing is enabled, which it is if `MMAP_ALLOC_TRACE` is
+/// set to anything but an empty string or `0`.
+///
+/// The environment is only read by the first call. Reading it may allocate,
+/// possibly through this allocator if it is the global one, so the
+/// allocations made meanwhile are not traced.
+pub(crate) fn enabled() -> bool {
+    match STATE.load(Ordering::Relaxed) {
+        ENABLED => return true,
+        DISABLED => return false,
+        _ => {}
+    }
+
+    if STATE
+        .compare_exchange(UNKNOWN, CHECKING, Ordering::Relaxed, Ordering::Relaxed)
+        .is_err()
+    {
+        return false;
+    }
+
+    let enabled =
+        std::env::var_os("MMAP_ALLOC_TRACE").is_some_and(|value| !value.is_empty() && value != "0");
+    STATE.store(if enabled { ENABLED } else { DISABLED }, Ordering::Relaxed);
+    enabled
+}
+
+/// Outcome of an operation returning a memory block, as shown in the traces.
+pub(crate) struct Outcome<'a, E>(pub(crate) &'a Result<NonNull<[u8]>, E>);
+
+impl<E> fmt::Display for Outcome<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Ok(ptr) => write!(f, "ptr={:p}, len={}", ptr.as_mut_ptr(), ptr.len()),
+            Err(_) => f.write_str("failed"),
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ffi::c_void,
+    ptr::{self, NonNull},
+};
+
+use windows_sys::Win32::Foundation::GetLastError;
+use windows_sys::Win32::System::Memory::{
+    VirtualAlloc, VirtualFree, MEM_COMMIT, MEM_DECOMMIT, MEM_RELEASE, MEM_RESERVE, MEM_RESET,
+    PAGE_EXECUTE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_NOACCESS, PAGE_PROTECTION_FLAGS,
+    PAGE_READONLY, PAGE_READWRITE,
+};
+
+use crate::{
+    cached_page_size, dangling, deallocation_failed, scrub, GenericMMapAllocator, MMapError,
+    MemoryMapper, Protection,
+};
+
+impl Protection {
+    /// Returns the equivalent `PAGE_*` flags for `VirtualAlloc`.
+    ///
+    /// Windows has no write-only pages, so `WRITE` always implies `READ`.
+    fn to_page_protection(self) -> PAGE_PROTECTION_FLAGS {
+        let writable = self.contains(Protection::WRITE);
+        let readable = writable || self.contains(Protection::READ);
+
+        match (self.contains(Protection::EXEC), readable, writable) {
+            (false, false, _) => PAGE_NOACCESS,
+            (false, true, false) => PAGE_READONLY,
+            (false, true, true) => PAGE_READWRITE,
+            (true, false, _) => PAGE_EXECUTE,
+            (true, true, false) => PAGE_EXECUTE_READ,
+            (true, true, true) => PAGE_EXECUTE_READWRITE,
+        }
+    }
+}
+
+impl<M: MemoryMapper> GenericMMapAllocator<M> {
+    /// Reserves and commits a new region of `size` bytes with the given
+    /// alignment.
+    pub(crate) fn map_region(&self, size: usize, align: usize) -> Result<NonNull<u8>, MMapError> {
+        if align > cached_page_size() {
+            // `VirtualAlloc` can only allocate memory page-aligned.
+            return Err(MMapError::AlignmentTooLarge);
+        }
+
+        let new_mapping = unsafe {
+            VirtualAlloc(
+                ptr::null(),
+                size,
+                MEM_COMMIT | MEM_RESERVE,
+                self.protection.to_page_protection(),
+            )
+        };
+
+        NonNull::new(new_mapping.cast::<u8>())
+            .ok_or_else(|| MMapError::MmapFailed(unsafe { GetLastError() } as i32))
+    }
+
+    /// Releases the region of `size` bytes starting at `ptr`.
+    pub(crate) unsafe fn unmap(&self, ptr: NonNull<u8>, size: usize) -> Result<(), MMapError> {
+        // `ptr` is assumed to be currently allocated, thus it is the base of a
+        // region reserved by `VirtualAlloc`. Releasing it frees the whole
+        // region, including any pages decommitted by `shrink`.
+        if let Some(byte) = self.freed_fill() {
+            scrub(ptr.as_ptr(), size, byte);
+        }
+
+        let res = VirtualFree(ptr.as_ptr().cast::<c_void>(), 0, MEM_RELEASE);
+        if res == 0 {
+            return Err(MMapError::MunmapFailed(GetLastError() as i32));
+        }
+
+        Ok(())
+    }
+
+    /// Releases the pages of the region at `ptr` that are past
+    /// `new_page_layout`, keeping the region in place.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be the base of a region of `old_page_layout` made by this
+    /// allocator, and `new_page_layout` must not be larger.
+    pub(crate) unsafe fn release_tail(
+        &self,
+        ptr: NonNull<u8>,
+        old_page_layout: Layout,
+        new_page_layout: Layout,
+    ) -> Result<NonNull<[u8]>, MMapError> {
+        // `VirtualFree` cannot release only part of a reserved region, so the
+        // pages at the end of the current allocation are decommitted instead.
+        // This returns their physical memory to the system, while the address
+        // range stays reserved until the whole region is released by
+        // `deallocate`.
+
+        let retained_area_size = new_page_layout.size();
+        let truncated_area_size = old_page_layout
+            .size()
+            .checked_sub(retained_area_size)
+            .ok_or(MMapError::ShrinkWouldGrow)?;
+        let truncated_area_ptr = ptr.as_ptr().add(retained_area_size);
+
+        if truncated_area_size > 0 {
+            if let Some(byte) = self.freed_fill() {
+                scrub(truncated_area_ptr, truncated_area_size, byte);
+            }
+
+            let res = VirtualFree(
+                truncated_area_ptr.cast::<c_void>(),
+                truncated_area_size,
+                MEM_DECOMMIT,
+            );
+            // If the pages cannot be decommitted, they stay committed until
+            // the whole allocation is released.
+            if res != 0 {
+                if let Some(stats) = &self.stats {
+                    stats.record_resize(old_page_layout.size(), retained_area_size);
+                }
+            }
+        }
+
+        Ok(NonNull::slice_from_raw_parts(ptr, retained_area_size))
+    }
+
+    /// Grows the memory block referenced by `ptr` like `Allocator::grow`,
+    /// also reporting whether it was relocated.
+    ///
+    /// The returned flag is `true` whenever the memory block no longer
+    /// starts at `ptr`, in which case any pointer into the old memory block
+    /// must be updated.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::grow`.
+    pub unsafe fn grow_report(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(NonNull<[u8]>, bool), AllocError> {
+        debug_assert!(
+            new_layout.size() >= old_layout.size(),
+            "`new_layout.size()` must be greater than or equal to `old_layout.size()`"
+        );
+
+        let new_layout = self.min_aligned(new_layout)?;
+        if new_layout.align() > cached_page_size() {
+            // `VirtualAlloc` can only allocate memory page-aligned.
+            return Err(AllocError);
+        }
+
+        // Zero-sized allocations are not mapped, so there is nothing to keep.
+        if old_layout.size() == 0 {
+            return Ok((self.allocate(new_layout)?, true));
+        }
+
+        if let Ok(new_ptr) = self.grow_in_place(ptr, old_layout, new_layout) {
+            return Ok((new_ptr, false));
+        }
+
+        if !self
+            .protection
+            .contains(Protection::READ | Protection::WRITE)
+        {
+            // The contents of the allocation cannot be moved to a new mapping.
+            return Err(AllocError);
+        }
+
+        let old_size = self.mapping_size(old_layout)?;
+        let new_ptr = self.allocate(new_layout)?;
+
+        // The whole old memory block was usable, including the end of its last
+        // page, so all of it is moved to the new mapping.
+        let old_size = old_size.min(new_ptr.len());
+
+        // SAFETY: because `new_layout.size()` must be greater than or equal to
+        // `old_layout.size()`, both the old and new memory allocation are valid for reads and
+        // writes for `old_size` bytes. Also, because the old allocation wasn't yet
+        // deallocated, it cannot overlap `new_ptr`. Thus, the call to `copy_nonoverlapping` is
+        // safe. The safety contract for `dealloc` must be upheld by the caller.
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), old_size);
+        self.munmap_or_leak(ptr, old_layout);
+
+        Ok((new_ptr, true))
+    }
+
+    /// Shrinks the memory block referenced by `ptr` like `Allocator::shrink`,
+    /// also reporting whether it was relocated.
+    ///
+    /// # Safety
+    ///
+    /// Same as `Allocator::shrink`.
+    pub(crate) unsafe fn shrink_report(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(NonNull<[u8]>, bool), AllocError> {
+        let new_layout = self.min_aligned(new_layout)?;
+        if new_layout.align() > cached_page_size() {
+            // `VirtualAlloc` can only allocate memory page-aligned.
+            return Err(AllocError);
+        }
+
+        // Zero-sized allocations are never mapped, so the whole mapping can be
+        // released.
+        if new_layout.size() == 0 {
+            self.munmap_or_leak(ptr, old_layout);
+            return Ok((dangling(new_layout), true));
+        }
+
+        Ok((self.shrink_in_place(ptr, old_layout, new_layout)?, false))
+    }
+
+    /// Releases the physical memory backing the memory block at `ptr`, while
+    /// keeping its address range reserved.
+    ///
+    /// The pages are decommitted, so the memory block cannot be accessed
+    /// until `recommit` is called.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. The contents of
+    /// the memory block are lost.
+    pub unsafe fn decommit(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), AllocError> {
+        let page_layout = self.page_layout(layout)?;
+
+        let res = VirtualFree(
+            ptr.as_ptr().cast::<c_void>(),
+            page_layout.size(),
+            MEM_DECOMMIT,
+        );
+        if res == 0 {
+            return Err(MMapError::MunmapFailed(GetLastError() as i32).into());
+        }
+
+        Ok(())
+    }
+
+    /// Lets the system discard the contents of the memory block at `ptr`
+    /// whenever it needs the physical memory backing it.
+    ///
+    /// The pages are reset, so they stay committed and accessible, but they
+    /// are not written to the paging file. Writing to a page before it is
+    /// discarded keeps it. The contents of the memory block are undefined
+    /// until it is written again.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory. The contents of
+    /// the memory block must not be read before being written again.
+    pub unsafe fn decommit_lazy(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), AllocError> {
+        let page_layout = self.page_layout(layout)?;
+
+        // The protection is ignored when resetting pages, but must be valid.
+        let res = VirtualAlloc(
+            ptr.as_ptr().cast::<c_void>(),
+            page_layout.size(),
+            MEM_RESET,
+            PAGE_NOACCESS,
+        );
+        if res.is_null() {
+            return Err(MMapError::MadviseFailed(GetLastError() as i32).into());
+        }
+
+        Ok(())
+    }
+
+    /// Commits the memory block at `ptr` again after `decommit`, filled with
+    /// zeros.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block of memory currently allocated via this
+    /// allocator, and `layout` must fit that block of memory.
+    pub unsafe fn recommit(&self, ptr: NonNull<u8>, layout: Layout) -> Result<(), AllocError> {
+        let page_layout = self.page_layout(layout)?;
+
+        let res = VirtualAlloc(
+            ptr.as_ptr().cast::<c_void>(),
+            page_layout.size(),
+            MEM_COMMIT,
+            self.protection.to_page_protection(),
+        );
+        if res.is_null() {
+            return Err(MMapError::MmapFailed(GetLastError() as i32).into());
+        }
+
+        Ok(())
+    }
+}
+
+unsafe impl<M: MemoryMapper> Allocator for GenericMMapAllocator<M> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let res = self.try_allocate(layout);
+        trace!(
+            "allocate(size={}, align={}) -> {}",
+            layout.size(),
+            layout.align(),
+            crate::trace::Outcome(&res)
+        );
+        Ok(res?)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        trace!("deallocate(ptr={:p}, size={})", ptr, layout.size());
+        if let Err(err) = self.try_deallocate(ptr, layout) {
+            deallocation_failed(err, ptr, layout.size());
+        }
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        // `VirtualAlloc` always commits zeroed memory.
+        self.allocate(layout)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // `grow_report` checks that the size does not decrease.
+        let res = self
+            .resize(ptr, old_layout, new_layout)
+            .map(|resize| resize.ptr);
+        trace!(
+            "grow(ptr={:p}, old_size={}, new_size={}) -> {}",
+            ptr,
+            old_layout.size(),
+            new_layout.size(),
+            crate::trace::Outcome(&res)
+        );
+        res
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // When growing on the same page, the new memory area is not required to
+        // be zeroed because it falls within the size returned for the old
+        // allocation, which is always page-aligned.
+        self.grow(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(
+            new_layout.size() <= old_layout.size(),
+            "`new_layout.size()` must be smaller than or equal to `old_layout.size()`"
+        );
+
+        let res = self
+            .resize(ptr, old_layout, new_layout)
+            .map(|resize| resize.ptr);
+        trace!(
+            "shrink(ptr={:p}, old_size={}, new_size={}) -> {}",
+            ptr,
+            old_layout.size(),
+            new_layout.size(),
+            crate::trace::Outcome(&res)
+        );
+        res
+    }
+}
@@ -0,0 +1,135 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{Advice, MMapAllocator};
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn advise_access_patterns() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    for advice in [Advice::WillNeed, Advice::Sequential, Advice::Random] {
+        unsafe { allocator.advise(allocation.as_non_null_ptr(), layout, advice) }
+            .expect("advise failed");
+    }
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn dont_need_discards_contents() {
+    let allocator = MMapAllocator::new();
+
+    // The advice should cover the whole last page, not only `layout.size()`.
+    let layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    unsafe { allocator.advise(allocation.as_non_null_ptr(), layout, Advice::DontNeed) }
+        .expect("advise failed");
+
+    // Anonymous private pages are refilled with zeroes on the next access.
+    let allocation_slice = unsafe { allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn prefault_makes_pages_resident() {
+    let allocator = MMapAllocator::new();
+
+    const PAGES: usize = 16;
+    let layout = Layout::from_size_align(PAGES * page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_non_null_ptr();
+
+    unsafe { allocator.prefault(ptr, layout) };
+
+    let mut residency = [0u8; PAGES];
+    let res = unsafe { libc::mincore(ptr.as_ptr().cast(), layout.size(), residency.as_mut_ptr()) };
+    if res == -1 && std::io::Error::last_os_error().raw_os_error() == Some(libc::ENOSYS) {
+        // `mincore` is not available, so residency cannot be checked.
+        unsafe { allocator.deallocate(ptr, layout) };
+        return;
+    }
+    assert_eq!(res, 0, "mincore failed");
+    assert!(residency.iter().all(|&page| page & 1 == 1));
+
+    unsafe { allocator.deallocate(ptr, layout) };
+}
+
+#[test]
+fn resident_pages_counts_touched_pages() {
+    let allocator = MMapAllocator::new();
+
+    // Large enough to be queried in several chunks.
+    const PAGES: usize = 200;
+    let layout = Layout::from_size_align(PAGES * page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_non_null_ptr();
+
+    assert_eq!(unsafe { allocator.resident_pages(ptr, layout) }, Ok(0));
+
+    let allocation_slice = unsafe { allocation.as_mut() };
+    for page in 0..PAGES / 2 {
+        allocation_slice[page * page_size::get()] = 42;
+    }
+
+    // The system may fault in a few pages around the touched ones.
+    let resident_pages = unsafe { allocator.resident_pages(ptr, layout) }.expect("mincore failed");
+    assert!(resident_pages >= PAGES / 2);
+    assert!(resident_pages < PAGES);
+
+    unsafe { allocator.deallocate(ptr, layout) };
+}
+
+#[test]
+fn decommit_lazy_shared_mapping() {
+    // Shared mappings do not support `MADV_FREE` on Linux, so they are
+    // decommitted eagerly instead.
+    let allocator = MMapAllocator::shared();
+
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    unsafe { allocator.decommit_lazy(allocation.as_non_null_ptr(), layout) }
+        .expect("decommit_lazy failed");
+
+    let allocation_slice = unsafe { allocation.as_mut() };
+    allocation_slice.fill(43);
+    assert!(allocation_slice.iter().all(|&byte| byte == 43));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn allocate_sequential_region() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let mut allocation = allocator
+        .allocate_sequential(layout)
+        .expect("allocate_sequential failed");
+    assert_eq!(allocation.len(), 4 * page_size::get());
+
+    unsafe { allocation.as_mut().fill(42) };
+    let allocation_slice = unsafe { allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 42));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    // Zero-sized allocations are never advised.
+    let zero_sized_layout = Layout::from_size_align(0, 16).unwrap();
+    let allocation = allocator
+        .allocate_sequential(zero_sized_layout)
+        .expect("allocate_sequential failed");
+    assert_eq!(allocation.len(), 0);
+}
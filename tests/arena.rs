@@ -0,0 +1,96 @@
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::BumpArena;
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn many_small_allocations() {
+    let arena = BumpArena::new(1 << 20).expect("arena creation failed");
+    assert!(arena.capacity() >= 1 << 20);
+
+    let mut addresses = Vec::new();
+    for i in 0..4096 {
+        let align = 1 << (i % 5);
+        let layout = Layout::from_size_align(1 + i % 24, align).unwrap();
+
+        let mut allocation = arena.allocate(layout).expect("allocate failed");
+        assert_eq!(allocation.as_mut_ptr() as usize % align, 0);
+        assert_eq!(allocation.len(), layout.size());
+        unsafe { allocation.as_mut().fill(i as u8) };
+
+        addresses.push((allocation.as_mut_ptr() as usize, layout.size(), i as u8));
+    }
+
+    // The allocations should all fit in one mapping, without overlapping.
+    addresses.sort_unstable();
+    let (first, _, _) = addresses[0];
+    let (last, last_size, _) = addresses[addresses.len() - 1];
+    assert!(last + last_size - first <= arena.capacity());
+    for window in addresses.windows(2) {
+        let (address, size, _) = window[0];
+        assert!(address + size <= window[1].0);
+    }
+
+    for (address, size, value) in addresses {
+        let bytes = unsafe { std::slice::from_raw_parts(address as *const u8, size) };
+        assert!(bytes.iter().all(|&byte| byte == value));
+    }
+}
+
+#[test]
+fn grow_last_allocation_in_place() {
+    let arena = BumpArena::new(page_size::get()).expect("arena creation failed");
+
+    let first_layout = Layout::from_size_align(16, 8).unwrap();
+    let first_allocation = arena.allocate(first_layout).expect("allocate failed");
+
+    let initial_layout = Layout::from_size_align(16, 8).unwrap();
+    let initial_allocation = arena.allocate(initial_layout).expect("allocate failed");
+
+    let grown_layout = Layout::from_size_align(64, 8).unwrap();
+    let grown_allocation = unsafe {
+        arena
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(
+        grown_allocation.as_mut_ptr(),
+        initial_allocation.as_mut_ptr()
+    );
+    assert_eq!(arena.used(), 16 + 64);
+
+    // Older allocations have to be moved.
+    let moved_allocation = unsafe {
+        arena
+            .grow(
+                first_allocation.as_non_null_ptr(),
+                first_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_ne!(moved_allocation.as_mut_ptr(), first_allocation.as_mut_ptr());
+
+    // The arena should refuse allocations that do not fit.
+    let huge_layout = Layout::from_size_align(page_size::get(), 8).unwrap();
+    assert!(arena.allocate(huge_layout).is_err());
+}
+
+#[test]
+fn vec_in_arena() {
+    let arena = BumpArena::new(1 << 20).expect("arena creation failed");
+
+    let mut vec = Vec::new_in(&arena);
+    for i in 0..10000u32 {
+        vec.push(i);
+    }
+
+    // The only allocation is always the most recent, so it grows in place.
+    assert!(arena.used() <= vec.capacity() * 4);
+    assert!(vec.iter().copied().eq(0..10000));
+}
@@ -0,0 +1,74 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+//! Runs with both the `libc` backend and, with the `raw-syscalls` feature,
+//! the raw system calls, which must behave in the same way.
+
+use mmap_allocator::{MMapAllocator, MMapError};
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn allocate_grow_shrink() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+    let page_size = page_size::get();
+
+    let layout = Layout::from_size_align(page_size, 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    let grown_layout = Layout::from_size_align(4 * page_size, 1).unwrap();
+    let mut allocation =
+        unsafe { allocator.grow(allocation.cast(), layout, grown_layout) }.expect("grow failed");
+    assert_eq!(allocation.len(), 4 * page_size);
+    assert!(unsafe { allocation.as_ref() }[..page_size]
+        .iter()
+        .all(|&byte| byte == 42));
+    unsafe { allocation.as_mut().fill(43) };
+
+    let allocation = unsafe { allocator.shrink(allocation.cast(), grown_layout, layout) }
+        .expect("shrink failed");
+    assert!(unsafe { allocation.as_ref() }
+        .iter()
+        .all(|&byte| byte == 43));
+
+    unsafe { allocator.deallocate(allocation.cast(), layout) };
+}
+
+#[test]
+fn report_errors() {
+    let fd = unsafe { libc::open(b"/dev/zero\0".as_ptr().cast(), libc::O_RDONLY) };
+    assert_ne!(fd, -1, "open failed");
+
+    // Errors are reported with the same error numbers, whether they are read
+    // from `errno` or returned by the kernel.
+    let allocator = MMapAllocator::file_backed(fd, 0);
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    assert_eq!(
+        allocator.try_allocate(layout),
+        Err(MMapError::MmapFailed(libc::EACCES))
+    );
+
+    let allocator = MMapAllocator::file_backed(-1, 0);
+    assert_eq!(
+        allocator.try_allocate(layout),
+        Err(MMapError::FstatFailed(libc::EBADF))
+    );
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+#[cfg(all(
+    feature = "raw-syscalls",
+    not(feature = "syscall-hooks"),
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn raw_syscalls_preserve_errno() {
+    unsafe { *libc::__errno_location() = libc::ENOENT };
+
+    let allocator = MMapAllocator::file_backed(-1, 0);
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    assert!(allocator.try_allocate(layout).is_err());
+
+    assert_eq!(unsafe { *libc::__errno_location() }, libc::ENOENT);
+}
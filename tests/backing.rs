@@ -0,0 +1,84 @@
+use mmap_allocator::{Backing, MMapAllocator};
+
+#[test]
+fn anonymous_by_default() {
+    assert_eq!(MMapAllocator::new().backing(), Backing::Anonymous);
+    assert_eq!(MMapAllocator::with_stats().backing(), Backing::Anonymous);
+}
+
+#[test]
+#[cfg(unix)]
+fn shared_anonymous() {
+    assert_eq!(MMapAllocator::shared().backing(), Backing::SharedAnonymous);
+}
+
+// The allocators are never used, so the file descriptors do not need to be
+// open.
+#[test]
+#[cfg(unix)]
+fn file_backed() {
+    assert_eq!(
+        MMapAllocator::file_backed(42, 0).backing(),
+        Backing::File {
+            fd: 42,
+            private: false
+        }
+    );
+    assert_eq!(
+        MMapAllocator::cow_file(42, 0).backing(),
+        Backing::File {
+            fd: 42,
+            private: true
+        }
+    );
+    assert_eq!(
+        MMapAllocator::sealed_memfd(42).backing(),
+        Backing::File {
+            fd: 42,
+            private: false
+        }
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn persistent_memory() {
+    assert_eq!(
+        MMapAllocator::pmem_file(42, 0).backing(),
+        Backing::PersistentMemory { fd: 42 }
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn huge_pages() {
+    use mmap_allocator::HugePageSize;
+
+    assert_eq!(
+        MMapAllocator::with_huge_pages(HugePageSize::Size2MiB).backing(),
+        Backing::HugePages {
+            size: HugePageSize::Size2MiB,
+            shared: false
+        }
+    );
+
+    let allocator = MMapAllocator::builder()
+        .huge_pages(HugePageSize::Default)
+        .shared()
+        .build()
+        .unwrap();
+    assert_eq!(
+        allocator.backing(),
+        Backing::HugePages {
+            size: HugePageSize::Default,
+            shared: true
+        }
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn kept_by_with_mapper() {
+    let allocator = MMapAllocator::shared().with_mapper(mmap_allocator::SystemMapper);
+    assert_eq!(allocator.backing(), Backing::SharedAnonymous);
+}
@@ -0,0 +1,131 @@
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+#[cfg(unix)]
+mod common;
+
+use mmap_allocator::{BuildError, MMapAllocator, Protection};
+use std::alloc::{Allocator, Layout};
+#[cfg(unix)]
+use std::ptr;
+
+#[test]
+fn build_default() {
+    let allocator = MMapAllocator::builder().build().expect("build failed");
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn build_with_stats_and_min_align() {
+    let allocator = MMapAllocator::builder()
+        .stats(true)
+        .min_align(256)
+        .zero_on_free(true)
+        .build()
+        .expect("build failed");
+
+    let layout = Layout::from_size_align(0, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % 256, 0);
+
+    let layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocator.live_allocations(), 1);
+    assert_eq!(allocator.bytes_mapped(), 2 * page_size::get());
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn build_with_guard_pages_and_populate() {
+    let allocator = MMapAllocator::builder()
+        .guard_pages(1)
+        .populate(true)
+        .stats(true)
+        .build()
+        .expect("build failed");
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    // Guard pages are not part of the memory block.
+    assert_eq!(allocator.bytes_mapped(), page_size::get());
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(allocation.as_mut_ptr().add(allocation.len()), 42);
+    });
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(unix)]
+fn build_read_only() {
+    let allocator = MMapAllocator::builder()
+        .protection(Protection::READ)
+        .build()
+        .expect("build failed");
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(allocation.as_mut_ptr(), 42);
+    });
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn reject_incompatible_options() {
+    assert_eq!(
+        MMapAllocator::builder().min_align(3).build().unwrap_err(),
+        BuildError::MinAlignNotPowerOfTwo
+    );
+    assert_eq!(
+        MMapAllocator::builder()
+            .protection(Protection::READ)
+            .zero_on_free(true)
+            .build()
+            .unwrap_err(),
+        BuildError::ZeroOnFreeNotWritable
+    );
+    assert_eq!(
+        MMapAllocator::builder()
+            .protection(Protection::READ)
+            .poison(0xde)
+            .build()
+            .unwrap_err(),
+        BuildError::PoisonNotWritable
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn reject_incompatible_huge_pages() {
+    use mmap_allocator::HugePageSize;
+
+    assert_eq!(
+        MMapAllocator::builder()
+            .huge_pages(HugePageSize::Size2MiB)
+            .guard_pages(1)
+            .build()
+            .unwrap_err(),
+        BuildError::GuardPagesWithHugePages
+    );
+    assert_eq!(
+        MMapAllocator::builder()
+            .file_backed(-1, 0)
+            .huge_pages(HugePageSize::Size2MiB)
+            .build()
+            .unwrap_err(),
+        BuildError::HugePagesWithFile
+    );
+}
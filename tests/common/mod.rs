@@ -0,0 +1,28 @@
+/// Runs `f` in a forked child process and asserts that it is killed by a
+/// memory access fault.
+pub fn assert_faults(f: impl FnOnce()) {
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1, "fork failed");
+
+    if pid == 0 {
+        f();
+
+        // The child should not get here, report it through the exit status.
+        unsafe { libc::_exit(0) };
+    }
+
+    let mut status = 0;
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "waitpid failed");
+
+    assert!(
+        libc::WIFSIGNALED(status),
+        "child exited normally instead of faulting"
+    );
+    let signal = libc::WTERMSIG(status);
+    assert!(
+        signal == libc::SIGSEGV || signal == libc::SIGBUS,
+        "child was killed by unexpected signal {}",
+        signal
+    );
+}
@@ -0,0 +1,88 @@
+#![cfg(all(target_os = "linux", feature = "syscall-hooks"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::hooks::{self, SyscallCounts};
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::cell::Cell;
+use std::ffi::c_void;
+
+std::thread_local! {
+    static INTERRUPTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Fails with `EINTR` once, then always returns `true`.
+fn interrupt_once() -> bool {
+    let interrupted = INTERRUPTED.with(|interrupted| interrupted.replace(true));
+    if !interrupted {
+        unsafe { *libc::__errno_location() = libc::EINTR };
+    }
+    interrupted
+}
+
+unsafe fn interrupted_mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: libc::c_int,
+    flags: libc::c_int,
+    fd: libc::c_int,
+    offset: libc::off_t,
+) -> *mut c_void {
+    if !interrupt_once() {
+        return libc::MAP_FAILED;
+    }
+    libc::mmap(addr, len, prot, flags, fd, offset)
+}
+
+unsafe fn interrupted_munmap(addr: *mut c_void, len: usize) -> libc::c_int {
+    if !interrupt_once() {
+        return -1;
+    }
+    libc::munmap(addr, len)
+}
+
+#[test]
+fn retry_interrupted_mmap() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    INTERRUPTED.with(|interrupted| interrupted.set(false));
+    hooks::reset_syscall_counts();
+    hooks::set_mmap_hook(Some(interrupted_mmap));
+    let allocation = allocator.allocate(layout);
+    hooks::set_mmap_hook(None);
+
+    let allocation = allocation.expect("allocate failed");
+    assert_eq!(
+        hooks::syscall_counts(),
+        SyscallCounts {
+            mmap: 2,
+            ..SyscallCounts::default()
+        }
+    );
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn retry_interrupted_munmap() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    INTERRUPTED.with(|interrupted| interrupted.set(false));
+    hooks::reset_syscall_counts();
+    hooks::set_munmap_hook(Some(interrupted_munmap));
+    let res = unsafe { allocator.try_deallocate(allocation.as_non_null_ptr(), layout) };
+    hooks::set_munmap_hook(None);
+
+    assert_eq!(res, Ok(()));
+    assert_eq!(
+        hooks::syscall_counts(),
+        SyscallCounts {
+            munmap: 2,
+            ..SyscallCounts::default()
+        }
+    );
+}
@@ -0,0 +1,127 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+
+use mmap_allocator::{BuildError, MMapAllocator, MMapError};
+use std::alloc::{AllocError, Allocator, Layout};
+
+#[test]
+fn read_only_file() {
+    let fd = unsafe { libc::open(b"/dev/zero\0".as_ptr().cast(), libc::O_RDONLY) };
+    assert_ne!(fd, -1, "open failed");
+
+    // Shared mappings can only be writable if the file is open for writing.
+    let allocator = MMapAllocator::file_backed(fd, 0);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    assert_eq!(
+        allocator.try_allocate(layout),
+        Err(MMapError::MmapFailed(libc::EACCES))
+    );
+    assert_eq!(allocator.allocate(layout), Err(AllocError));
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn size_overflow() {
+    let allocator = MMapAllocator::with_guard_pages(usize::MAX);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    assert_eq!(allocator.try_allocate(layout), Err(MMapError::SizeOverflow));
+}
+
+#[test]
+fn size_exceeds_isize_max() {
+    let allocator = MMapAllocator::new();
+
+    // The largest size a `Layout` can have is `isize::MAX`, which is not a
+    // multiple of the page size.
+    let layout = Layout::from_size_align(isize::MAX as usize, 1).unwrap();
+    assert_eq!(allocator.try_allocate(layout), Err(MMapError::SizeOverflow));
+    assert_eq!(allocator.allocate(layout), Err(AllocError));
+
+    // The size fits once rounded, but not together with the guard pages.
+    let size = isize::MAX as usize + 1 - page_size::get();
+    let layout = Layout::from_size_align(size, 1).unwrap();
+    let allocator = MMapAllocator::with_guard_pages(1);
+    assert_eq!(allocator.try_allocate(layout), Err(MMapError::SizeOverflow));
+
+    // Nor with the padding needed to align the mapping.
+    let align = isize::MAX as usize / 2 + 1;
+    let layout = Layout::from_size_align(align, align).unwrap();
+    assert_eq!(
+        allocator.try_allocate(layout),
+        Err(MMapError::AlignmentTooLarge)
+    );
+
+    let layout = Layout::from_size_align(size, 1).unwrap();
+    let allocator = MMapAllocator::new();
+    assert_eq!(
+        unsafe { allocator.allocate_with_offset(layout, page_size::get() / 2) },
+        Err(AllocError)
+    );
+}
+
+#[test]
+fn grow_exceeds_isize_max() {
+    let allocator = MMapAllocator::new();
+
+    let old_layout = Layout::from_size_align(10, 16).unwrap();
+    let mut allocation = allocator.allocate(old_layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    let new_layout = Layout::from_size_align(isize::MAX as usize, 1).unwrap();
+    let res = unsafe { allocator.grow(allocation.cast(), old_layout, new_layout) };
+    assert_eq!(res, Err(AllocError));
+
+    // The memory block is left untouched.
+    assert!(unsafe { allocation.as_ref() }
+        .iter()
+        .all(|&byte| byte == 42));
+
+    unsafe { allocator.deallocate(allocation.cast(), old_layout) };
+}
+
+#[test]
+fn try_new_checks_options() {
+    let allocator =
+        MMapAllocator::try_new(MMapAllocator::builder().stats(true)).expect("try_new failed");
+
+    // The probe mapping is already released.
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+
+    assert_eq!(
+        MMapAllocator::try_new(MMapAllocator::builder().min_align(3)).unwrap_err(),
+        MMapError::InvalidOptions(BuildError::MinAlignNotPowerOfTwo)
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn try_new_probes_huge_pages() {
+    use mmap_allocator::HugePageSize;
+
+    let meminfo = std::fs::read_to_string("/proc/meminfo").expect("cannot read /proc/meminfo");
+    let huge_pages_free = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("HugePages_Free:"))
+        .and_then(|free| free.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let res = MMapAllocator::try_new(MMapAllocator::builder().huge_pages(HugePageSize::Default));
+    if huge_pages_free > 0 {
+        assert!(res.is_ok());
+    } else {
+        // Without reserved huge pages, `MAP_HUGETLB` mappings cannot be
+        // backed.
+        assert_eq!(res.unwrap_err(), MMapError::MmapFailed(libc::ENOMEM));
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn try_new_probes_numa_node() {
+    let res = MMapAllocator::try_new(MMapAllocator::builder().numa_node(u32::MAX));
+    assert!(matches!(res, Err(MMapError::MbindFailed(_))));
+}
@@ -0,0 +1,369 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+
+/// Creates an anonymous in-memory file of `size` bytes.
+fn create_memfd(size: usize) -> libc::c_int {
+    let fd =
+        unsafe { libc::memfd_create(b"mmap-allocator-test\0".as_ptr().cast(), libc::MFD_CLOEXEC) };
+    assert_ne!(fd, -1, "memfd_create failed");
+
+    let res = unsafe { libc::ftruncate(fd, size as libc::off_t) };
+    assert_ne!(res, -1, "ftruncate failed");
+
+    fd
+}
+
+/// Reads `len` bytes at `offset` from `fd`.
+fn read_at(fd: libc::c_int, offset: usize, len: usize) -> Vec<u8> {
+    let mut buf = vec![0; len];
+    let res = unsafe { libc::pread(fd, buf.as_mut_ptr().cast(), len, offset as libc::off_t) };
+    assert_eq!(res, len as isize, "pread failed");
+    buf
+}
+
+#[test]
+fn write_through_mapping() {
+    let fd = create_memfd(2 * page_size::get());
+    let allocator = MMapAllocator::file_backed(fd, 0);
+
+    let layout = Layout::from_size_align(5, 1).unwrap();
+
+    let mut first_allocation = allocator.allocate(layout).expect("allocate failed");
+    let mut second_allocation = allocator.allocate(layout).expect("allocate failed");
+
+    unsafe {
+        first_allocation.as_mut()[..5].copy_from_slice(b"hello");
+        second_allocation.as_mut()[..5].copy_from_slice(b"world");
+    }
+
+    // Each allocation should be backed by the next page of the file.
+    assert_eq!(read_at(fd, 0, 5), b"hello");
+    assert_eq!(read_at(fd, page_size::get(), 5), b"world");
+
+    unsafe {
+        allocator.deallocate(first_allocation.as_non_null_ptr(), layout);
+        allocator.deallocate(second_allocation.as_non_null_ptr(), layout);
+    }
+
+    // Deallocating should not close the file.
+    assert_eq!(read_at(fd, 0, 5), b"hello");
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn grow_into_new_region() {
+    let fd = create_memfd(4 * page_size::get());
+    let allocator = MMapAllocator::file_backed(fd, page_size::get() as u64);
+
+    let initial_layout = Layout::from_size_align(5, 1).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut()[..5].copy_from_slice(b"hello") };
+
+    let grown_layout = Layout::from_size_align(page_size::get() + 5, 1).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+
+    // The grown allocation should be copied to the following region.
+    assert_eq!(read_at(fd, 2 * page_size::get(), 5), b"hello");
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn copy_on_write_mapping() {
+    let fd = create_memfd(2 * page_size::get());
+    let pattern: Vec<u8> = (0..2 * page_size::get()).map(|i| i as u8).collect();
+    let res = unsafe { libc::pwrite(fd, pattern.as_ptr().cast(), pattern.len(), 0) };
+    assert_eq!(res, pattern.len() as isize, "pwrite failed");
+
+    let allocator = MMapAllocator::cow_file(fd, 0);
+
+    let layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+
+    // The mapping should start with the contents of the file.
+    let allocation_slice = unsafe { allocation.as_mut() };
+    assert_eq!(allocation_slice, &pattern[..page_size::get()]);
+
+    // Writes should stay private to the mapping.
+    allocation_slice.fill(42);
+    assert_eq!(
+        read_at(fd, 0, page_size::get()),
+        &pattern[..page_size::get()]
+    );
+
+    // The next region of the file has to be cleared explicitly.
+    let zeroed_allocation = allocator.allocate_zeroed(layout).expect("allocate failed");
+    let zeroed_slice = unsafe { zeroed_allocation.as_ref() };
+    assert!(zeroed_slice.iter().all(|&byte| byte == 0));
+    assert_eq!(
+        read_at(fd, page_size::get(), page_size::get()),
+        &pattern[page_size::get()..]
+    );
+
+    unsafe {
+        allocator.deallocate(allocation.as_non_null_ptr(), layout);
+        allocator.deallocate(zeroed_allocation.as_non_null_ptr(), layout);
+    }
+
+    assert_eq!(read_at(fd, 0, pattern.len()), pattern);
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn grow_zeroed_copy_on_write_mapping() {
+    let fd = create_memfd(3 * page_size::get());
+    let pattern = vec![0xffu8; 3 * page_size::get()];
+    let res = unsafe { libc::pwrite(fd, pattern.as_ptr().cast(), pattern.len(), 0) };
+    assert_eq!(res, pattern.len() as isize, "pwrite failed");
+
+    let allocator = MMapAllocator::cow_file(fd, 0);
+
+    let initial_layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    let grown_layout = Layout::from_size_align(2 * page_size::get(), 1).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow_zeroed(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+
+    // The old contents are kept, and only the new memory is zeroed.
+    let allocation_slice = unsafe { grown_allocation.as_ref() };
+    let (old_slice, new_slice) = allocation_slice.split_at(page_size::get());
+    assert!(old_slice.iter().all(|&byte| byte == 0xff));
+    assert!(new_slice.iter().all(|&byte| byte == 0));
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn map_sealed_memfd() {
+    let fd = unsafe {
+        libc::memfd_create(
+            b"mmap-allocator-test\0".as_ptr().cast(),
+            libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+        )
+    };
+    assert_ne!(fd, -1, "memfd_create failed");
+
+    let res = unsafe { libc::write(fd, b"hello".as_ptr().cast(), 5) };
+    assert_eq!(res, 5, "write failed");
+
+    let seals = libc::F_SEAL_WRITE | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW;
+    let res = unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) };
+    assert_ne!(res, -1, "fcntl failed");
+
+    let allocator = MMapAllocator::sealed_memfd(fd);
+
+    let layout = Layout::from_size_align(5, 1).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(unsafe { &allocation.as_ref()[..5] }, b"hello");
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    // A writable shared mapping of the sealed file should be refused.
+    let writable_allocator = MMapAllocator::file_backed(fd, 0);
+    assert!(writable_allocator.allocate(layout).is_err());
+
+    // Deallocating should not close the file.
+    assert_eq!(read_at(fd, 0, 5), b"hello");
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn allocate_zeroed_file_mapping() {
+    for private in [false, true] {
+        let fd = create_memfd(2 * page_size::get());
+        let pattern = vec![0xffu8; 2 * page_size::get()];
+        let res = unsafe { libc::pwrite(fd, pattern.as_ptr().cast(), pattern.len(), 0) };
+        assert_eq!(res, pattern.len() as isize, "pwrite failed");
+
+        let allocator = if private {
+            MMapAllocator::cow_file(fd, 0)
+        } else {
+            MMapAllocator::file_backed(fd, 0)
+        };
+
+        let layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+
+        // `allocate` maps the contents of the file as they are.
+        let allocation = allocator.allocate(layout).expect("allocate failed");
+        let allocation_slice = unsafe { allocation.as_ref() };
+        assert!(allocation_slice.iter().all(|&byte| byte == 0xff));
+
+        let zeroed_allocation = allocator.allocate_zeroed(layout).expect("allocate failed");
+        let zeroed_slice = unsafe { zeroed_allocation.as_ref() };
+        assert!(zeroed_slice.iter().all(|&byte| byte == 0));
+
+        // Only shared mappings write the zeros through to the file.
+        let expected = if private { 0xff } else { 0 };
+        assert!(read_at(fd, page_size::get(), page_size::get())
+            .iter()
+            .all(|&byte| byte == expected));
+
+        unsafe {
+            allocator.deallocate(allocation.as_non_null_ptr(), layout);
+            allocator.deallocate(zeroed_allocation.as_non_null_ptr(), layout);
+            libc::close(fd);
+        }
+    }
+}
+
+#[test]
+fn try_new_keeps_file_offset() {
+    let fd = create_memfd(page_size::get());
+    let allocator = MMapAllocator::try_new(MMapAllocator::builder().file_backed(fd, 0))
+        .expect("try_new failed");
+
+    // The probe mapping does not use up the start of the file.
+    let layout = Layout::from_size_align(5, 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut()[..5].copy_from_slice(b"hello") };
+    assert_eq!(read_at(fd, 0, 5), b"hello");
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn pmem_file_requires_dax() {
+    use mmap_allocator::MMapError;
+
+    // Regular files do not support synchronous mappings.
+    let fd = create_memfd(page_size::get());
+    let allocator = MMapAllocator::pmem_file(fd, 0);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let res = allocator.try_allocate(layout);
+    assert_eq!(res, Err(MMapError::MmapFailed(libc::EOPNOTSUPP)));
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn flush_file_mapping() {
+    let fd = create_memfd(page_size::get());
+    let allocator = MMapAllocator::file_backed(fd, 0);
+
+    let layout = Layout::from_size_align(100, 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe {
+        allocation.as_mut()[..5].copy_from_slice(b"hello");
+        allocator.flush(allocation.as_non_null_ptr(), layout);
+    }
+    assert_eq!(read_at(fd, 0, 5), b"hello");
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn round_misaligned_offset() {
+    let fd = create_memfd(2 * page_size::get());
+    let allocator = MMapAllocator::file_backed(fd, 10);
+
+    // The offset is rounded up to the next page of the file.
+    let layout = Layout::from_size_align(5, 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut()[..5].copy_from_slice(b"hello") };
+    assert_eq!(read_at(fd, page_size::get(), 5), b"hello");
+    assert_eq!(read_at(fd, 10, 5), [0; 5]);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn reject_regions_past_end_of_file() {
+    use mmap_allocator::MMapError;
+
+    // The partial page at the end of the file can still be mapped.
+    let fd = create_memfd(page_size::get() + 1);
+    let allocator = MMapAllocator::file_backed(fd, 0);
+
+    let layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+    let first_allocation = allocator.try_allocate(layout).expect("allocate failed");
+    let second_allocation = allocator.try_allocate(layout).expect("allocate failed");
+
+    // Nothing is left for a third page, and failing does not use up the
+    // remaining part of the file.
+    assert_eq!(allocator.try_allocate(layout), Err(MMapError::FileTooSmall));
+    let res = unsafe { libc::ftruncate(fd, 3 * page_size::get() as libc::off_t) };
+    assert_ne!(res, -1, "ftruncate failed");
+    let mut third_allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { third_allocation.as_mut()[..5].copy_from_slice(b"hello") };
+    assert_eq!(read_at(fd, 2 * page_size::get(), 5), b"hello");
+
+    unsafe {
+        allocator.deallocate(first_allocation.as_non_null_ptr(), layout);
+        allocator.deallocate(second_allocation.as_non_null_ptr(), layout);
+        allocator.deallocate(third_allocation.as_non_null_ptr(), layout);
+    }
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn sync_file_mapping() {
+    use std::fs::{self, OpenOptions};
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+
+    let path = std::env::temp_dir().join(format!("mmap-allocator-msync-{}", std::process::id()));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .expect("cannot create the file");
+    file.set_len(page_size::get() as u64)
+        .expect("cannot resize the file");
+    let allocator = MMapAllocator::file_backed(file.as_raw_fd(), 0);
+
+    let layout = Layout::from_size_align(100, 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe {
+        allocation.as_mut()[..5].copy_from_slice(b"hello");
+        allocator
+            .sync_to_file(allocation.as_non_null_ptr(), layout, true)
+            .expect("sync_to_file failed");
+    }
+
+    // The writes are visible to a file opened afterwards.
+    let mut contents = Vec::new();
+    fs::File::open(&path)
+        .and_then(|mut file| file.read_to_end(&mut contents))
+        .expect("cannot read the file");
+    assert_eq!(&contents[..5], b"hello");
+
+    // Scheduling the writes only succeeds as well.
+    unsafe { allocator.sync_to_file(allocation.as_non_null_ptr(), layout, false) }
+        .expect("sync_to_file failed");
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    drop(file);
+    fs::remove_file(&path).expect("cannot remove the file");
+}
@@ -0,0 +1,28 @@
+use mmap_allocator::MMapAllocator;
+
+#[global_allocator]
+static ALLOCATOR: MMapAllocator = MMapAllocator::new();
+
+#[test]
+fn allocation_heavy_code() {
+    let mut strings = Vec::new();
+    for i in 0..1000 {
+        strings.push(format!("string number {}", i));
+    }
+
+    // Growing and shrinking go through `realloc`.
+    strings.retain(|string| string.ends_with('7'));
+    strings.shrink_to_fit();
+    for string in &mut strings {
+        string.push_str(&"x".repeat(2 * page_size::get()));
+    }
+
+    assert_eq!(strings.len(), 100);
+    for (i, string) in strings.iter().enumerate() {
+        assert!(string.starts_with(&format!("string number {}", 10 * i + 7)));
+        assert_eq!(
+            string.len(),
+            string.trim_end_matches('x').len() + 2 * page_size::get()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+mod common;
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::ptr;
+
+#[test]
+fn write_inside_guarded_allocation() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % page_size::get(), 0);
+
+    let allocation_slice = unsafe { allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), 2 * page_size::get());
+
+    *allocation_slice.first_mut().unwrap() = 42;
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn write_past_end_faults() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(allocation.as_mut_ptr().add(allocation.len()), 42);
+    });
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn write_before_start_faults() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(allocation.as_mut_ptr().sub(1), 42);
+    });
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn grow_keeps_guard_pages() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    let grown_layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(grown_allocation.len(), 2 * page_size::get());
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(
+            grown_allocation.as_mut_ptr().add(grown_allocation.len()),
+            42,
+        );
+    });
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+fn shrink_moves_trailing_guard_pages() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let initial_layout = Layout::from_size_align(2 * page_size::get() + 10, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    let shrunk_layout = Layout::from_size_align(10, 16).unwrap();
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(shrunk_allocation.len(), page_size::get());
+    assert_eq!(
+        initial_allocation.as_mut_ptr(),
+        shrunk_allocation.as_mut_ptr()
+    );
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(
+            shrunk_allocation.as_mut_ptr().add(shrunk_allocation.len()),
+            42,
+        );
+    });
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+}
@@ -0,0 +1,84 @@
+#![cfg(all(feature = "test-huge-pages", target_os = "linux"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{HugePageSize, MMapAllocator};
+use std::alloc::{Allocator, Layout};
+
+/// Returns whether the host has any huge pages reserved for `MAP_HUGETLB`.
+fn huge_pages_reserved() -> bool {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").expect("cannot read /proc/meminfo");
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("HugePages_Total:"))
+        .and_then(|total| total.trim().parse::<usize>().ok())
+        .is_some_and(|total| total > 0)
+}
+
+#[test]
+fn allocate_deallocate_huge_page() {
+    if !huge_pages_reserved() {
+        eprintln!("no huge pages reserved, skipping");
+        return;
+    }
+
+    let allocator = MMapAllocator::with_huge_pages(HugePageSize::Default);
+    let huge_page_size = HugePageSize::Default.size();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % huge_page_size, 0);
+
+    let allocation_slice = unsafe { allocation.as_mut() };
+
+    // A small allocation should still be backed by a full huge page.
+    assert_eq!(allocation_slice.len(), huge_page_size);
+
+    *allocation_slice.first_mut().unwrap() = 42;
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn shrink_huge_pages() {
+    if !huge_pages_reserved() {
+        eprintln!("no huge pages reserved, skipping");
+        return;
+    }
+
+    let allocator = MMapAllocator::with_huge_pages(HugePageSize::Default);
+    let huge_page_size = HugePageSize::Default.size();
+
+    let initial_layout = Layout::from_size_align(huge_page_size + 10, 16).unwrap();
+    let initial_allocation = match allocator.allocate(initial_layout) {
+        Ok(allocation) => allocation,
+        Err(_) => {
+            eprintln!("not enough huge pages reserved, skipping");
+            return;
+        }
+    };
+    assert_eq!(initial_allocation.len(), 2 * huge_page_size);
+
+    let shrunk_layout = Layout::from_size_align(10, 16).unwrap();
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(shrunk_allocation.len(), huge_page_size);
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+}
+
+#[test]
+fn mapping_size_rounds_to_huge_pages() {
+    let allocator = MMapAllocator::with_huge_pages(HugePageSize::Size2MiB);
+
+    let layout = Layout::from_size_align((2 << 20) + 10, 16).unwrap();
+    assert_eq!(allocator.mapping_size(layout).unwrap(), 2 * (2 << 20));
+}
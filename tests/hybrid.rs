@@ -0,0 +1,77 @@
+#![cfg(feature = "std")]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{HybridAllocator, MMapAllocator};
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn small_allocations_use_global() {
+    let allocator = HybridAllocator::with_allocator(MMapAllocator::with_stats(), 1024);
+
+    let small_layout = Layout::from_size_align(16, 8).unwrap();
+    let small_allocation = allocator.allocate(small_layout).expect("allocate failed");
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+
+    let large_layout = Layout::from_size_align(1024, 8).unwrap();
+    let large_allocation = allocator.allocate(large_layout).expect("allocate failed");
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 1);
+    assert_eq!(large_allocation.len(), page_size::get());
+
+    unsafe { allocator.deallocate(small_allocation.as_non_null_ptr(), small_layout) };
+    unsafe { allocator.deallocate(large_allocation.as_non_null_ptr(), large_layout) };
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+}
+
+#[test]
+fn vec_grows_across_threshold() {
+    let allocator = HybridAllocator::with_allocator(MMapAllocator::with_stats(), 1024);
+
+    let mut vec: Vec<u32, _> = Vec::new_in(&allocator);
+    for i in 0..64 {
+        vec.push(i);
+    }
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+
+    // Growing past the threshold moves the contents to a mapping.
+    for i in 64..4096 {
+        vec.push(i);
+    }
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 1);
+    assert!(vec.iter().copied().eq(0..4096));
+
+    // Shrinking below the threshold moves them back.
+    vec.truncate(16);
+    vec.shrink_to_fit();
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+    assert!(vec.iter().copied().eq(0..16));
+
+    drop(vec);
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+}
+
+#[test]
+fn grow_zeroed_across_threshold() {
+    let allocator = HybridAllocator::new(1024);
+
+    let initial_layout = Layout::from_size_align(16, 8).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut().fill(42) };
+
+    let grown_layout = Layout::from_size_align(2048, 8).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow_zeroed(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+
+    let allocation_slice = unsafe { grown_allocation.as_ref() };
+    assert!(allocation_slice[..16].iter().all(|&byte| byte == 42));
+    assert!(allocation_slice[16..].iter().all(|&byte| byte == 0));
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
@@ -1,11 +1,16 @@
 #![feature(allocator_api)]
 #![feature(slice_ptr_get)]
 
+#[cfg(unix)]
+mod common;
+
 use mmap_allocator::MMapAllocator;
 use std::alloc::{Allocator, Layout};
+#[cfg(unix)]
+use std::ptr;
 
 fn allocate_deallocate(size: usize) -> usize {
-    let allocator = MMapAllocator;
+    let allocator = MMapAllocator::new();
 
     let layout = Layout::from_size_align(size, 16).unwrap();
     let mut allocation = allocator.allocate(layout).expect("allocate failed");
@@ -39,7 +44,7 @@ fn allocate_deallocate_multi_page() {
 
 #[test]
 fn grow_inside_last_page() {
-    let allocator = MMapAllocator;
+    let allocator = MMapAllocator::new();
 
     let initial_layout = Layout::from_size_align(10, 16).unwrap();
     let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
@@ -68,7 +73,7 @@ fn grow_inside_last_page() {
 
 #[test]
 fn grow_outside_last_page() {
-    let allocator = MMapAllocator;
+    let allocator = MMapAllocator::new();
 
     let initial_layout = Layout::from_size_align(10, 16).unwrap();
     let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
@@ -89,6 +94,9 @@ fn grow_outside_last_page() {
     };
     let allocation_slice = unsafe { grown_allocation.as_mut() };
     assert_eq!(allocation_slice.len(), 2 * page_size::get()); // The size should be double
+
+    // On Linux, `mremap` may extend the mapping in place
+    #[cfg(not(target_os = "linux"))]
     assert_ne!(initial_allocation.as_ptr(), grown_allocation.as_ptr()); // The map should be somewhere else
 
     // The data should be correctly transferred
@@ -97,9 +105,44 @@ fn grow_outside_last_page() {
     unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
 }
 
+#[test]
+fn grow_keeps_end_of_last_page() {
+    // Guard pages force `grow` to copy the contents to a new mapping.
+    #[cfg(unix)]
+    let allocator = MMapAllocator::with_guard_pages(1);
+    #[cfg(not(unix))]
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    // The whole page is usable, not only the requested size.
+    unsafe { *initial_allocation.as_mut().last_mut().unwrap() = 42 };
+
+    let grown_layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_ne!(
+        grown_allocation.as_mut_ptr(),
+        initial_allocation.as_mut_ptr()
+    );
+
+    let allocation_slice = unsafe { grown_allocation.as_ref() };
+    assert_eq!(allocation_slice[page_size::get() - 1], 42);
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
 #[test]
 fn shrink_inside_last_page() {
-    let allocator = MMapAllocator;
+    let allocator = MMapAllocator::new();
 
     let initial_layout = Layout::from_size_align(page_size::get() + 16, 64).unwrap();
     let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
@@ -125,7 +168,7 @@ fn shrink_inside_last_page() {
 
 #[test]
 fn shrink_outside_last_page() {
-    let allocator = MMapAllocator;
+    let allocator = MMapAllocator::new();
 
     let initial_layout = Layout::from_size_align(page_size::get() + 16, 64).unwrap();
     let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
@@ -151,3 +194,1426 @@ fn shrink_outside_last_page() {
 
     unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
 }
+
+#[cfg(unix)]
+fn allocate_deallocate_over_aligned(size: usize) -> usize {
+    let allocator = MMapAllocator::new();
+
+    let align = 4 * page_size::get();
+    let layout = Layout::from_size_align(size, align).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % align, 0);
+
+    let allocation_slice = unsafe { allocation.as_mut() };
+
+    let slice_size = allocation_slice.len();
+
+    *allocation_slice.first_mut().unwrap() = 42;
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    slice_size
+}
+
+#[test]
+#[cfg(unix)]
+fn allocate_deallocate_over_aligned_single_page() {
+    let mapping_size = allocate_deallocate_over_aligned(10);
+
+    // The alignment should not affect the size of the mapping.
+    assert_eq!(mapping_size, page_size::get());
+}
+
+#[test]
+#[cfg(unix)]
+fn allocate_deallocate_over_aligned_multi_page() {
+    let mapping_size = allocate_deallocate_over_aligned(page_size::get() + 10);
+
+    assert_eq!(mapping_size, page_size::get() * 2);
+}
+
+#[test]
+#[cfg(unix)]
+fn grow_to_over_aligned() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    let allocation_slice = unsafe { initial_allocation.as_mut() };
+
+    *allocation_slice.first_mut().unwrap() = 42;
+
+    let align = 4 * page_size::get();
+    let grown_layout = Layout::from_size_align(3 * page_size::get(), align).unwrap();
+    let mut grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(grown_allocation.as_mut_ptr() as usize % align, 0);
+
+    let allocation_slice = unsafe { grown_allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), 3 * page_size::get());
+    assert_eq!(*allocation_slice.first().unwrap(), 42);
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+fn grow_across_several_pages() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    let allocation_slice = unsafe { initial_allocation.as_mut() };
+
+    for (i, byte) in allocation_slice.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    let grown_layout = Layout::from_size_align(16 * page_size::get() + 10, 16).unwrap();
+    let mut grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    let allocation_slice = unsafe { grown_allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), 17 * page_size::get());
+
+    // The old contents should be intact, wherever the mapping ended up.
+    for (i, byte) in allocation_slice[..2 * page_size::get()].iter().enumerate() {
+        assert_eq!(*byte, i as u8);
+    }
+
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+#[cfg(unix)]
+fn shrink_releases_tail_pages() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    let shrunk_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(shrunk_allocation.len(), page_size::get());
+
+    // The released tail pages should no longer be mapped.
+    for page in 1..4 {
+        common::assert_faults(|| unsafe {
+            ptr::write_volatile(
+                initial_allocation.as_mut_ptr().add(page * page_size::get()),
+                42,
+            );
+        });
+    }
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+}
+
+#[test]
+fn share_allocator_by_reference() {
+    let allocator = MMapAllocator::with_zero_on_free(true);
+
+    let mut vecs: Vec<Vec<u8, &MMapAllocator>> = (0..4)
+        .map(|i| {
+            let mut vec = Vec::with_capacity_in(page_size::get() + 10, &allocator);
+            vec.resize(page_size::get() + 10, i);
+            vec
+        })
+        .collect();
+
+    // Growing one collection should not affect the others.
+    vecs[0].resize(4 * page_size::get(), 0);
+
+    for (i, vec) in vecs.iter().enumerate().skip(1) {
+        assert!(vec.iter().all(|&byte| byte == i as u8));
+    }
+    assert!(vecs[0][..page_size::get() + 10]
+        .iter()
+        .all(|&byte| byte == 0));
+
+    drop(vecs);
+}
+
+#[test]
+#[cfg(unix)]
+fn allocate_at_hint() {
+    let allocator = MMapAllocator::new();
+
+    // Ask for the pages right after an existing mapping, which are likely
+    // to be free.
+    let layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let neighbour = allocator.allocate(layout).expect("allocate failed");
+    let hint = unsafe { neighbour.as_mut_ptr().add(neighbour.len()) };
+
+    let mut allocation =
+        unsafe { allocator.allocate_at(hint.cast(), layout) }.expect("allocate_at failed");
+    let allocation_slice = unsafe { allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), 2 * page_size::get());
+
+    *allocation_slice.first_mut().unwrap() = 42;
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    unsafe { allocator.deallocate(neighbour.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn allocate_fixed_does_not_replace() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let existing = allocator.allocate(layout).expect("allocate failed");
+    let addr = existing.as_non_null_ptr();
+
+    // The range is already mapped, so it must not be clobbered.
+    assert!(unsafe { allocator.allocate_fixed(addr, layout) }.is_err());
+
+    // A misaligned address is rejected up front.
+    let misaligned_addr = unsafe { addr.add(1) };
+    assert!(unsafe { allocator.allocate_fixed(misaligned_addr, layout) }.is_err());
+
+    unsafe { allocator.deallocate(addr, layout) };
+
+    // Once the range is free again, the mapping lands exactly at `addr`.
+    let mut allocation =
+        unsafe { allocator.allocate_fixed(addr, layout) }.expect("allocate_fixed failed");
+    assert_eq!(allocation.as_non_null_ptr(), addr);
+
+    let allocation_slice = unsafe { allocation.as_mut() };
+    *allocation_slice.first_mut().unwrap() = 42;
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+/// Returns whether any page overlapping `[ptr, ptr + len)` is mapped.
+#[cfg(target_os = "linux")]
+fn is_mapped(ptr: *mut u8, len: usize) -> bool {
+    let mut residency = 0u8;
+
+    (0..len.div_ceil(page_size::get())).any(|page| unsafe {
+        // `mincore` fails with `ENOMEM` on unmapped pages.
+        libc::mincore(
+            ptr.add(page * page_size::get()).cast(),
+            page_size::get(),
+            &mut residency,
+        ) == 0
+    })
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn deallocate_over_aligned_unmaps_everything() {
+    for guard_pages in [0, 1] {
+        let allocator = MMapAllocator::with_guard_pages(guard_pages);
+        let guard_size = guard_pages * page_size::get();
+
+        for align_pages in [2, 4, 16] {
+            let align = align_pages * page_size::get();
+            let layout = Layout::from_size_align(page_size::get() + 10, align).unwrap();
+            let allocation = allocator.allocate(layout).expect("allocate failed");
+            assert_eq!(allocation.as_mut_ptr() as usize % align, 0);
+
+            // The allocation and its guard pages should be released as a
+            // whole, even if they did not start at the base of the original
+            // over-aligned mapping.
+            let region = unsafe { allocation.as_mut_ptr().sub(guard_size) };
+            let region_size = allocation.len() + 2 * guard_size;
+            assert!(is_mapped(region, region_size));
+
+            unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+            assert!(!is_mapped(region, region_size));
+        }
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn shrink_over_aligned_releases_tail_pages() {
+    for guard_pages in [0, 1] {
+        let allocator = MMapAllocator::with_guard_pages(guard_pages);
+        let guard_size = guard_pages * page_size::get();
+
+        let align = 16 * page_size::get();
+        let initial_layout = Layout::from_size_align(4 * page_size::get(), align).unwrap();
+        let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+        unsafe { initial_allocation.as_mut().fill(42) };
+        let ptr = initial_allocation.as_mut_ptr();
+
+        // Shrinking within the last page keeps the mapping as it is.
+        let same_page_layout = Layout::from_size_align(3 * page_size::get() + 1, align).unwrap();
+        let same_page_allocation = unsafe {
+            allocator
+                .shrink(
+                    initial_allocation.as_non_null_ptr(),
+                    initial_layout,
+                    same_page_layout,
+                )
+                .expect("shrink failed")
+        };
+        assert_eq!(same_page_allocation.as_mut_ptr(), ptr);
+        assert_eq!(same_page_allocation.len(), 4 * page_size::get());
+
+        // Crossing a page boundary releases the tail, but nothing before the
+        // over-aligned memory block.
+        let shrunk_layout = Layout::from_size_align(page_size::get() + 1, align).unwrap();
+        let shrunk_allocation = unsafe {
+            allocator
+                .shrink(
+                    same_page_allocation.as_non_null_ptr(),
+                    same_page_layout,
+                    shrunk_layout,
+                )
+                .expect("shrink failed")
+        };
+        assert_eq!(shrunk_allocation.as_mut_ptr(), ptr);
+        assert_eq!(shrunk_allocation.len(), 2 * page_size::get());
+
+        let allocation_slice = unsafe { shrunk_allocation.as_ref() };
+        assert!(allocation_slice.iter().all(|&byte| byte == 42));
+
+        let retained_end = unsafe { ptr.add(2 * page_size::get() + guard_size) };
+        assert!(is_mapped(
+            unsafe { ptr.sub(guard_size) },
+            2 * page_size::get() + 2 * guard_size
+        ));
+        assert!(!is_mapped(retained_end, 2 * page_size::get()));
+
+        unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+        assert!(!is_mapped(
+            unsafe { ptr.sub(guard_size) },
+            2 * page_size::get() + 2 * guard_size
+        ));
+    }
+}
+
+#[test]
+fn mapping_size_matches_allocate() {
+    let allocator = MMapAllocator::new();
+
+    let mut layouts = vec![
+        Layout::from_size_align(1, 1).unwrap(),
+        Layout::from_size_align(10, 16).unwrap(),
+        Layout::from_size_align(page_size::get(), 16).unwrap(),
+        Layout::from_size_align(page_size::get() + 1, 64).unwrap(),
+        Layout::from_size_align(5 * page_size::get() - 1, 16).unwrap(),
+    ];
+    if cfg!(unix) {
+        layouts.push(Layout::from_size_align(page_size::get() + 10, 4 * page_size::get()).unwrap());
+    }
+
+    for layout in layouts {
+        let allocation = allocator.allocate(layout).expect("allocate failed");
+        assert_eq!(allocator.mapping_size(layout).unwrap(), allocation.len());
+
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+}
+
+#[test]
+fn allocate_deallocate_zero_sized() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(0, 64).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.len(), 0);
+    assert_eq!(allocation.as_mut_ptr() as usize % 64, 0);
+
+    // No memory should be mapped for a zero-sized allocation.
+    assert_eq!(allocator.live_allocations(), 0);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn grow_zero_sized_to_one_page() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(0, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    let grown_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut grown_allocation = unsafe {
+        allocator
+            .grow_zeroed(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    let allocation_slice = unsafe { grown_allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), page_size::get());
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+
+    *allocation_slice.first_mut().unwrap() = 42;
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+fn shrink_to_zero_sized() {
+    let allocator = MMapAllocator::with_stats();
+
+    let initial_layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    let shrunk_layout = Layout::from_size_align(0, 16).unwrap();
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(shrunk_allocation.len(), 0);
+    assert_eq!(allocator.live_allocations(), 0);
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+}
+
+#[test]
+fn allocate_with_page_offset() {
+    let allocator = MMapAllocator::with_stats();
+
+    for (page_offset, size) in [
+        (64, 10),
+        (256, page_size::get()),
+        (page_size::get() - 16, 16),
+    ] {
+        let layout = Layout::from_size_align(size, 16).unwrap();
+        let mut allocation = unsafe { allocator.allocate_with_offset(layout, page_offset) }
+            .expect("allocate_with_offset failed");
+        assert_eq!(
+            allocation.as_mut_ptr() as usize % page_size::get(),
+            page_offset
+        );
+
+        // The memory block should extend to the end of the last page.
+        let allocation_slice = unsafe { allocation.as_mut() };
+        assert!(allocation_slice.len() >= size);
+        assert_eq!((allocation_slice.len() + page_offset) % page_size::get(), 0);
+
+        *allocation_slice.first_mut().unwrap() = 42;
+        *allocation_slice.last_mut().unwrap() = 42;
+
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+fn allocate_with_invalid_page_offset() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    assert!(unsafe { allocator.allocate_with_offset(layout, page_size::get()) }.is_err());
+    assert!(unsafe { allocator.allocate_with_offset(layout, 8) }.is_err());
+}
+
+#[test]
+fn page_size_matches_system() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(1, 1).unwrap();
+
+    // The page size is cached after the first query, and should stay the
+    // same afterwards.
+    for _ in 0..2 {
+        assert_eq!(allocator.mapping_size(layout).unwrap(), page_size::get());
+    }
+}
+
+#[test]
+fn allocate_with_min_align() {
+    let allocator = MMapAllocator::with_min_align(256);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % 256, 0);
+
+    let zero_sized_layout = Layout::from_size_align(0, 16).unwrap();
+    let zero_sized_allocation = allocator
+        .allocate(zero_sized_layout)
+        .expect("allocate failed");
+    assert_eq!(zero_sized_allocation.as_mut_ptr() as usize % 256, 0);
+
+    // Page offsets have to respect the minimum alignment as well.
+    assert!(unsafe { allocator.allocate_with_offset(layout, 16) }.is_err());
+    let offset_allocation = unsafe { allocator.allocate_with_offset(layout, 512) }
+        .expect("allocate_with_offset failed");
+    assert_eq!(offset_allocation.as_mut_ptr() as usize % 256, 0);
+
+    unsafe {
+        allocator.deallocate(allocation.as_non_null_ptr(), layout);
+        allocator.deallocate(zero_sized_allocation.as_non_null_ptr(), zero_sized_layout);
+        allocator.deallocate(offset_allocation.as_non_null_ptr(), layout);
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn grow_with_min_align_above_page_size() {
+    let min_align = 4 * page_size::get();
+    let allocator = MMapAllocator::with_min_align(min_align);
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    assert_eq!(initial_allocation.as_mut_ptr() as usize % min_align, 0);
+
+    let grown_layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(grown_allocation.as_mut_ptr() as usize % min_align, 0);
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+fn grow_in_place_up_to_last_page() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    let ptr = initial_allocation.as_non_null_ptr();
+
+    // The whole first page is already mapped.
+    let grown_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut grown_allocation =
+        unsafe { allocator.grow_in_place(ptr, initial_layout, grown_layout) }
+            .expect("grow_in_place failed");
+    assert_eq!(grown_allocation.as_non_null_ptr(), ptr);
+    unsafe { *grown_allocation.as_mut().last_mut().unwrap() = 42 };
+
+    // One more byte needs another page, which cannot be mapped in place.
+    let too_large_layout = Layout::from_size_align(page_size::get() + 1, 16).unwrap();
+    assert!(unsafe { allocator.grow_in_place(ptr, grown_layout, too_large_layout) }.is_err());
+    assert_eq!(unsafe { *ptr.as_ptr().add(page_size::get() - 1) }, 42);
+
+    unsafe { allocator.deallocate(ptr, grown_layout) };
+}
+
+#[test]
+fn shrink_in_place_to_last_page() {
+    let allocator = MMapAllocator::with_stats();
+
+    let initial_layout = Layout::from_size_align(3 * page_size::get(), 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    let ptr = initial_allocation.as_non_null_ptr();
+
+    // Ending exactly on the last byte of the second page keeps two pages.
+    let shrunk_layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let mut shrunk_allocation =
+        unsafe { allocator.shrink_in_place(ptr, initial_layout, shrunk_layout) }
+            .expect("shrink_in_place failed");
+    assert_eq!(shrunk_allocation.as_non_null_ptr(), ptr);
+    assert_eq!(shrunk_allocation.len(), 2 * page_size::get());
+    unsafe { *shrunk_allocation.as_mut().last_mut().unwrap() = 42 };
+
+    // Zero-sized allocations are never mapped, so `ptr` cannot be kept.
+    let zero_sized_layout = Layout::from_size_align(0, 16).unwrap();
+    assert!(unsafe { allocator.shrink_in_place(ptr, shrunk_layout, zero_sized_layout) }.is_err());
+
+    unsafe { allocator.deallocate(ptr, shrunk_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn shrink_to_larger_alignment() {
+    let allocator = MMapAllocator::with_stats();
+
+    let initial_layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut().fill(42) };
+
+    // The new alignment exceeds both the old one and the page size, so the
+    // retained pages must be computed from the new layout alone.
+    let align = 8 * page_size::get();
+    let shrunk_layout = Layout::from_size_align(page_size::get() + 1, align).unwrap();
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(shrunk_allocation.as_mut_ptr() as usize % align, 0);
+    assert_eq!(shrunk_allocation.len(), 2 * page_size::get());
+    assert_eq!(allocator.live_allocations(), 1);
+
+    let allocation_slice = unsafe { &shrunk_allocation.as_ref()[..shrunk_layout.size()] };
+    assert!(allocation_slice.iter().all(|&byte| byte == 42));
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+// Debug builds reject larger layouts with an assertion instead.
+#[test]
+#[cfg(not(debug_assertions))]
+fn shrink_in_place_to_larger_layout() {
+    let allocator = MMapAllocator::with_stats();
+
+    let initial_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut().fill(42) };
+    let ptr = initial_allocation.as_non_null_ptr();
+
+    // The retained pages would outnumber the mapped ones, which must fail
+    // rather than releasing a wrapped-around tail.
+    let larger_layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    assert!(unsafe { allocator.shrink_in_place(ptr, initial_layout, larger_layout) }.is_err());
+    assert_eq!(allocator.bytes_mapped(), page_size::get());
+
+    let allocation_slice = unsafe { initial_allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 42));
+
+    unsafe { allocator.deallocate(ptr, initial_layout) };
+}
+
+#[test]
+fn decommit_and_reuse() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    unsafe {
+        allocator
+            .decommit(allocation.as_non_null_ptr(), layout)
+            .expect("decommit failed");
+        allocator
+            .recommit(allocation.as_non_null_ptr(), layout)
+            .expect("recommit failed");
+    }
+
+    // The address range stays mapped, but the contents are discarded.
+    assert_eq!(allocator.bytes_mapped(), 4 * page_size::get());
+    let allocation_slice = unsafe { allocation.as_mut() };
+    #[cfg(any(target_os = "linux", windows))]
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+
+    allocation_slice.fill(43);
+    assert!(allocation_slice.iter().all(|&byte| byte == 43));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn decommit_lazy_and_reuse() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    unsafe {
+        allocator
+            .decommit_lazy(allocation.as_non_null_ptr(), layout)
+            .expect("decommit_lazy failed");
+    }
+
+    // The contents are undefined until written, but the memory block stays
+    // usable without being recommitted.
+    assert_eq!(allocator.bytes_mapped(), 4 * page_size::get());
+    let allocation_slice = unsafe { allocation.as_mut() };
+    allocation_slice.fill(43);
+    assert!(allocation_slice.iter().all(|&byte| byte == 43));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn allocate_array_of_u64() {
+    let allocator = MMapAllocator::with_stats();
+
+    for len in [1, 10, page_size::get()] {
+        let array = allocator
+            .allocate_array::<u64>(len)
+            .expect("allocate_array failed");
+        assert_eq!(array.len(), len);
+        assert_eq!(array.as_mut_ptr() as usize % std::mem::align_of::<u64>(), 0);
+
+        let array_slice = unsafe {
+            let array_ptr = array.as_mut_ptr();
+            for i in 0..len {
+                array_ptr.add(i).write(i as u64);
+            }
+            &*array.as_ptr()
+        };
+        assert!(array_slice
+            .iter()
+            .enumerate()
+            .all(|(i, &elem)| elem == i as u64));
+
+        unsafe { allocator.deallocate_array(array) };
+    }
+
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn allocate_whole_pages() {
+    let allocator = MMapAllocator::with_stats();
+    let page_size = page_size::get();
+
+    for count in [1, 2, 5] {
+        let mut pages = allocator.allocate_pages(count).expect("allocate failed");
+        assert_eq!(pages.len(), count * page_size);
+        assert_eq!(pages.as_mut_ptr() as usize % page_size, 0);
+        assert_eq!(allocator.bytes_mapped(), count * page_size);
+        unsafe { pages.as_mut().fill(42) };
+
+        unsafe { allocator.deallocate_pages(pages.as_non_null_ptr(), count) };
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+
+    // The pages are a regular memory block of the same layout.
+    let pages = allocator.allocate_pages(2).expect("allocate failed");
+    let layout = Layout::from_size_align(2 * page_size, page_size).unwrap();
+    unsafe { allocator.deallocate(pages.as_non_null_ptr(), layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+
+    assert!(allocator.allocate_pages(usize::MAX).is_err());
+}
+
+#[test]
+fn allocate_array_too_large() {
+    let allocator = MMapAllocator::new();
+
+    assert!(allocator.allocate_array::<u64>(usize::MAX / 4).is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn allocate_chunks_in_one_mapping() {
+    const COUNT: usize = 8;
+
+    let allocator = MMapAllocator::with_stats();
+
+    // The size is not a multiple of the alignment, so every chunk is padded.
+    let chunk = Layout::from_size_align(100, 64).unwrap();
+    let (base, chunks) = allocator
+        .allocate_chunks(chunk, COUNT)
+        .expect("allocate_chunks failed");
+    assert_eq!(chunks.len(), COUNT);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    for (i, chunk_ptr) in chunks.iter().enumerate() {
+        assert_eq!(chunk_ptr.len(), chunk.size());
+        assert_eq!(
+            chunk_ptr.as_mut_ptr() as usize,
+            base.as_ptr() as usize + i * 128
+        );
+        unsafe { (*chunk_ptr.as_ptr()).fill(i as u8) };
+    }
+
+    for (i, chunk_ptr) in chunks.iter().enumerate() {
+        assert!(unsafe { chunk_ptr.as_ref() }
+            .iter()
+            .all(|&byte| byte == i as u8));
+    }
+
+    unsafe { allocator.deallocate_chunks(base, chunk, COUNT) };
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn allocate_chunks_too_large() {
+    let allocator = MMapAllocator::new();
+
+    let chunk = Layout::from_size_align(16, 16).unwrap();
+    assert!(allocator.allocate_chunks(chunk, usize::MAX / 8).is_err());
+}
+
+#[test]
+fn iterate_over_pages() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(2 * page_size::get() + 10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_non_null_ptr();
+
+    let pages: Vec<_> = allocator.pages(ptr, layout).unwrap().collect();
+    assert_eq!(pages.len(), 3);
+    for (i, page) in pages.iter().enumerate() {
+        assert_eq!(
+            page.as_ptr() as usize,
+            ptr.as_ptr() as usize + i * page_size::get()
+        );
+        unsafe { page.as_ptr().write(42) };
+    }
+
+    let zero_sized_layout = Layout::from_size_align(0, 16).unwrap();
+    assert_eq!(allocator.pages(ptr, zero_sized_layout).unwrap().count(), 0);
+
+    unsafe { allocator.deallocate(ptr, layout) };
+}
+
+#[test]
+#[cfg(unix)]
+fn split_and_free_halves() {
+    let allocator = MMapAllocator::with_stats();
+    let page_size = page_size::get();
+
+    let layout = Layout::from_size_align(4 * page_size, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    let (mut first, mut second) =
+        unsafe { allocator.split(allocation.as_non_null_ptr(), layout, 2 * page_size) }
+            .expect("split failed");
+    assert_eq!(first.as_mut_ptr(), allocation.as_mut_ptr());
+    assert_eq!(first.len(), 2 * page_size);
+    assert_eq!(second.as_mut_ptr(), unsafe {
+        allocation.as_mut_ptr().add(2 * page_size)
+    });
+    assert_eq!(second.len(), 2 * page_size);
+    assert_eq!(allocator.live_allocations(), 2);
+    assert_eq!(allocator.bytes_mapped(), 4 * page_size);
+
+    unsafe {
+        first.as_mut().fill(42);
+        second.as_mut().fill(43);
+    }
+
+    let half_layout = Layout::from_size_align(2 * page_size, 16).unwrap();
+    unsafe { allocator.deallocate(first.as_non_null_ptr(), half_layout) };
+    assert!(unsafe { second.as_ref() }.iter().all(|&byte| byte == 43));
+    unsafe { allocator.deallocate(second.as_non_null_ptr(), half_layout) };
+
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn split_rejects_invalid_offsets() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let layout = Layout::from_size_align(2 * page_size, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_non_null_ptr();
+
+    for at in [0, 1, page_size + 1, 2 * page_size, 3 * page_size] {
+        assert!(unsafe { allocator.split(ptr, layout, at) }.is_err());
+    }
+
+    unsafe { allocator.deallocate(ptr, layout) };
+
+    // Each half would need guard pages of its own.
+    let allocator = MMapAllocator::with_guard_pages(1);
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_non_null_ptr();
+
+    assert!(unsafe { allocator.split(ptr, layout, page_size) }.is_err());
+
+    unsafe { allocator.deallocate(ptr, layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn merge_adjacent_fixed_allocations() {
+    let allocator = MMapAllocator::with_stats();
+    let page_size = page_size::get();
+
+    // Find two free adjacent pages.
+    let region_layout = Layout::from_size_align(2 * page_size, 16).unwrap();
+    let region = allocator.allocate(region_layout).expect("allocate failed");
+    let addr = region.as_non_null_ptr();
+    unsafe { allocator.deallocate(addr, region_layout) };
+
+    let layout = Layout::from_size_align(page_size, 16).unwrap();
+    let a = unsafe { allocator.allocate_fixed(addr, layout) }.expect("allocate_fixed failed");
+    let b = unsafe { allocator.allocate_fixed(addr.add(page_size), layout) }
+        .expect("allocate_fixed failed");
+    assert_eq!(allocator.live_allocations(), 2);
+
+    // The order of the memory blocks matters.
+    assert!(
+        unsafe { allocator.merge(b.as_non_null_ptr(), layout, a.as_non_null_ptr(), layout) }
+            .is_err()
+    );
+
+    let (mut merged, merged_layout) =
+        unsafe { allocator.merge(a.as_non_null_ptr(), layout, b.as_non_null_ptr(), layout) }
+            .expect("merge failed");
+    assert_eq!(merged.as_non_null_ptr(), addr);
+    assert_eq!(merged.len(), 2 * page_size);
+    assert_eq!(merged_layout.size(), 2 * page_size);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    // Write across the boundary between the two mappings.
+    let merged_slice = unsafe { merged.as_mut() };
+    merged_slice[page_size - 8..page_size + 8].fill(42);
+    assert!(merged_slice[page_size - 8..page_size + 8]
+        .iter()
+        .all(|&byte| byte == 42));
+
+    unsafe { allocator.deallocate(merged.as_non_null_ptr(), merged_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn merge_after_split() {
+    let allocator = MMapAllocator::with_stats();
+    let page_size = page_size::get();
+
+    let layout = Layout::from_size_align(3 * page_size, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    let (first, second) =
+        unsafe { allocator.split(allocation.as_non_null_ptr(), layout, page_size) }
+            .expect("split failed");
+
+    let first_layout = Layout::from_size_align(page_size, 16).unwrap();
+    let second_layout = Layout::from_size_align(2 * page_size - 10, 1).unwrap();
+    let (merged, merged_layout) = unsafe {
+        allocator.merge(
+            first.as_non_null_ptr(),
+            first_layout,
+            second.as_non_null_ptr(),
+            second_layout,
+        )
+    }
+    .expect("merge failed");
+    assert_eq!(merged.len(), 3 * page_size);
+    assert_eq!(merged_layout.size(), 3 * page_size - 10);
+    assert_eq!(merged_layout.align(), 16);
+
+    unsafe { allocator.deallocate(merged.as_non_null_ptr(), merged_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+fn grow_report_in_place() {
+    let allocator = MMapAllocator::new();
+
+    let old_layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(old_layout).expect("allocate failed");
+
+    // The new size still fits in the first page.
+    let new_layout = Layout::from_size_align(20, 16).unwrap();
+    let (new_allocation, relocated) =
+        unsafe { allocator.grow_report(allocation.as_non_null_ptr(), old_layout, new_layout) }
+            .expect("grow_report failed");
+    assert!(!relocated);
+    assert_eq!(new_allocation.as_mut_ptr(), allocation.as_mut_ptr());
+
+    unsafe { allocator.deallocate(new_allocation.as_non_null_ptr(), new_layout) };
+}
+
+#[test]
+#[cfg(unix)]
+fn grow_report_relocating() {
+    // Guard pages prevent the mapping from being extended, so growing always
+    // copies the contents to a new mapping.
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let old_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(old_layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    let new_layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let (new_allocation, relocated) =
+        unsafe { allocator.grow_report(allocation.as_non_null_ptr(), old_layout, new_layout) }
+            .expect("grow_report failed");
+    assert!(relocated);
+    assert_ne!(new_allocation.as_mut_ptr(), allocation.as_mut_ptr());
+    assert!(unsafe { new_allocation.as_ref() }[..page_size::get()]
+        .iter()
+        .all(|&byte| byte == 42));
+
+    unsafe { allocator.deallocate(new_allocation.as_non_null_ptr(), new_layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn grow_report_with_mremap() {
+    let allocator = MMapAllocator::new();
+
+    let old_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(old_layout).expect("allocate failed");
+
+    // `mremap` may or may not move the mapping, which must be reported.
+    let new_layout = Layout::from_size_align(64 * page_size::get(), 16).unwrap();
+    let (new_allocation, relocated) =
+        unsafe { allocator.grow_report(allocation.as_non_null_ptr(), old_layout, new_layout) }
+            .expect("grow_report failed");
+    assert_eq!(
+        relocated,
+        new_allocation.as_mut_ptr() != allocation.as_mut_ptr()
+    );
+
+    unsafe { allocator.deallocate(new_allocation.as_non_null_ptr(), new_layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn grow_dontunmap_keeps_old_range() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let old_layout = Layout::from_size_align(2 * page_size, 16).unwrap();
+    let mut allocation = allocator.allocate(old_layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(0xaa) };
+    let old_ptr = allocation.as_mut_ptr();
+
+    let new_layout = Layout::from_size_align(8 * page_size, 16).unwrap();
+    let new_allocation =
+        unsafe { allocator.grow_dontunmap(allocation.as_non_null_ptr(), old_layout, new_layout) }
+            .expect("grow_dontunmap failed");
+    assert_eq!(new_allocation.len(), 8 * page_size);
+    assert!(unsafe { new_allocation.as_ref() }[..2 * page_size]
+        .iter()
+        .all(|&byte| byte == 0xaa));
+
+    // `mincore` fails with `ENOMEM` if the range is not mapped.
+    let mut residency = [0u8; 2];
+    let res = unsafe { libc::mincore(old_ptr.cast(), 2 * page_size, residency.as_mut_ptr()) };
+    if res == 0 {
+        assert_ne!(new_allocation.as_mut_ptr(), old_ptr);
+
+        // The old range now holds fresh zero-filled pages.
+        let old_range = unsafe { std::slice::from_raw_parts(old_ptr, 2 * page_size) };
+        assert!(old_range.iter().all(|&byte| byte == 0));
+
+        assert_eq!(unsafe { libc::munmap(old_ptr.cast(), 2 * page_size) }, 0);
+    } else {
+        eprintln!("MREMAP_DONTUNMAP is not supported, skipping");
+    }
+
+    unsafe { allocator.deallocate(new_allocation.as_non_null_ptr(), new_layout) };
+}
+
+#[test]
+fn try_allocate_opt_normal_layout() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.try_allocate_opt(layout).expect("allocate failed");
+    assert_eq!(allocation.len(), page_size::get());
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn try_allocate_opt_over_aligned_layout() {
+    let allocator = MMapAllocator::with_stats();
+
+    // The padding needed to align the block cannot fit in the address space.
+    let layout = Layout::from_size_align(10, 1 << (usize::BITS - 2)).unwrap();
+    assert!(allocator.try_allocate_opt(layout).is_none());
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn max_supported_align_is_unbounded() {
+    let allocator = MMapAllocator::new();
+    assert_eq!(allocator.max_supported_align(), usize::MAX);
+
+    // Any alignment is accepted as long as the padding fits in memory.
+    let align = 1 << 30;
+    let layout = Layout::from_size_align(10, align).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % align, 0);
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    // Zero-sized allocations are never mapped, even at the largest alignment
+    // that a layout can have.
+    let layout = Layout::from_size_align(0, 1 << (usize::BITS - 1)).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.len(), 0);
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn clone_mapping_is_independent() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(2 * page_size::get() + 10, 16).unwrap();
+    let mut original = allocator.allocate(layout).expect("allocate failed");
+    for (i, byte) in unsafe { original.as_mut() }.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    let mut clone = unsafe { allocator.clone_mapping(original.as_non_null_ptr(), layout) }
+        .expect("clone_mapping failed");
+    assert_ne!(clone.as_mut_ptr(), original.as_mut_ptr());
+    assert_eq!(clone.len(), original.len());
+    assert_eq!(unsafe { clone.as_ref() }, unsafe { original.as_ref() });
+    assert_eq!(allocator.live_allocations(), 2);
+
+    unsafe { clone.as_mut().fill(0xff) };
+    assert!(unsafe { original.as_ref() }
+        .iter()
+        .enumerate()
+        .all(|(i, &byte)| byte == i as u8));
+
+    unsafe {
+        allocator.deallocate(clone.as_non_null_ptr(), layout);
+        allocator.deallocate(original.as_non_null_ptr(), layout);
+    }
+}
+
+#[test]
+fn realign_already_aligned() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(10, 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    // Mappings are always page-aligned, so nothing is copied.
+    let realigned =
+        unsafe { allocator.realign(allocation.as_non_null_ptr(), layout, page_size::get()) }
+            .expect("realign failed");
+    assert_eq!(realigned, allocation);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    assert!(unsafe { allocator.realign(realigned.as_non_null_ptr(), layout, 3) }.is_err());
+
+    let realigned_layout = Layout::from_size_align(10, page_size::get()).unwrap();
+    unsafe { allocator.deallocate(realigned.as_non_null_ptr(), realigned_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn realign_moves_misaligned() {
+    let allocator = MMapAllocator::with_stats();
+    let align = 64 * page_size::get();
+
+    // Keep allocating until a mapping is not aligned by chance.
+    let layout = Layout::from_size_align(2 * page_size::get(), 1).unwrap();
+    let mut allocations = Vec::new();
+    let mut allocation = loop {
+        let allocation = allocator.allocate(layout).expect("allocate failed");
+        if allocation.as_mut_ptr().align_offset(align) != 0 {
+            break allocation;
+        }
+        allocations.push(allocation);
+    };
+    unsafe { allocation.as_mut().fill(42) };
+
+    let realigned = unsafe { allocator.realign(allocation.as_non_null_ptr(), layout, align) }
+        .expect("realign failed");
+    assert_ne!(realigned.as_mut_ptr(), allocation.as_mut_ptr());
+    assert_eq!(realigned.as_mut_ptr() as usize % align, 0);
+    assert_eq!(realigned.len(), allocation.len());
+    assert!(unsafe { realigned.as_ref() }.iter().all(|&byte| byte == 42));
+    assert_eq!(allocator.live_allocations(), allocations.len() + 1);
+
+    let realigned_layout = Layout::from_size_align(layout.size(), align).unwrap();
+    unsafe { allocator.deallocate(realigned.as_non_null_ptr(), realigned_layout) };
+    for allocation in allocations {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn resize_reports_moves() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+    let page_size = page_size::get();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_non_null_ptr();
+
+    // Growing within the first page.
+    let in_page_layout = Layout::from_size_align(20, 16).unwrap();
+    let resize = unsafe { allocator.resize(ptr, layout, in_page_layout) }.expect("resize failed");
+    assert!(!resize.moved);
+    assert_eq!(resize.ptr.as_non_null_ptr(), ptr);
+
+    // Growing past the first page, which the guard pages prevent in place.
+    let large_layout = Layout::from_size_align(4 * page_size, 16).unwrap();
+    let resize =
+        unsafe { allocator.resize(ptr, in_page_layout, large_layout) }.expect("resize failed");
+    assert!(resize.moved);
+    assert_ne!(resize.ptr.as_non_null_ptr(), ptr);
+    let ptr = resize.ptr.as_non_null_ptr();
+
+    // Shrinking within the last page.
+    let shrunk_layout = Layout::from_size_align(4 * page_size - 10, 16).unwrap();
+    let resize =
+        unsafe { allocator.resize(ptr, large_layout, shrunk_layout) }.expect("resize failed");
+    assert!(!resize.moved);
+    assert_eq!(resize.ptr.len(), 4 * page_size);
+
+    // Shrinking to fewer pages truncates the mapping.
+    let resize = unsafe { allocator.resize(ptr, shrunk_layout, layout) }.expect("resize failed");
+    assert!(!resize.moved);
+    assert_eq!(resize.ptr.as_non_null_ptr(), ptr);
+    assert_eq!(resize.ptr.len(), page_size);
+
+    unsafe { allocator.deallocate(ptr, layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn swap_exchanges_contents() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(3 * page_size::get(), 16).unwrap();
+    let mut a = allocator.allocate(layout).expect("allocate failed");
+    let mut b = allocator.allocate(layout).expect("allocate failed");
+    unsafe {
+        a.as_mut().fill(0xaa);
+        b.as_mut().fill(0xbb);
+    }
+
+    unsafe { allocator.swap(a.as_non_null_ptr(), b.as_non_null_ptr(), layout) }
+        .expect("swap failed");
+    assert!(unsafe { a.as_ref() }.iter().all(|&byte| byte == 0xbb));
+    assert!(unsafe { b.as_ref() }.iter().all(|&byte| byte == 0xaa));
+    assert_eq!(allocator.live_allocations(), 2);
+
+    // Both memory blocks are still writable, and can be swapped back.
+    unsafe {
+        a.as_mut()[0] = 1;
+        b.as_mut()[0] = 2;
+        allocator
+            .swap(a.as_non_null_ptr(), b.as_non_null_ptr(), layout)
+            .expect("swap failed");
+    }
+    assert_eq!(unsafe { a.as_ref() }[..2], [2, 0xaa]);
+    assert_eq!(unsafe { b.as_ref() }[..2], [1, 0xbb]);
+
+    unsafe {
+        allocator.deallocate(a.as_non_null_ptr(), layout);
+        allocator.deallocate(b.as_non_null_ptr(), layout);
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn swap_with_guard_pages() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut a = allocator.allocate(layout).expect("allocate failed");
+    let mut b = allocator.allocate(layout).expect("allocate failed");
+    unsafe {
+        a.as_mut().fill(1);
+        b.as_mut().fill(2);
+        allocator
+            .swap(a.as_non_null_ptr(), b.as_non_null_ptr(), layout)
+            .expect("swap failed");
+    }
+    assert!(unsafe { a.as_ref() }.iter().all(|&byte| byte == 2));
+    assert!(unsafe { b.as_ref() }.iter().all(|&byte| byte == 1));
+
+    // The guard pages stay in place.
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(a.as_mut_ptr().add(page_size::get()), 42);
+    });
+
+    unsafe {
+        allocator.deallocate(a.as_non_null_ptr(), layout);
+        allocator.deallocate(b.as_non_null_ptr(), layout);
+    }
+}
+
+/// Asserts that every byte of `allocation` is zero, including the padding
+/// past `layout.size()`.
+fn assert_zeroed(allocation: &[u8], layout: Layout) {
+    assert!(allocation.len() >= layout.size());
+
+    // Comparing whole words is much faster than comparing bytes for large
+    // allocations, the bytes are only looked at to report the failure.
+    let (prefix, words, suffix) = unsafe { allocation.align_to::<u64>() };
+    let zeroed =
+        prefix.iter().chain(suffix).all(|&byte| byte == 0) && words.iter().all(|&word| word == 0);
+    let offset = (!zeroed).then(|| allocation.iter().position(|&byte| byte != 0).unwrap());
+
+    if let Some(offset) = offset {
+        panic!(
+            "byte {} of {} is {:#04x} instead of zero ({} bytes requested)",
+            offset,
+            allocation.len(),
+            allocation[offset],
+            layout.size()
+        );
+    }
+}
+
+#[test]
+fn allocate_zeroed_whole_length() {
+    let page_size = page_size::get();
+
+    for allocator in [
+        MMapAllocator::new(),
+        #[cfg(unix)]
+        MMapAllocator::with_guard_pages(1),
+        #[cfg(unix)]
+        MMapAllocator::shared(),
+    ] {
+        for size in [
+            1,
+            10,
+            page_size - 1,
+            page_size,
+            page_size + 1,
+            3 * page_size + 7,
+            1 << 20,
+        ] {
+            let layout = Layout::from_size_align(size, 16).unwrap();
+
+            // Dirty the pages of a previous allocation of the same size, in
+            // case the new one ends up at the same address.
+            let mut allocation = allocator.allocate(layout).expect("allocate failed");
+            unsafe { allocation.as_mut().fill(0xaa) };
+            unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+            let allocation = allocator.allocate_zeroed(layout).expect("allocate failed");
+            assert_eq!(allocation.len(), allocator.mapping_size(layout).unwrap());
+            assert_zeroed(unsafe { allocation.as_ref() }, layout);
+
+            unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+        }
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn allocate_zeroed_over_aligned() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(page_size::get() + 1, 1 << 21).unwrap();
+    let allocation = allocator.allocate_zeroed(layout).expect("allocate failed");
+    assert_zeroed(unsafe { allocation.as_ref() }, layout);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(unix)]
+fn returned_length_excludes_padding() {
+    let page_size = page_size::get();
+    let layout = Layout::from_size_align(page_size + 1, 16).unwrap();
+    let over_aligned_layout = Layout::from_size_align(page_size + 1, 16 * page_size).unwrap();
+
+    // Neither the alignment padding nor the guard pages are part of the
+    // memory block, which only spans the pages holding the layout.
+    let allocators = [
+        (MMapAllocator::new(), over_aligned_layout),
+        (MMapAllocator::with_guard_pages(2), layout),
+        (MMapAllocator::with_guard_pages(1), over_aligned_layout),
+        (MMapAllocator::with_min_align(8 * page_size), layout),
+    ];
+    for (allocator, layout) in &allocators {
+        let mut allocation = allocator.allocate(*layout).expect("allocate failed");
+        assert_eq!(allocation.len(), 2 * page_size);
+        assert_eq!(allocation.len(), allocator.mapping_size(*layout).unwrap());
+        assert_eq!(allocation.as_mut_ptr() as usize % layout.align(), 0);
+        unsafe { allocation.as_mut().fill(42) };
+
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), *layout) };
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn returned_length_excludes_thp_padding() {
+    let allocator = MMapAllocator::with_thp_alignment(true);
+
+    let layout = Layout::from_size_align((4 << 20) + 1, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.len(), (4 << 20) + page_size::get());
+    assert_eq!(allocation.as_mut_ptr() as usize % (2 << 20), 0);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn vec_with_page_capacity_fills_one_page() {
+    let allocator = MMapAllocator::with_stats();
+
+    let mut vec = allocator.vec_with_page_capacity::<u32>();
+    let capacity = page_size::get() / std::mem::size_of::<u32>();
+    assert_eq!(vec.capacity(), capacity);
+    assert_eq!(allocator.bytes_mapped(), page_size::get());
+
+    // Filling the page does not reallocate.
+    let ptr = vec.as_ptr();
+    for i in 0..capacity {
+        vec.push(i as u32);
+    }
+    assert_eq!(vec.as_ptr(), ptr);
+    assert_eq!(vec.capacity(), capacity);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    // Types larger than a page still get room for one element.
+    let large_vec = allocator.vec_with_page_capacity::<[u8; 3 * 4096]>();
+    assert_eq!(large_vec.capacity(), (page_size::get() / (3 * 4096)).max(1));
+
+    drop(vec);
+    drop(large_vec);
+    assert_eq!(allocator.live_allocations(), 0);
+
+    let zst_vec = allocator.vec_with_page_capacity::<()>();
+    assert_eq!(zst_vec.capacity(), usize::MAX);
+}
+
+#[test]
+#[cfg(unix)]
+fn largest_available_mapping() {
+    let allocator = MMapAllocator::new();
+
+    let largest = allocator.largest_available();
+    assert!(largest > 0);
+    assert_eq!(largest % page_size::get(), 0);
+
+    // Other tests map memory concurrently, so only half of the estimate is
+    // expected to still be available.
+    let layout = Layout::from_size_align(largest / 2, page_size::get()).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
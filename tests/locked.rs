@@ -0,0 +1,37 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+
+/// Returns the amount of locked memory of the process in KiB.
+fn locked_kibibytes() -> usize {
+    let status = std::fs::read_to_string("/proc/self/status").expect("cannot read status");
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmLck:"))
+        .and_then(|locked| locked.trim().trim_end_matches("kB").trim().parse().ok())
+        .expect("cannot parse VmLck")
+}
+
+#[test]
+fn allocate_locked() {
+    let allocator = MMapAllocator::with_locked(true);
+
+    let locked_before = locked_kibibytes();
+
+    let layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let allocation = match allocator.allocate(layout) {
+        Ok(allocation) => allocation,
+        Err(_) => {
+            eprintln!("cannot lock memory, skipping");
+            return;
+        }
+    };
+
+    let locked_after = locked_kibibytes();
+    assert!(locked_after >= locked_before + 4 * page_size::get() / 1024);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
@@ -0,0 +1,177 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::ptr;
+use std::time::Instant;
+
+/// Allocates `size` bytes, touches every page and deallocates them, returning
+/// the time it took.
+fn touch_all_pages(allocator: &MMapAllocator, size: usize) -> std::time::Duration {
+    let start = Instant::now();
+
+    let layout = Layout::from_size_align(size, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    for offset in (0..allocation.len()).step_by(page_size::get()) {
+        unsafe { ptr::write_volatile(allocation.as_mut_ptr().add(offset), 42) };
+    }
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    start.elapsed()
+}
+
+#[test]
+fn allocate_with_and_without_populate() {
+    const SIZE: usize = 16 << 20;
+
+    let lazy = touch_all_pages(&MMapAllocator::with_populate(false), SIZE);
+    let populated = touch_all_pages(&MMapAllocator::with_populate(true), SIZE);
+
+    eprintln!(
+        "without populate: {:?}, with populate: {:?}",
+        lazy, populated
+    );
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+fn allocate_larger_than_memory_without_reserve() {
+    // Strict overcommit accounting ignores `MAP_NORESERVE`.
+    let overcommit = std::fs::read_to_string("/proc/sys/vm/overcommit_memory").unwrap();
+    if overcommit.trim() == "2" {
+        eprintln!("strict overcommit is enabled, skipping");
+        return;
+    }
+
+    let physical_pages = unsafe { libc::sysconf(libc::_SC_PHYS_PAGES) } as usize;
+    let size = 4 * physical_pages * page_size::get();
+
+    let allocator = MMapAllocator::with_no_reserve(true);
+    let layout = Layout::from_size_align(size, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    unsafe {
+        ptr::write_volatile(allocation.as_mut_ptr(), 42);
+        ptr::write_volatile(allocation.as_mut_ptr().add(allocation.len() - 1), 42);
+    }
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn allocate_with_thp_hint() {
+    let allocator = MMapAllocator::with_thp(true);
+
+    // Whether the pages are actually promoted depends on the system, so only
+    // the allocation itself can be checked.
+    for size in [10, 4 << 20] {
+        let layout = Layout::from_size_align(size, 16).unwrap();
+        let mut allocation = allocator.allocate(layout).expect("allocate failed");
+        unsafe { allocation.as_mut().fill(42) };
+
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn align_large_allocations_for_thp() {
+    let allocator = MMapAllocator::with_thp_alignment(true);
+
+    let layout = Layout::from_size_align(4 << 20, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % (2 << 20), 0);
+    assert_eq!(allocation.len(), 4 << 20);
+    unsafe { allocation.as_mut().fill(42) };
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn collapse_into_huge_pages() {
+    let allocator = MMapAllocator::with_thp_alignment(true);
+
+    let layout = Layout::from_size_align(4 << 20, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    if unsafe { allocator.collapse(allocation.as_non_null_ptr(), layout) }.is_err() {
+        // Older kernels and kernels without transparent huge pages reject
+        // the advice, while `EAGAIN` and `ENOMEM` are transient, so the
+        // error is checked by issuing it again.
+        let res = unsafe { libc::madvise(allocation.as_mut_ptr().cast(), 4 << 20, 25) };
+        if res == -1 {
+            let errno = std::io::Error::last_os_error().raw_os_error();
+            assert!(
+                matches!(errno, Some(libc::EINVAL | libc::EAGAIN | libc::ENOMEM)),
+                "unexpected error: {:?}",
+                errno
+            );
+            eprintln!("MADV_COLLAPSE is not supported, skipping");
+        }
+    }
+
+    // Collapsing keeps the contents of the pages.
+    let allocation_slice = unsafe { allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 42));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn name_anonymous_mappings() {
+    let allocator = MMapAllocator::with_name("mmap-allocator-test");
+
+    let layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    let maps = std::fs::read_to_string("/proc/self/maps").expect("cannot read /proc/self/maps");
+    let start = format!("{:x}-", allocation.as_mut_ptr() as usize);
+    let line = maps
+        .lines()
+        .find(|line| line.starts_with(&start))
+        .expect("mapping not found");
+
+    if !line.ends_with("[anon:mmap-allocator-test]") {
+        // Check whether the kernel supports naming mappings at all.
+        let res = unsafe {
+            libc::prctl(
+                libc::PR_SET_VMA,
+                libc::PR_SET_VMA_ANON_NAME,
+                allocation.as_mut_ptr(),
+                allocation.len(),
+                b"mmap-allocator-test\0".as_ptr(),
+            )
+        };
+        assert_eq!(res, -1, "mapping was not named: {}", line);
+        eprintln!("naming mappings is not supported, skipping");
+    }
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn allocate_in_low_address_space() {
+    let allocator = MMapAllocator::with_32bit(true);
+
+    let layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert!(allocation.as_mut_ptr() as usize + allocation.len() <= 1 << 32);
+
+    // Growing moves the memory block to a new low mapping instead of
+    // letting `mremap` place it anywhere.
+    let new_layout = Layout::from_size_align(256 * page_size::get(), 16).unwrap();
+    let allocation = unsafe { allocator.grow(allocation.as_non_null_ptr(), layout, new_layout) }
+        .expect("grow failed");
+    assert!(allocation.as_mut_ptr() as usize + allocation.len() <= 1 << 32);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), new_layout) };
+}
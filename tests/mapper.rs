@@ -0,0 +1,190 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{GenericMMapAllocator, MMapAllocator, MemoryMapper};
+use std::alloc::{self, Allocator, Layout};
+use std::cell::RefCell;
+use std::ffi::c_void;
+
+/// Number of pages in the simulated address space.
+const PAGES: usize = 16;
+
+/// Mapper handing out the pages of a heap buffer instead of mapping memory.
+struct FakeMapper {
+    base: *mut u8,
+    /// Whether each page of the buffer is currently mapped.
+    mapped: RefCell<[bool; PAGES]>,
+}
+
+impl FakeMapper {
+    fn new() -> Self {
+        let base = unsafe { alloc::alloc(Self::buffer_layout()) };
+        assert!(!base.is_null());
+
+        Self {
+            base,
+            mapped: RefCell::new([false; PAGES]),
+        }
+    }
+
+    fn buffer_layout() -> Layout {
+        Layout::from_size_align(PAGES * page_size::get(), page_size::get()).unwrap()
+    }
+
+    /// Returns the range of pages covered by `len` bytes at `addr`.
+    fn pages(&self, addr: *mut c_void, len: usize) -> std::ops::Range<usize> {
+        let start = (addr as usize - self.base as usize) / page_size::get();
+        start..start + len / page_size::get()
+    }
+
+    fn mapped_pages(&self) -> usize {
+        self.mapped
+            .borrow()
+            .iter()
+            .filter(|&&mapped| mapped)
+            .count()
+    }
+}
+
+impl Drop for FakeMapper {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.base, Self::buffer_layout()) };
+    }
+}
+
+unsafe impl MemoryMapper for FakeMapper {
+    unsafe fn map(
+        &self,
+        _addr: *mut c_void,
+        len: usize,
+        _prot: libc::c_int,
+        _flags: libc::c_int,
+        _fd: libc::c_int,
+        _offset: libc::off_t,
+    ) -> Result<*mut c_void, i32> {
+        let count = len / page_size::get();
+        let mut mapped = self.mapped.borrow_mut();
+
+        // Hints are ignored, the first free range that is large enough is
+        // used.
+        let start = (0..(PAGES + 1).saturating_sub(count))
+            .find(|&start| mapped[start..start + count].iter().all(|&page| !page))
+            .ok_or(libc::ENOMEM)?;
+        mapped[start..start + count].fill(true);
+
+        let addr = self.base.add(start * page_size::get());
+        // Fresh mappings are zeroed.
+        addr.write_bytes(0, len);
+        Ok(addr.cast())
+    }
+
+    unsafe fn unmap(&self, addr: *mut c_void, len: usize) -> Result<(), i32> {
+        let pages = self.pages(addr, len);
+        self.mapped.borrow_mut()[pages].fill(false);
+        Ok(())
+    }
+
+    unsafe fn protect(&self, addr: *mut c_void, len: usize, _prot: libc::c_int) -> Result<(), i32> {
+        let pages = self.pages(addr, len);
+        if !self.mapped.borrow()[pages].iter().all(|&page| page) {
+            return Err(libc::ENOMEM);
+        }
+
+        Ok(())
+    }
+}
+
+fn fake_allocator() -> GenericMMapAllocator<FakeMapper> {
+    MMapAllocator::with_stats().with_mapper(FakeMapper::new())
+}
+
+#[test]
+fn allocate_from_fake_mapper() {
+    let allocator = fake_allocator();
+
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.len(), 2 * page_size::get());
+    assert_eq!(allocator.live_allocations(), 1);
+    assert_eq!(allocator.bytes_mapped(), 2 * page_size::get());
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+fn grow_and_shrink_with_fake_mapper() {
+    let allocator = fake_allocator();
+    let page_size = page_size::get();
+
+    let layout = Layout::from_size_align(page_size, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    // Growing past the last page maps a new range and copies the contents,
+    // as the fake mapper cannot remap.
+    let grown_layout = Layout::from_size_align(4 * page_size, 16).unwrap();
+    let grown = unsafe { allocator.resize(allocation.as_non_null_ptr(), layout, grown_layout) }
+        .expect("resize failed");
+    assert!(grown.moved);
+    assert!(unsafe { grown.ptr.as_ref() }[..page_size]
+        .iter()
+        .all(|&byte| byte == 42));
+    assert_eq!(allocator.mapper().mapped_pages(), 4);
+    assert_eq!(allocator.bytes_mapped(), 4 * page_size);
+
+    // Shrinking unmaps the tail pages in place.
+    let shrunk_layout = Layout::from_size_align(page_size + 10, 16).unwrap();
+    let shrunk =
+        unsafe { allocator.resize(grown.ptr.as_non_null_ptr(), grown_layout, shrunk_layout) }
+            .expect("resize failed");
+    assert!(!shrunk.moved);
+    assert_eq!(shrunk.ptr.len(), 2 * page_size);
+    assert_eq!(allocator.mapper().mapped_pages(), 2);
+    assert_eq!(allocator.bytes_mapped(), 2 * page_size);
+
+    unsafe { allocator.deallocate(shrunk.ptr.as_non_null_ptr(), shrunk_layout) };
+    assert_eq!(allocator.mapper().mapped_pages(), 0);
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn guard_pages_with_fake_mapper() {
+    let allocator = MMapAllocator::with_guard_pages(1).with_mapper(FakeMapper::new());
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocator.mapper().mapped_pages(), 3);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    assert_eq!(allocator.mapper().mapped_pages(), 0);
+}
+
+#[test]
+fn fake_mapper_out_of_memory() {
+    let allocator = fake_allocator();
+
+    let layout = Layout::from_size_align((PAGES + 1) * page_size::get(), 16).unwrap();
+    assert!(allocator.allocate(layout).is_err());
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn largest_available_in_fake_mapper() {
+    let allocator = fake_allocator();
+    assert_eq!(allocator.largest_available(), PAGES * page_size::get());
+
+    let layout = Layout::from_size_align(3 * page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(
+        allocator.largest_available(),
+        (PAGES - 3) * page_size::get()
+    );
+
+    // Every probe was unmapped.
+    assert_eq!(allocator.mapper().mapped_pages(), 3);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
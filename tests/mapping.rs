@@ -0,0 +1,76 @@
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{MMapAllocator, Protection};
+use std::alloc::{Allocator, Layout};
+
+/// Returns whether the page at `addr` is mapped.
+#[cfg(unix)]
+fn is_mapped(addr: *mut u8) -> bool {
+    let mut residency = 0u8;
+    unsafe {
+        libc::mincore(
+            addr.cast(),
+            page_size::get(),
+            (&mut residency as *mut u8).cast(),
+        ) == 0
+    }
+}
+
+#[test]
+fn write_to_mapping() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let mut mapping = allocator.map(layout).expect("map failed");
+    assert_eq!(mapping.len(), 2 * page_size::get());
+    assert_eq!(mapping.layout(), layout);
+    assert_eq!(allocator.live_allocations(), 1);
+
+    mapping.fill(42);
+    assert!(mapping.iter().all(|&byte| byte == 42));
+}
+
+#[test]
+fn drop_unmaps_mapping() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let mapping = allocator.map(layout).expect("map failed");
+    #[cfg(unix)]
+    let addr = mapping.as_ptr() as *mut u8;
+    #[cfg(unix)]
+    assert!(is_mapped(addr));
+
+    drop(mapping);
+    assert_eq!(allocator.live_allocations(), 0);
+    #[cfg(unix)]
+    assert!(!is_mapped(addr));
+}
+
+#[test]
+fn leak_keeps_mapping() {
+    let allocator = MMapAllocator::with_stats();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let mut mapping = allocator.map(layout).expect("map failed");
+    mapping[0] = 42;
+
+    let (ptr, leaked_layout) = mapping.leak();
+    assert_eq!(leaked_layout, layout);
+    assert_eq!(allocator.live_allocations(), 1);
+    #[cfg(unix)]
+    assert!(is_mapped(ptr.as_mut_ptr()));
+    assert_eq!(unsafe { ptr.as_ref() }[0], 42);
+
+    unsafe { allocator.deallocate(ptr.as_non_null_ptr(), leaked_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+}
+
+#[test]
+fn map_requires_writable_memory() {
+    let allocator = MMapAllocator::with_protection(Protection::READ);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    assert!(allocator.map(layout).is_err());
+}
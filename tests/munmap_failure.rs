@@ -0,0 +1,214 @@
+#![cfg(all(unix, feature = "syscall-hooks"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+#![cfg_attr(
+    all(
+        target_os = "linux",
+        debug_assertions,
+        not(feature = "no-panic-dealloc")
+    ),
+    feature(internal_output_capture)
+)]
+
+use mmap_allocator::{hooks, MMapAllocator};
+use std::alloc::{Allocator, Layout};
+use std::ffi::c_void;
+
+/// Fails every call without unmapping anything, with `EINVAL` on Linux.
+unsafe fn failing_munmap(_addr: *mut c_void, _len: usize) -> libc::c_int {
+    #[cfg(target_os = "linux")]
+    {
+        *libc::__errno_location() = libc::EINVAL;
+    }
+    -1
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn try_deallocate_reports_errno() {
+    use mmap_allocator::MMapError;
+
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.try_allocate(layout).expect("allocate failed");
+
+    hooks::set_munmap_hook(Some(failing_munmap));
+    let res = unsafe { allocator.try_deallocate(allocation.as_non_null_ptr(), layout) };
+    hooks::set_munmap_hook(None);
+    assert_eq!(res, Err(MMapError::MunmapFailed(libc::EINVAL)));
+
+    unsafe { allocator.try_deallocate(allocation.as_non_null_ptr(), layout) }
+        .expect("deallocate failed");
+}
+
+#[test]
+fn grow_survives_munmap_failure() {
+    // Guard pages force `grow` to copy the contents to a new mapping.
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let initial_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut().fill(0xaa) };
+
+    let grown_layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    hooks::set_munmap_hook(Some(failing_munmap));
+    let grown_allocation = unsafe {
+        allocator.grow(
+            initial_allocation.as_non_null_ptr(),
+            initial_layout,
+            grown_layout,
+        )
+    };
+    hooks::set_munmap_hook(None);
+
+    let mut grown_allocation = grown_allocation.expect("grow failed");
+    let allocation_slice = unsafe { grown_allocation.as_mut() };
+    assert!(allocation_slice[..page_size::get()]
+        .iter()
+        .all(|&byte| byte == 0xaa));
+    allocation_slice.fill(42);
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+fn shrink_survives_munmap_failure() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    let initial_layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut().fill(0xaa) };
+
+    let shrunk_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    hooks::set_munmap_hook(Some(failing_munmap));
+    let shrunk_allocation = unsafe {
+        allocator.shrink(
+            initial_allocation.as_non_null_ptr(),
+            initial_layout,
+            shrunk_layout,
+        )
+    };
+    hooks::set_munmap_hook(None);
+
+    let shrunk_allocation = shrunk_allocation.expect("shrink failed");
+    let allocation_slice = unsafe { shrunk_allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 0xaa));
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+}
+
+#[test]
+#[cfg(not(feature = "no-panic-dealloc"))]
+fn deallocate_aborts_on_munmap_failure() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1, "fork failed");
+
+    if pid == 0 {
+        hooks::set_munmap_hook(Some(failing_munmap));
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+        unsafe { libc::_exit(0) };
+    }
+
+    let mut status = 0;
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "waitpid failed");
+    assert!(libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGABRT);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(all(
+    target_os = "linux",
+    debug_assertions,
+    not(feature = "no-panic-dealloc")
+))]
+fn deallocate_reports_errno_before_aborting() {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0, "pipe failed");
+    let [read_fd, write_fd] = fds;
+
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1, "fork failed");
+
+    if pid == 0 {
+        // Capture the message printed before aborting, which the test harness
+        // would otherwise keep to itself.
+        std::io::set_output_capture(None);
+        unsafe { libc::dup2(write_fd, libc::STDERR_FILENO) };
+        hooks::set_munmap_hook(Some(failing_munmap));
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+        unsafe { libc::_exit(0) };
+    }
+
+    unsafe { libc::close(write_fd) };
+    let mut message = String::new();
+    unsafe { File::from_raw_fd(read_fd) }
+        .read_to_string(&mut message)
+        .expect("cannot read the message");
+
+    let mut status = 0;
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "waitpid failed");
+    assert!(libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGABRT);
+
+    let expected = format!(
+        "munmap failed (errno={}, ptr={:p}, size=10)",
+        libc::EINVAL,
+        allocation.as_mut_ptr()
+    );
+    assert!(
+        message.contains(&expected),
+        "unexpected message: {}",
+        message
+    );
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(feature = "no-panic-dealloc")]
+fn deallocate_leaks_on_munmap_failure() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+
+    // The child neither aborts nor unwinds, and the allocation is left
+    // mapped.
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1, "fork failed");
+
+    if pid == 0 {
+        hooks::set_munmap_hook(Some(failing_munmap));
+        let res = std::panic::catch_unwind(|| unsafe {
+            allocator.deallocate(allocation.as_non_null_ptr(), layout)
+        });
+        hooks::set_munmap_hook(None);
+
+        let still_mapped = unsafe { std::ptr::read_volatile(allocation.as_mut_ptr()) } == 0;
+        unsafe { libc::_exit(if res.is_ok() && still_mapped { 0 } else { 1 }) };
+    }
+
+    let mut status = 0;
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "waitpid failed");
+    assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
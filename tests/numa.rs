@@ -0,0 +1,60 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::path::Path;
+
+const MPOL_BIND: libc::c_int = 2;
+const MPOL_F_ADDR: libc::c_ulong = 1 << 1;
+
+/// Returns the memory policy and node mask applying to `addr`.
+fn memory_policy(addr: *mut u8) -> (libc::c_int, [libc::c_ulong; 16]) {
+    let mut mode = 0;
+    let mut node_mask = [0; 16];
+    let max_node = (node_mask.len() * libc::c_ulong::BITS as usize + 1) as libc::c_ulong;
+
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_get_mempolicy,
+            &mut mode,
+            node_mask.as_mut_ptr(),
+            max_node,
+            addr,
+            MPOL_F_ADDR,
+        )
+    };
+    assert_ne!(res, -1, "get_mempolicy failed");
+
+    (mode, node_mask)
+}
+
+#[test]
+fn bind_to_node_0() {
+    if !Path::new("/sys/devices/system/node/node0").exists() {
+        eprintln!("NUMA is not available, skipping");
+        return;
+    }
+
+    let allocator = MMapAllocator::with_numa_node(0);
+
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+
+    let (mode, node_mask) = memory_policy(allocation.as_mut_ptr());
+    assert_eq!(mode, MPOL_BIND);
+    assert_eq!(node_mask[0], 1);
+    assert!(node_mask[1..].iter().all(|&word| word == 0));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn bind_to_missing_node() {
+    let allocator = MMapAllocator::with_numa_node(u32::MAX);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    assert!(allocator.allocate(layout).is_err());
+}
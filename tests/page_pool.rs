@@ -0,0 +1,86 @@
+#![cfg(feature = "std")]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{MMapAllocator, PagePool};
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn reuse_freed_mapping() {
+    let pool = PagePool::with_allocator(MMapAllocator::with_stats(), 1, 4);
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    let mut first_allocation = pool.allocate(layout).expect("allocate failed");
+    unsafe { first_allocation.as_mut().fill(42) };
+    unsafe { pool.deallocate(first_allocation.as_non_null_ptr(), layout) };
+    assert_eq!(pool.free_mappings(), 1);
+    assert_eq!(pool.mmap_allocator().live_allocations(), 1);
+
+    // The same mapping should be handed out again, without mapping anything.
+    let second_allocation = pool.allocate_zeroed(layout).expect("allocate failed");
+    assert_eq!(second_allocation, first_allocation);
+    assert_eq!(pool.free_mappings(), 0);
+    assert_eq!(pool.mmap_allocator().live_allocations(), 1);
+
+    let allocation_slice = unsafe { second_allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+
+    unsafe { pool.deallocate(second_allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn only_reuse_matching_sizes() {
+    let pool = PagePool::with_allocator(MMapAllocator::with_stats(), 2, 4);
+
+    let small_layout = Layout::from_size_align(10, 16).unwrap();
+    let small_allocation = pool.allocate(small_layout).expect("allocate failed");
+    unsafe { pool.deallocate(small_allocation.as_non_null_ptr(), small_layout) };
+
+    let large_layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let large_allocation = pool.allocate(large_layout).expect("allocate failed");
+    assert_eq!(large_allocation.len(), 2 * page_size::get());
+    assert_eq!(pool.mmap_allocator().live_allocations(), 2);
+
+    // Mappings larger than `max_pages` are never recycled.
+    let huge_layout = Layout::from_size_align(3 * page_size::get(), 16).unwrap();
+    let huge_allocation = pool.allocate(huge_layout).expect("allocate failed");
+    unsafe { pool.deallocate(huge_allocation.as_non_null_ptr(), huge_layout) };
+    assert_eq!(pool.free_mappings(), 1);
+
+    unsafe { pool.deallocate(large_allocation.as_non_null_ptr(), large_layout) };
+    assert_eq!(pool.free_mappings(), 2);
+    assert_eq!(pool.mmap_allocator().live_allocations(), 2);
+}
+
+#[test]
+fn unmap_beyond_capacity() {
+    let pool = PagePool::with_allocator(MMapAllocator::with_stats(), 1, 2);
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    let allocations: Vec<_> = (0..4)
+        .map(|_| pool.allocate(layout).expect("allocate failed"))
+        .collect();
+    assert_eq!(pool.mmap_allocator().live_allocations(), 4);
+
+    for allocation in allocations {
+        unsafe { pool.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+
+    // Only the mappings that fit in the free list are kept.
+    assert_eq!(pool.free_mappings(), 2);
+    assert_eq!(pool.mmap_allocator().live_allocations(), 2);
+}
+
+#[test]
+fn vec_in_pool() {
+    let pool = PagePool::new(4, 4);
+
+    let mut vec: Vec<u32, _> = Vec::new_in(&pool);
+    vec.extend(0..4096);
+    assert!(vec.iter().copied().eq(0..4096));
+
+    drop(vec);
+    let mut vec: Vec<u32, _> = Vec::new_in(&pool);
+    vec.resize(16, 0);
+    assert!(vec.iter().all(|&elem| elem == 0));
+}
@@ -0,0 +1,139 @@
+#![cfg(all(unix, feature = "page-size-override"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+
+/// Logical page size larger than the system page size of any common host.
+const LARGE_PAGE_SIZE: usize = 64 << 10;
+
+#[test]
+fn round_to_small_pages() {
+    let allocator = MMapAllocator::with_page_size_for_testing(4 << 10);
+
+    // Pages smaller than the system ones cannot be mapped.
+    let page_size = page_size::get().max(4 << 10);
+
+    for (size, mapping_size) in [
+        (1, page_size),
+        (page_size, page_size),
+        (page_size + 1, 2 * page_size),
+    ] {
+        let layout = Layout::from_size_align(size, 1).unwrap();
+        assert_eq!(allocator.mapping_size(layout).unwrap(), mapping_size);
+
+        let allocation = allocator.allocate(layout).expect("allocate failed");
+        assert_eq!(allocation.len(), mapping_size);
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+}
+
+#[test]
+fn round_to_large_pages() {
+    let allocator = MMapAllocator::builder()
+        .page_size_for_testing(LARGE_PAGE_SIZE)
+        .stats(true)
+        .build()
+        .expect("build failed");
+
+    let layout = Layout::from_size_align(10, 1).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.len(), LARGE_PAGE_SIZE);
+    assert_eq!(allocation.as_mut_ptr() as usize % LARGE_PAGE_SIZE, 0);
+    assert_eq!(allocator.bytes_mapped(), LARGE_PAGE_SIZE);
+    unsafe { allocation.as_mut().fill(42) };
+    let ptr = allocation.as_non_null_ptr();
+
+    // Growing within the last logical page keeps the memory block in place.
+    let in_page_layout = Layout::from_size_align(LARGE_PAGE_SIZE, 1).unwrap();
+    let resize = unsafe { allocator.resize(ptr, layout, in_page_layout) }.expect("resize failed");
+    assert!(!resize.moved);
+
+    // Growing past it maps a whole new logical page.
+    let grown_layout = Layout::from_size_align(LARGE_PAGE_SIZE + 1, 1).unwrap();
+    let grown = unsafe { allocator.grow(ptr, in_page_layout, grown_layout) }.expect("grow failed");
+    assert_eq!(grown.len(), 2 * LARGE_PAGE_SIZE);
+    assert_eq!(grown.as_mut_ptr() as usize % LARGE_PAGE_SIZE, 0);
+    assert_eq!(allocator.bytes_mapped(), 2 * LARGE_PAGE_SIZE);
+    assert!(unsafe { grown.as_ref() }[..LARGE_PAGE_SIZE]
+        .iter()
+        .all(|&byte| byte == 42));
+    let ptr = grown.as_non_null_ptr();
+
+    // Shrinking releases the last logical page.
+    let shrunk = unsafe { allocator.shrink(ptr, grown_layout, layout) }.expect("shrink failed");
+    assert_eq!(shrunk.as_non_null_ptr(), ptr);
+    assert_eq!(shrunk.len(), LARGE_PAGE_SIZE);
+    assert_eq!(allocator.bytes_mapped(), LARGE_PAGE_SIZE);
+
+    unsafe { allocator.deallocate(ptr, layout) };
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+fn offsets_within_large_pages() {
+    let allocator = MMapAllocator::with_page_size_for_testing(LARGE_PAGE_SIZE);
+
+    // The offset is only valid within a logical page.
+    let layout = Layout::from_size_align(10, 1).unwrap();
+    let offset = LARGE_PAGE_SIZE - 10;
+    let allocation =
+        unsafe { allocator.allocate_with_offset(layout, offset) }.expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % LARGE_PAGE_SIZE, offset);
+    assert_eq!(allocation.len(), 10);
+
+    assert!(unsafe { allocator.allocate_with_offset(layout, LARGE_PAGE_SIZE) }.is_err());
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(not(debug_assertions))]
+fn reject_non_power_of_two() {
+    use mmap_allocator::MMapError;
+    use std::ptr::NonNull;
+
+    // Smaller page sizes are raised to the system page size, which is a
+    // power of two.
+    let page_size = 3 * page_size::get();
+    let allocator = MMapAllocator::with_page_size_for_testing(page_size);
+
+    let layout = Layout::from_size_align(10, 1).unwrap();
+    assert_eq!(
+        allocator.try_allocate(layout),
+        Err(MMapError::PageSizeNotPowerOfTwo)
+    );
+    assert!(allocator.allocate(layout).is_err());
+    assert!(unsafe { allocator.allocate_with_offset(layout, 0) }.is_err());
+    let addr = NonNull::new(page_size as *mut u8).unwrap();
+    assert!(unsafe { allocator.allocate_fixed(addr, layout) }.is_err());
+    assert!(allocator.mapping_size(layout).is_err());
+    assert!(allocator.pages(NonNull::dangling(), layout).is_err());
+    assert!(allocator.reserve(page_size).is_err());
+    assert!(allocator.allocate_growable(1, 4).is_err());
+    assert_eq!(allocator.largest_available(), 0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "the page size is not a power of two")]
+fn assert_power_of_two_in_debug() {
+    let allocator = MMapAllocator::with_page_size_for_testing(3 * page_size::get());
+
+    let layout = Layout::from_size_align(10, 1).unwrap();
+    let _ = allocator.try_allocate(layout);
+}
+
+#[test]
+fn reject_non_power_of_two_when_building() {
+    use mmap_allocator::BuildError;
+
+    assert_eq!(
+        MMapAllocator::builder()
+            .page_size_for_testing(3 * page_size::get())
+            .build()
+            .unwrap_err(),
+        BuildError::PageSizeNotPowerOfTwo
+    );
+}
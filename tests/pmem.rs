@@ -0,0 +1,57 @@
+#![cfg(all(feature = "test-pmem", target_os = "linux"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+
+/// Opens the file on persistent memory given by `MMAP_ALLOCATOR_PMEM_PATH`,
+/// making it at least `size` bytes long.
+fn open_pmem_file(size: usize) -> Option<std::fs::File> {
+    let path = std::env::var_os("MMAP_ALLOCATOR_PMEM_PATH")?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .expect("cannot open the persistent memory file");
+
+    if file.metadata().unwrap().len() < size as u64 {
+        file.set_len(size as u64).expect("cannot resize the file");
+    }
+
+    Some(file)
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn write_and_flush_pmem() {
+    let size = 2 * page_size::get();
+    let file = match open_pmem_file(size) {
+        Some(file) => file,
+        None => {
+            eprintln!("MMAP_ALLOCATOR_PMEM_PATH is not set, skipping");
+            return;
+        }
+    };
+
+    let allocator = MMapAllocator::pmem_file(file.as_raw_fd(), 0);
+
+    let layout = Layout::from_size_align(size, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe {
+        allocation.as_mut().fill(0x5a);
+        allocator.flush(allocation.as_non_null_ptr(), layout);
+    }
+
+    // The stores must have reached the file.
+    let mut buf = vec![0; size];
+    let res = unsafe { libc::pread(file.as_raw_fd(), buf.as_mut_ptr().cast(), size, 0) };
+    assert_eq!(res, size as isize, "pread failed");
+    assert!(buf.iter().all(|&byte| byte == 0x5a));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
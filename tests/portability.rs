@@ -0,0 +1,43 @@
+//! The flags passed to `mmap` are selected per target, as `libc` does not
+//! spell them the same way everywhere. Besides running the tests on the host,
+//! the library should be checked for the targets covering every branch:
+//!
+//! ```text
+//! cargo check --target x86_64-unknown-linux-gnu    # MAP_ANONYMOUS, glibc
+//! cargo check --target x86_64-unknown-linux-musl   # MAP_ANONYMOUS, musl
+//! cargo check --target aarch64-linux-android       # MAP_ANONYMOUS, bionic
+//! cargo check --target aarch64-apple-darwin        # MAP_ANON
+//! cargo check --target x86_64-unknown-freebsd      # MAP_ANON
+//! cargo check --target x86_64-unknown-illumos      # MAP_ANON
+//! cargo check --target x86_64-pc-windows-gnu       # VirtualAlloc
+//! ```
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn anonymous_mappings_are_zeroed() {
+    let allocator = MMapAllocator::new();
+
+    for size in [1, page_size::get(), 3 * page_size::get() + 10] {
+        let layout = Layout::from_size_align(size, 16).unwrap();
+        let allocation = allocator.allocate(layout).expect("allocate failed");
+        assert!(unsafe { allocation.as_ref() }.iter().all(|&byte| byte == 0));
+
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn shared_anonymous_mappings_are_zeroed() {
+    let allocator = MMapAllocator::shared();
+
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert!(unsafe { allocation.as_ref() }.iter().all(|&byte| byte == 0));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
@@ -0,0 +1,101 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+mod common;
+
+use mmap_allocator::{MMapAllocator, Protection};
+use std::alloc::{Allocator, Layout};
+use std::ptr;
+
+#[test]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn allocate_executable() {
+    let allocator =
+        MMapAllocator::with_protection(Protection::READ | Protection::WRITE | Protection::EXEC);
+
+    let layout = Layout::from_size_align(16, 16).unwrap();
+    let mut allocation = match allocator.allocate(layout) {
+        Ok(allocation) => allocation,
+        Err(_) => {
+            eprintln!("writable and executable mappings are not allowed, skipping");
+            return;
+        }
+    };
+    let allocation_slice = unsafe { allocation.as_mut() };
+
+    const RET: u8 = 0xc3;
+    *allocation_slice.first_mut().unwrap() = RET;
+
+    let function: extern "C" fn() = unsafe { std::mem::transmute(allocation.as_mut_ptr()) };
+    function();
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn allocate_read_only() {
+    let allocator = MMapAllocator::with_protection(Protection::READ);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let allocation_slice = unsafe { allocation.as_ref() };
+
+    // Read-only anonymous memory is still zeroed.
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn protect_read_only() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    let allocation_slice = unsafe { allocation.as_mut() };
+
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe {
+        allocator
+            .protect(allocation.as_non_null_ptr(), layout, Protection::READ)
+            .expect("protect failed")
+    };
+
+    // The contents should still be readable.
+    let allocation_slice = unsafe { allocation.as_ref() };
+    assert_eq!(*allocation_slice.last().unwrap(), 42);
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(allocation.as_mut_ptr().add(allocation.len() - 1), 0);
+    });
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn allocate_readonly_copy() {
+    let allocator = MMapAllocator::new();
+
+    let data: Vec<u8> = (0..=255).cycle().take(page_size::get() + 10).collect();
+    let allocation = allocator
+        .allocate_readonly_from(&data)
+        .expect("allocate_readonly_from failed");
+    assert_eq!(allocation.len(), 2 * page_size::get());
+
+    let allocation_slice = unsafe { allocation.as_ref() };
+    assert_eq!(&allocation_slice[..data.len()], &data[..]);
+    assert!(allocation_slice[data.len()..].iter().all(|&byte| byte == 0));
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(allocation.as_mut_ptr(), 42);
+    });
+
+    let layout = Layout::for_value(&data[..]);
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    // Scrubbing the memory block on deallocation would fault.
+    let allocator = MMapAllocator::with_zero_on_free(true);
+    assert!(allocator.allocate_readonly_from(&data).is_err());
+}
@@ -0,0 +1,291 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+mod common;
+
+use mmap_allocator::{GrowableRegion, MMapAllocator, ReservedAllocator};
+use std::alloc::{Allocator, Layout};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+#[test]
+fn commit_incrementally() {
+    let allocator = MMapAllocator::new();
+
+    let reservation = allocator
+        .reserve(16 * page_size::get())
+        .expect("reserve failed");
+    assert_eq!(reservation.size(), 16 * page_size::get());
+
+    let base = reservation.as_ptr().as_ptr();
+
+    reservation
+        .commit(0, 2 * page_size::get())
+        .expect("commit failed");
+    unsafe {
+        ptr::write_volatile(base, 42);
+        ptr::write_volatile(base.add(2 * page_size::get() - 1), 42);
+    }
+
+    // Uncommitted pages should still be inaccessible.
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(base.add(2 * page_size::get()), 42);
+    });
+
+    // A partial page commit covers the whole page.
+    reservation
+        .commit(2 * page_size::get(), page_size::get() + 10)
+        .expect("commit failed");
+    unsafe {
+        ptr::write_volatile(base.add(2 * page_size::get()), 42);
+        ptr::write_volatile(base.add(4 * page_size::get() - 1), 42);
+
+        // Committing more pages should keep the existing contents.
+        assert_eq!(ptr::read_volatile(base), 42);
+    }
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(base.add(4 * page_size::get()), 42);
+    });
+}
+
+#[test]
+fn commit_out_of_bounds() {
+    let allocator = MMapAllocator::new();
+
+    let reservation = allocator
+        .reserve(4 * page_size::get())
+        .expect("reserve failed");
+
+    assert!(reservation
+        .commit(3 * page_size::get(), 2 * page_size::get())
+        .is_err());
+    assert!(reservation.commit(usize::MAX, 1).is_err());
+}
+
+#[test]
+fn grow_within_reservation() {
+    let page_size = page_size::get();
+    let reservation = MMapAllocator::new()
+        .reserve(64 * page_size)
+        .expect("reserve failed");
+    let allocator = ReservedAllocator::new(reservation);
+
+    let mut layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let base = allocation.as_mut_ptr();
+    assert_eq!(base, allocator.reservation().as_ptr().as_ptr());
+
+    // A single memory block can be allocated at a time.
+    assert!(allocator.allocate(layout).is_err());
+
+    for pages in 1..=64 {
+        let new_layout = Layout::from_size_align(pages * page_size, 16).unwrap();
+        let grown = unsafe { allocator.grow(ptr::NonNull::new(base).unwrap(), layout, new_layout) }
+            .expect("grow failed");
+        assert_eq!(grown.as_mut_ptr(), base);
+        assert_eq!(grown.len(), pages * page_size);
+
+        unsafe { ptr::write_volatile(base.add(pages * page_size - 1), pages as u8) };
+        layout = new_layout;
+    }
+
+    // Every page kept its contents.
+    for pages in 1..=64 {
+        assert_eq!(unsafe { *base.add(pages * page_size - 1) }, pages as u8);
+    }
+
+    // The reservation is exhausted.
+    let too_large = Layout::from_size_align(65 * page_size, 16).unwrap();
+    assert!(
+        unsafe { allocator.grow(ptr::NonNull::new(base).unwrap(), layout, too_large) }.is_err()
+    );
+
+    unsafe { allocator.deallocate(ptr::NonNull::new(base).unwrap(), layout) };
+}
+
+#[test]
+fn vec_in_reservation() {
+    let reservation = MMapAllocator::new()
+        .reserve(16 * page_size::get())
+        .expect("reserve failed");
+    let allocator = ReservedAllocator::new(reservation);
+
+    let mut vec = Vec::with_capacity_in(1, &allocator);
+    vec.push(0u64);
+    let base = vec.as_ptr();
+
+    for i in 1..(16 * page_size::get() / 8) as u64 {
+        vec.push(i);
+        assert_eq!(vec.as_ptr(), base);
+    }
+    assert!(vec.iter().enumerate().all(|(i, &elem)| elem == i as u64));
+    assert!(vec.try_reserve(1).is_err());
+}
+
+#[test]
+fn shrink_and_reallocate_reservation() {
+    let page_size = page_size::get();
+    let reservation = MMapAllocator::new()
+        .reserve(4 * page_size)
+        .expect("reserve failed");
+    let allocator = ReservedAllocator::new(reservation);
+
+    let layout = Layout::from_size_align(4 * page_size, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let base = allocation.as_mut_ptr();
+    unsafe { allocation.as_mut_ptr().write_bytes(0xaa, 4 * page_size) };
+
+    let shrunk_layout = Layout::from_size_align(page_size, 16).unwrap();
+    let shrunk = unsafe { allocator.shrink(allocation.as_non_null_ptr(), layout, shrunk_layout) }
+        .expect("shrink failed");
+    assert_eq!(shrunk.as_mut_ptr(), base);
+    assert_eq!(shrunk.len(), page_size);
+
+    // The tail pages are decommitted.
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(base.add(page_size), 42);
+    });
+
+    unsafe { allocator.deallocate(shrunk.as_non_null_ptr(), shrunk_layout) };
+
+    // The reservation can be reused, with fresh pages.
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr(), base);
+    assert!(unsafe { allocation.as_ref() }.iter().all(|&byte| byte == 0));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn commit_growable_region_page_by_page() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let region = allocator.allocate_growable(1, 4).expect("allocate failed");
+    assert_eq!(region.committed_size(), page_size);
+    assert_eq!(region.max_size(), 4 * page_size);
+
+    let base = region.as_ptr().as_ptr();
+    for pages in 1..=4 {
+        let guard = region.guard_page().as_ptr();
+        assert_eq!(guard, unsafe { base.add(pages * page_size) });
+
+        // The last committed page is writable, and the guard page faults.
+        unsafe {
+            let last_page = guard.sub(page_size);
+            ptr::write_volatile(last_page, pages as u8);
+            ptr::write_volatile(guard.sub(1), pages as u8);
+        }
+        common::assert_faults(|| unsafe { ptr::write_volatile(guard, 1) });
+
+        if pages < 4 {
+            // Anywhere in the guard page commits it.
+            let fault = unsafe { NonNull::new_unchecked(guard.add(page_size / 2)) };
+            region.commit_next(fault).expect("commit failed");
+            assert_eq!(region.committed_size(), (pages + 1) * page_size);
+        }
+    }
+
+    // The region cannot grow past `max` pages, and keeps faulting past them.
+    let guard = region.guard_page();
+    assert!(region.commit_next(guard).is_err());
+    assert_eq!(region.committed_size(), 4 * page_size);
+
+    // The committed pages kept their contents.
+    for pages in 1..=4 {
+        assert_eq!(
+            unsafe { ptr::read_volatile(base.add((pages - 1) * page_size)) },
+            pages as u8
+        );
+    }
+}
+
+#[test]
+fn commit_next_outside_guard_page() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let region = allocator.allocate_growable(0, 4).expect("allocate failed");
+    assert_eq!(region.committed_size(), 0);
+    assert_eq!(region.guard_page(), region.as_ptr());
+
+    // Faults past the guard page, or before the region, are not growths.
+    let base = region.as_ptr().as_ptr();
+    let past_guard = unsafe { NonNull::new_unchecked(base.add(page_size)) };
+    assert!(region.commit_next(past_guard).is_err());
+    let before = unsafe { NonNull::new_unchecked(base.sub(1)) };
+    assert!(region.commit_next(before).is_err());
+    assert_eq!(region.committed_size(), 0);
+
+    // Committed pages are left as they are.
+    region.commit_next(region.as_ptr()).expect("commit failed");
+    region.commit_next(region.as_ptr()).expect("commit failed");
+    assert_eq!(region.committed_size(), page_size);
+
+    assert!(allocator.allocate_growable(5, 4).is_err());
+}
+
+/// Region grown by `commit_on_fault`.
+static GROWABLE_REGION: AtomicPtr<GrowableRegion> = AtomicPtr::new(ptr::null_mut());
+
+/// Commits the faulting page of `GROWABLE_REGION`, or lets the fault kill the
+/// process if it is not a growth of the region.
+extern "C" fn commit_on_fault(
+    _signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    _context: *mut libc::c_void,
+) {
+    unsafe {
+        let region = &*GROWABLE_REGION.load(Ordering::Acquire);
+        let fault = NonNull::new((*info).si_addr().cast::<u8>());
+        if fault.is_none_or(|fault| region.commit_next(fault).is_err()) {
+            libc::signal(libc::SIGSEGV, libc::SIG_DFL);
+            libc::signal(libc::SIGBUS, libc::SIG_DFL);
+        }
+    }
+}
+
+#[test]
+fn grow_on_fault() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let region = allocator.allocate_growable(1, 8).expect("allocate failed");
+
+    // The handler is only installed in a child process, so that it does not
+    // interfere with the other tests.
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1, "fork failed");
+
+    if pid == 0 {
+        unsafe {
+            GROWABLE_REGION.store(&region as *const _ as *mut _, Ordering::Release);
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = commit_on_fault as *const () as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO;
+            libc::sigaction(libc::SIGSEGV, &action, ptr::null_mut());
+            libc::sigaction(libc::SIGBUS, &action, ptr::null_mut());
+
+            // Every page is committed by the handler when first written.
+            let base = region.as_ptr().as_ptr();
+            for offset in (0..region.max_size()).step_by(page_size) {
+                ptr::write_volatile(base.add(offset), 42);
+            }
+
+            let code = if region.committed_size() == region.max_size() {
+                0
+            } else {
+                1
+            };
+            libc::_exit(code);
+        }
+    }
+
+    let mut status = 0;
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "waitpid failed");
+    assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+}
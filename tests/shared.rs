@@ -0,0 +1,157 @@
+#![cfg(unix)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::ptr;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+/// Runs `f` in a forked child process and waits for it to exit.
+fn run_in_child(f: impl FnOnce()) {
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1, "fork failed");
+
+    if pid == 0 {
+        f();
+        unsafe { libc::_exit(0) };
+    }
+
+    let mut status = 0;
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "waitpid failed");
+    assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+}
+
+/// Writes a sentinel in the parent and one in a child, then returns the
+/// values seen by the parent.
+fn write_across_fork(allocator: &MMapAllocator) -> (u8, u8) {
+    let layout = Layout::from_size_align(2, 1).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_mut_ptr();
+
+    unsafe { ptr::write_volatile(ptr, 1) };
+    run_in_child(|| unsafe {
+        assert_eq!(ptr::read_volatile(ptr), 1);
+        ptr::write_volatile(ptr.add(1), 2);
+    });
+
+    let values = unsafe { (ptr::read_volatile(ptr), ptr::read_volatile(ptr.add(1))) };
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    values
+}
+
+#[test]
+fn shared_across_fork() {
+    assert_eq!(write_across_fork(&MMapAllocator::shared()), (1, 2));
+}
+
+#[test]
+fn private_by_default() {
+    assert_eq!(write_across_fork(&MMapAllocator::new()), (1, 0));
+}
+
+#[test]
+fn grow_keeps_sharing() {
+    let allocator = MMapAllocator::shared();
+
+    let initial_layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { ptr::write_volatile(initial_allocation.as_mut_ptr(), 1) };
+
+    let grown_layout = Layout::from_size_align(2 * page_size::get(), 1).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    let ptr = grown_allocation.as_mut_ptr();
+
+    // The whole grown allocation should be shared with new children.
+    run_in_child(|| unsafe {
+        ptr::write_volatile(ptr.add(page_size::get()), 2);
+    });
+    unsafe {
+        assert_eq!(ptr::read_volatile(ptr), 1);
+        assert_eq!(ptr::read_volatile(ptr.add(page_size::get())), 2);
+    }
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+/// Asserts that the pages of the `len` bytes at `ptr` are absent from the
+/// address space of a forked child, which faults when accessing them.
+#[cfg(target_os = "linux")]
+fn assert_not_inherited(ptr: *mut u8, len: usize) {
+    common::assert_faults(|| unsafe {
+        // `mincore` fails with `ENOMEM` on unmapped pages.
+        for offset in (0..len).step_by(page_size::get()) {
+            let mut vec = 0;
+            if libc::mincore(ptr.add(offset).cast(), 1, &mut vec) == 0 {
+                libc::_exit(1);
+            }
+        }
+
+        ptr::read_volatile(ptr);
+    });
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn dontfork_not_inherited() {
+    let allocator = MMapAllocator::with_dontfork(true);
+
+    let layout = Layout::from_size_align(10, 1).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let ptr = allocation.as_mut_ptr();
+    unsafe { ptr::write_volatile(ptr, 42) };
+
+    assert_not_inherited(ptr, allocation.len());
+    assert_eq!(unsafe { ptr::read_volatile(ptr) }, 42);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn grow_keeps_dontfork() {
+    // Guard pages force `grow` to copy the contents to a new mapping, which
+    // is tried along with the `mremap` path of the plain allocator.
+    for allocator in [
+        MMapAllocator::with_dontfork(true),
+        MMapAllocator::builder()
+            .dontfork(true)
+            .guard_pages(1)
+            .build()
+            .unwrap(),
+    ] {
+        let initial_layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+        let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+        unsafe { ptr::write_volatile(initial_allocation.as_mut_ptr(), 42) };
+
+        let grown_layout = Layout::from_size_align(4 * page_size::get(), 1).unwrap();
+        let grown_allocation = unsafe {
+            allocator
+                .grow(
+                    initial_allocation.as_non_null_ptr(),
+                    initial_layout,
+                    grown_layout,
+                )
+                .expect("grow failed")
+        };
+        let ptr = grown_allocation.as_mut_ptr();
+
+        assert_not_inherited(ptr, grown_allocation.len());
+        assert_eq!(unsafe { ptr::read_volatile(ptr) }, 42);
+
+        unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+    }
+}
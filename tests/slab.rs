@@ -0,0 +1,252 @@
+#![cfg(feature = "std")]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{MMapAllocator, SlabAllocator};
+use std::alloc::{Allocator, Layout};
+use std::ptr::NonNull;
+
+fn slab_allocator() -> SlabAllocator {
+    SlabAllocator::with_allocator(MMapAllocator::with_stats())
+}
+
+#[test]
+fn allocate_many_objects() {
+    let allocator = slab_allocator();
+
+    let mut allocations = Vec::new();
+    for size in [1, 16, 24, 100, 512] {
+        let layout = Layout::from_size_align(size, 8).unwrap();
+        for i in 0..1000 {
+            let mut allocation = allocator.allocate(layout).expect("allocate failed");
+            assert!(allocation.len() >= size);
+            assert_eq!(allocation.as_mut_ptr() as usize % 8, 0);
+            unsafe { allocation.as_mut()[..size].fill(i as u8) };
+            allocations.push((allocation, layout, i as u8));
+        }
+    }
+
+    // Cells are packed into slabs instead of being mapped one by one.
+    let slabs = allocator.slabs();
+    assert!(slabs < 1000);
+    assert_eq!(allocator.mmap_allocator().live_allocations(), slabs);
+
+    for (allocation, layout, byte) in allocations {
+        let allocation_slice = unsafe { &allocation.as_ref()[..layout.size()] };
+        assert!(allocation_slice.iter().all(|&value| value == byte));
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+
+    // Every slab is unmapped once all of its cells are free.
+    assert_eq!(allocator.slabs(), 0);
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+}
+
+#[test]
+fn reuse_freed_cells() {
+    let allocator = slab_allocator();
+    let layout = Layout::from_size_align(32, 8).unwrap();
+
+    // Keep the slab mapped while freeing a cell.
+    let first_allocation = allocator.allocate(layout).expect("allocate failed");
+    let second_allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocator.slabs(), 1);
+
+    unsafe { allocator.deallocate(second_allocation.as_non_null_ptr(), layout) };
+    let third_allocation = allocator.allocate_zeroed(layout).expect("allocate failed");
+    assert_eq!(third_allocation, second_allocation);
+    assert_eq!(allocator.slabs(), 1);
+
+    let allocation_slice = unsafe { third_allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+
+    unsafe {
+        allocator.deallocate(first_allocation.as_non_null_ptr(), layout);
+        allocator.deallocate(third_allocation.as_non_null_ptr(), layout);
+    }
+    assert_eq!(allocator.slabs(), 0);
+}
+
+#[test]
+fn separate_slabs_per_size_class() {
+    let allocator = slab_allocator();
+
+    let small_layout = Layout::from_size_align(16, 8).unwrap();
+    let large_layout = Layout::from_size_align(64, 8).unwrap();
+    let small_allocation = allocator.allocate(small_layout).expect("allocate failed");
+    let large_allocation = allocator.allocate(large_layout).expect("allocate failed");
+    assert_eq!(small_allocation.len(), 16);
+    assert_eq!(large_allocation.len(), 64);
+    assert_eq!(allocator.slabs(), 2);
+
+    unsafe { allocator.deallocate(small_allocation.as_non_null_ptr(), small_layout) };
+    assert_eq!(allocator.slabs(), 1);
+    unsafe { allocator.deallocate(large_allocation.as_non_null_ptr(), large_layout) };
+    assert_eq!(allocator.slabs(), 0);
+}
+
+#[test]
+fn fill_several_slabs() {
+    let allocator = slab_allocator();
+    let layout = Layout::from_size_align(allocator.max_cell_size(), 8).unwrap();
+
+    let allocations: Vec<NonNull<[u8]>> = (0..16)
+        .map(|_| allocator.allocate(layout).expect("allocate failed"))
+        .collect();
+    let slabs = allocator.slabs();
+    assert!(slabs > 1);
+
+    // Freeing a cell of each slab leaves all of them mapped, and the freed
+    // cells are handed out again before mapping a new slab.
+    for allocation in allocations.iter().step_by(4) {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+    let refills: Vec<NonNull<[u8]>> = (0..4)
+        .map(|_| allocator.allocate(layout).expect("allocate failed"))
+        .collect();
+    assert_eq!(allocator.slabs(), slabs);
+
+    for allocation in allocations.iter().skip(1).step_by(4) {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+    for allocation in allocations
+        .iter()
+        .skip(2)
+        .step_by(4)
+        .chain(allocations.iter().skip(3).step_by(4))
+        .chain(&refills)
+    {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+    assert_eq!(allocator.slabs(), 0);
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+}
+
+#[test]
+fn large_allocations_are_mapped() {
+    let allocator = slab_allocator();
+
+    let layout = Layout::from_size_align(allocator.max_cell_size() + 1, 8).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.len(), page_size::get());
+    assert_eq!(allocator.slabs(), 0);
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 1);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 0);
+}
+
+#[test]
+fn over_aligned_cells() {
+    let allocator = slab_allocator();
+
+    let layout = Layout::from_size_align(8, 256).unwrap();
+    let allocations: Vec<NonNull<[u8]>> = (0..4)
+        .map(|_| allocator.allocate(layout).expect("allocate failed"))
+        .collect();
+    for allocation in &allocations {
+        assert_eq!(allocation.as_mut_ptr() as usize % 256, 0);
+    }
+    assert_eq!(allocator.slabs(), 1);
+
+    for allocation in allocations {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+}
+
+#[test]
+fn grow_across_size_classes() {
+    let allocator = slab_allocator();
+
+    let small_layout = Layout::from_size_align(10, 8).unwrap();
+    let mut allocation = allocator.allocate(small_layout).expect("allocate failed");
+    unsafe { allocation.as_mut()[..10].fill(42) };
+
+    // Growing within the cell keeps it in place.
+    let same_class_layout = Layout::from_size_align(16, 8).unwrap();
+    let same_class_allocation = unsafe {
+        allocator
+            .grow(
+                allocation.as_non_null_ptr(),
+                small_layout,
+                same_class_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(same_class_allocation, allocation);
+
+    // Growing past the largest cells moves the contents to a mapping.
+    let large_layout = Layout::from_size_align(2 * page_size::get(), 8).unwrap();
+    let large_allocation = unsafe {
+        allocator
+            .grow_zeroed(
+                same_class_allocation.as_non_null_ptr(),
+                same_class_layout,
+                large_layout,
+            )
+            .expect("grow failed")
+    };
+    let allocation_slice = unsafe { large_allocation.as_ref() };
+    assert!(allocation_slice[..10].iter().all(|&byte| byte == 42));
+    assert!(allocation_slice[16..].iter().all(|&byte| byte == 0));
+    assert_eq!(allocator.slabs(), 0);
+
+    // Shrinking moves them back to a cell.
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                large_allocation.as_non_null_ptr(),
+                large_layout,
+                small_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(shrunk_allocation.len(), 16);
+    let allocation_slice = unsafe { shrunk_allocation.as_ref() };
+    assert!(allocation_slice[..10].iter().all(|&byte| byte == 42));
+    assert_eq!(allocator.slabs(), 1);
+    assert_eq!(allocator.mmap_allocator().live_allocations(), 1);
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), small_layout) };
+}
+
+#[test]
+fn share_across_threads() {
+    let allocator = slab_allocator();
+    let layout = Layout::from_size_align(48, 8).unwrap();
+
+    std::thread::scope(|scope| {
+        for thread in 0..4u8 {
+            let allocator = &allocator;
+            scope.spawn(move || {
+                let allocations: Vec<NonNull<[u8]>> = (0..500)
+                    .map(|_| {
+                        let mut allocation = allocator.allocate(layout).expect("allocate failed");
+                        unsafe { allocation.as_mut().fill(thread) };
+                        allocation
+                    })
+                    .collect();
+
+                for allocation in allocations {
+                    let allocation_slice = unsafe { allocation.as_ref() };
+                    assert!(allocation_slice.iter().all(|&byte| byte == thread));
+                    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+                }
+            });
+        }
+    });
+
+    assert_eq!(allocator.slabs(), 0);
+}
+
+#[test]
+fn drop_unmaps_slabs() {
+    let allocator = slab_allocator();
+    let layout = Layout::from_size_align(16, 8).unwrap();
+
+    // Leaked cells are released along with their slab.
+    for _ in 0..1000 {
+        allocator.allocate(layout).expect("allocate failed");
+    }
+    assert!(allocator.slabs() > 1);
+}
@@ -0,0 +1,120 @@
+#![cfg(unix)]
+
+mod common;
+
+use mmap_allocator::MMapAllocator;
+use std::ptr;
+
+#[test]
+fn write_inside_stack() {
+    let allocator = MMapAllocator::new();
+
+    let stack = allocator
+        .allocate_stack(4 * page_size::get() - 10)
+        .expect("allocate_stack failed");
+    assert_eq!(stack.size(), 4 * page_size::get());
+    assert_eq!(stack.base().as_ptr() as usize % page_size::get(), 0);
+    assert_eq!(
+        stack.top().as_ptr() as usize - stack.base().as_ptr() as usize,
+        stack.size()
+    );
+
+    unsafe {
+        let near_top = stack.top().as_ptr().sub(8);
+        ptr::write_volatile(near_top, 42);
+        assert_eq!(ptr::read_volatile(near_top), 42);
+
+        ptr::write_volatile(stack.base().as_ptr(), 42);
+    }
+}
+
+#[test]
+fn write_below_base_faults() {
+    let allocator = MMapAllocator::new();
+
+    let stack = allocator
+        .allocate_stack(page_size::get())
+        .expect("allocate_stack failed");
+
+    common::assert_faults(|| unsafe {
+        ptr::write_volatile(stack.base().as_ptr().sub(1), 42);
+    });
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn write_inside_growsdown_region() {
+    let allocator = MMapAllocator::new();
+
+    let region = allocator
+        .allocate_growsdown(2 * page_size::get() - 10)
+        .expect("allocate_growsdown failed");
+    assert_eq!(region.initial_size(), 2 * page_size::get());
+    assert_eq!(region.top().as_ptr() as usize % page_size::get(), 0);
+    assert_eq!(
+        region.top().as_ptr() as usize - region.base().as_ptr() as usize,
+        region.initial_size()
+    );
+
+    unsafe {
+        let near_top = region.top().as_ptr().sub(8);
+        ptr::write_volatile(near_top, 42);
+        assert_eq!(ptr::read_volatile(near_top), 42);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn growsdown_region_grows_on_access() {
+    let allocator = MMapAllocator::new();
+
+    let region = allocator
+        .allocate_growsdown(page_size::get())
+        .expect("allocate_growsdown failed");
+    let initial_base = region.base().as_ptr();
+
+    // The kernel maps the page below the region when it is touched.
+    unsafe { ptr::write_volatile(initial_base.sub(1), 42) };
+    assert_eq!(region.base().as_ptr(), unsafe {
+        initial_base.sub(page_size::get())
+    });
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn drop_growsdown_region_next_to_mapping() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let region = allocator
+        .allocate_growsdown(page_size)
+        .expect("allocate_growsdown failed");
+    let initial_base = region.base().as_ptr();
+
+    // Map a page right below the region, which the region must not claim.
+    let neighbour = unsafe {
+        libc::mmap(
+            initial_base.sub(page_size).cast(),
+            page_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE,
+            -1,
+            0,
+        )
+    };
+    if neighbour != initial_base.wrapping_sub(page_size).cast() {
+        eprintln!("cannot map right below the region, skipping");
+        return;
+    }
+
+    assert_eq!(region.base().as_ptr(), initial_base);
+    drop(region);
+
+    // The neighbour is still mapped.
+    let mut residency = 0u8;
+    assert_eq!(
+        unsafe { libc::mincore(neighbour, page_size, &mut residency) },
+        0
+    );
+    assert_eq!(unsafe { libc::munmap(neighbour, page_size) }, 0);
+}
@@ -0,0 +1,90 @@
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn counters_track_mappings() {
+    let allocator = MMapAllocator::with_stats();
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+
+    let small_layout = Layout::from_size_align(10, 16).unwrap();
+    let small_allocation = allocator.allocate(small_layout).expect("allocate failed");
+
+    let initial_layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    assert_eq!(allocator.live_allocations(), 2);
+    assert_eq!(allocator.bytes_mapped(), 3 * page_size::get());
+
+    let grown_layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(allocator.live_allocations(), 2);
+    assert_eq!(allocator.bytes_mapped(), 5 * page_size::get());
+
+    let shrunk_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                grown_allocation.as_non_null_ptr(),
+                grown_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(allocator.live_allocations(), 2);
+    assert_eq!(allocator.bytes_mapped(), 2 * page_size::get());
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+    unsafe { allocator.deallocate(small_allocation.as_non_null_ptr(), small_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+#[cfg(unix)]
+fn counters_track_relocations() {
+    let allocator = MMapAllocator::with_stats();
+
+    // Over-aligning the new layout forces `grow` to move the allocation.
+    let initial_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    let grown_layout = Layout::from_size_align(2 * page_size::get(), 4 * page_size::get()).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(allocator.live_allocations(), 1);
+    assert_eq!(allocator.bytes_mapped(), 2 * page_size::get());
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+fn counters_disabled_by_default() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
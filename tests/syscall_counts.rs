@@ -0,0 +1,208 @@
+#![cfg(all(unix, feature = "syscall-hooks"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::hooks::{self, SyscallCounts};
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::ffi::c_void;
+
+#[test]
+fn allocate_deallocate_single_mapping() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    hooks::reset_syscall_counts();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    assert_eq!(
+        hooks::syscall_counts(),
+        SyscallCounts {
+            mmap: 1,
+            munmap: 1,
+            mremap: 0,
+        }
+    );
+}
+
+#[test]
+fn grow_inside_last_page_makes_no_syscall() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    hooks::reset_syscall_counts();
+    let grown_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    assert_eq!(hooks::syscall_counts(), SyscallCounts::default());
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn grow_outside_last_page_remaps() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+
+    hooks::reset_syscall_counts();
+    let grown_layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+
+    // The mapping should be resized instead of copied.
+    assert_eq!(
+        hooks::syscall_counts(),
+        SyscallCounts {
+            mmap: 0,
+            munmap: 0,
+            mremap: 1,
+        }
+    );
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+/// Fails every call without mapping anything.
+unsafe fn failing_mmap(
+    _addr: *mut c_void,
+    _len: usize,
+    _prot: libc::c_int,
+    _flags: libc::c_int,
+    _fd: libc::c_int,
+    _offset: libc::off_t,
+) -> *mut c_void {
+    libc::MAP_FAILED
+}
+
+#[test]
+fn allocate_fails_with_mmap() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    hooks::set_mmap_hook(Some(failing_mmap));
+    let res = allocator.allocate(layout);
+    hooks::set_mmap_hook(None);
+
+    assert!(res.is_err());
+}
+
+#[test]
+#[cfg(any(target_os = "freebsd", target_os = "netbsd"))]
+fn over_aligned_allocation_needs_no_trimming() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(10, 64 * 1024).unwrap();
+
+    hooks::reset_syscall_counts();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % (64 * 1024), 0);
+
+    // The kernel aligns the mapping by itself, so nothing has to be unmapped.
+    assert_eq!(
+        hooks::syscall_counts(),
+        SyscallCounts {
+            mmap: 1,
+            munmap: 0,
+            mremap: 0,
+        }
+    );
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn try_allocate_opt_rejects_unmappable_layout_without_syscalls() {
+    let allocator = MMapAllocator::with_guard_pages(1);
+
+    // The guard pages and the padding needed to align the block cannot fit in
+    // the address space, so the layout is rejected before `mmap` is called.
+    hooks::reset_syscall_counts();
+    let align = 1 << (usize::BITS - 2);
+    let layout = Layout::from_size_align(align, align).unwrap();
+    assert!(allocator.try_allocate_opt(layout).is_none());
+    assert_eq!(hooks::syscall_counts(), SyscallCounts::default());
+
+    // The padding fits in the address space, but not in the memory that the
+    // system can map, so `mmap` is attempted and fails.
+    hooks::reset_syscall_counts();
+    let layout = Layout::from_size_align(10, align).unwrap();
+    assert!(allocator.try_allocate_opt(layout).is_none());
+    assert_eq!(
+        hooks::syscall_counts(),
+        SyscallCounts {
+            mmap: 1,
+            munmap: 0,
+            mremap: 0,
+        }
+    );
+}
+
+/// Moves mappings without resizing them, but fails every resize.
+#[cfg(target_os = "linux")]
+unsafe fn failing_resize_mremap(
+    addr: *mut c_void,
+    old_len: usize,
+    new_len: usize,
+    flags: libc::c_int,
+) -> *mut c_void {
+    // The C library reads a new address when given `MREMAP_DONTUNMAP`,
+    // which the kernel then ignores without `MREMAP_FIXED`.
+    if old_len == new_len {
+        return libc::mremap(
+            addr,
+            old_len,
+            new_len,
+            flags,
+            std::ptr::null_mut::<c_void>(),
+        );
+    }
+
+    *libc::__errno_location() = libc::ENOMEM;
+    libc::MAP_FAILED
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn failed_grow_dontunmap_keeps_contents() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let old_layout = Layout::from_size_align(2 * page_size, 16).unwrap();
+    let mut allocation = allocator.allocate(old_layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(0xaa) };
+
+    // The pages are moved, but neither resized nor copied to a new mapping.
+    hooks::set_mremap_hook(Some(failing_resize_mremap));
+    hooks::set_mmap_hook(Some(failing_mmap));
+    let new_layout = Layout::from_size_align(4 * page_size, 16).unwrap();
+    let res =
+        unsafe { allocator.grow_dontunmap(allocation.as_non_null_ptr(), old_layout, new_layout) };
+    hooks::set_mmap_hook(None);
+    hooks::set_mremap_hook(None);
+    assert!(res.is_err());
+
+    // The contents are back at the old address.
+    let allocation_slice = unsafe { allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 0xaa));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), old_layout) };
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "tags")]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{dump_live_tagged, MMapAllocator, TaggedAllocation};
+use std::alloc::{Allocator, Layout};
+use std::ptr::NonNull;
+
+// The registry is shared by the tests running in parallel, so each of them
+// uses its own tags.
+
+fn live_tagged(tag: u32) -> Vec<TaggedAllocation> {
+    dump_live_tagged().remove(&tag).unwrap_or_default()
+}
+
+fn tagged(allocation: NonNull<[u8]>) -> TaggedAllocation {
+    TaggedAllocation {
+        ptr: allocation.as_non_null_ptr(),
+        size: allocation.len(),
+    }
+}
+
+#[test]
+fn dump_live_allocations_by_tag() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(100, 8).unwrap();
+
+    let first_allocations: Vec<NonNull<[u8]>> = (0..4)
+        .map(|_| {
+            allocator
+                .allocate_tagged(layout, 1)
+                .expect("allocate failed")
+        })
+        .collect();
+    let second_allocations: Vec<NonNull<[u8]>> = (0..2)
+        .map(|_| {
+            allocator
+                .allocate_tagged(layout, 2)
+                .expect("allocate failed")
+        })
+        .collect();
+
+    // Untagged allocations are not recorded.
+    let untagged_allocation = allocator.allocate(layout).expect("allocate failed");
+
+    let dump = dump_live_tagged();
+    let mut expected: Vec<TaggedAllocation> =
+        first_allocations.iter().copied().map(tagged).collect();
+    expected.sort_by_key(|allocation| allocation.ptr);
+    assert_eq!(dump[&1], expected);
+    assert_eq!(dump[&2].len(), 2);
+    assert!(dump
+        .values()
+        .flatten()
+        .all(|allocation| allocation.ptr != untagged_allocation.as_non_null_ptr()));
+
+    // Deallocating removes the allocations from the dump.
+    for allocation in first_allocations.iter().step_by(2) {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+    let mut expected: Vec<TaggedAllocation> = first_allocations
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .copied()
+        .map(tagged)
+        .collect();
+    expected.sort_by_key(|allocation| allocation.ptr);
+    assert_eq!(live_tagged(1), expected);
+    assert_eq!(live_tagged(2).len(), 2);
+
+    for allocation in first_allocations.iter().skip(1).step_by(2) {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+    for allocation in second_allocations {
+        unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    }
+    unsafe { allocator.deallocate(untagged_allocation.as_non_null_ptr(), layout) };
+
+    let dump = dump_live_tagged();
+    assert!(!dump.contains_key(&1));
+    assert!(!dump.contains_key(&2));
+}
+
+#[test]
+fn keep_tag_when_resizing() {
+    let allocator = MMapAllocator::new();
+    let page_size = page_size::get();
+
+    let small_layout = Layout::from_size_align(10, 8).unwrap();
+    let allocation = allocator
+        .allocate_tagged(small_layout, 3)
+        .expect("allocate failed");
+
+    let large_layout = Layout::from_size_align(4 * page_size, 8).unwrap();
+    let large_allocation = unsafe {
+        allocator
+            .grow(allocation.as_non_null_ptr(), small_layout, large_layout)
+            .expect("grow failed")
+    };
+    assert_eq!(live_tagged(3), [tagged(large_allocation)]);
+
+    let shrunk_layout = Layout::from_size_align(page_size, 8).unwrap();
+    let shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                large_allocation.as_non_null_ptr(),
+                large_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    assert_eq!(live_tagged(3), [tagged(shrunk_allocation)]);
+
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+    assert!(live_tagged(3).is_empty());
+}
+
+#[test]
+fn skip_zero_sized_allocations() {
+    let allocator = MMapAllocator::new();
+    let layout = Layout::from_size_align(0, 8).unwrap();
+
+    let allocation = allocator
+        .allocate_tagged(layout, 4)
+        .expect("allocate failed");
+    assert!(live_tagged(4).is_empty());
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
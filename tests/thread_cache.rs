@@ -0,0 +1,153 @@
+#![cfg(all(unix, feature = "syscall-hooks"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::hooks::{self, SyscallCounts};
+use mmap_allocator::{MMapAllocator, Protection};
+use std::alloc::{Allocator, Layout};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[test]
+fn reuse_cached_pages_across_threads() {
+    let allocator = MMapAllocator::with_thread_cache();
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    std::thread::scope(|scope| {
+        for thread in 0..4u8 {
+            let allocator = &allocator;
+            scope.spawn(move || {
+                hooks::reset_syscall_counts();
+                for _ in 0..100 {
+                    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+
+                    // Cached pages are zeroed before being handed out again.
+                    let allocation_slice = unsafe { allocation.as_mut() };
+                    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+                    allocation_slice.fill(thread);
+
+                    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+                }
+
+                // Every thread maps a single page, and keeps it cached.
+                assert_eq!(
+                    hooks::syscall_counts(),
+                    SyscallCounts {
+                        mmap: 1,
+                        munmap: 0,
+                        mremap: 0,
+                    }
+                );
+
+                allocator.flush_thread_cache();
+                assert_eq!(hooks::syscall_counts().munmap, 1);
+            });
+        }
+    });
+}
+
+#[test]
+fn cache_only_single_pages() {
+    let allocator = MMapAllocator::with_thread_cache();
+    let other_allocator = MMapAllocator::with_thread_cache();
+
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    hooks::reset_syscall_counts();
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    assert_eq!(hooks::syscall_counts().munmap, 1);
+
+    // The pages cached for an allocator are not used by the others.
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    hooks::reset_syscall_counts();
+    let other_allocation = other_allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(hooks::syscall_counts().mmap, 1);
+
+    unsafe { other_allocator.deallocate(other_allocation.as_non_null_ptr(), layout) };
+    allocator.flush_thread_cache();
+    other_allocator.flush_thread_cache();
+}
+
+#[test]
+fn stats_exclude_cached_pages() {
+    let allocator = MMapAllocator::try_new(MMapAllocator::builder().stats(true).thread_cache(true))
+        .expect("try_new failed");
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    hooks::reset_syscall_counts();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocator.live_allocations(), 1);
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+    assert_eq!(hooks::syscall_counts().munmap, 0);
+
+    allocator.flush_thread_cache();
+}
+
+/// Number of bytes unmapped by `counting_munmap`.
+static UNMAPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts the bytes unmapped, before unmapping them.
+unsafe fn counting_munmap(addr: *mut c_void, len: usize) -> libc::c_int {
+    UNMAPPED.fetch_add(len, Ordering::Relaxed);
+    libc::munmap(addr, len)
+}
+
+#[test]
+fn thread_exit_unmaps_cached_pages() {
+    let allocator = MMapAllocator::with_thread_cache();
+    let layout = Layout::from_size_align(10, 16).unwrap();
+
+    // Joining the thread waits for its thread-locals to be destroyed, unlike
+    // the end of a scope.
+    std::thread::spawn(move || {
+        hooks::set_munmap_hook(Some(counting_munmap));
+
+        let allocations: Vec<_> = (0..3)
+            .map(|_| allocator.allocate(layout).expect("allocate failed"))
+            .collect();
+        for allocation in allocations {
+            unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+        }
+        assert_eq!(UNMAPPED.load(Ordering::Relaxed), 0);
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(UNMAPPED.load(Ordering::Relaxed), 3 * page_size::get());
+}
+
+#[test]
+fn restore_protection_of_cached_pages() {
+    let allocator = MMapAllocator::with_thread_cache();
+    let page_size = page_size::get();
+    let layout = Layout::from_size_align(page_size, 1).unwrap();
+
+    let data = vec![7; page_size];
+    let allocation = allocator
+        .allocate_readonly_from(&data)
+        .expect("allocate failed");
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    // The read-only page is writable again once taken from the cache.
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    let allocation_slice = unsafe { allocation.as_mut() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+    allocation_slice.fill(42);
+
+    unsafe {
+        allocator
+            .protect(allocation.as_non_null_ptr(), layout, Protection::READ)
+            .expect("protect failed");
+        allocator.deallocate(allocation.as_non_null_ptr(), layout);
+    }
+
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(42) };
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    allocator.flush_thread_cache();
+}
@@ -0,0 +1,124 @@
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{BumpArena, HybridAllocator, MMapAllocator, PagePool};
+use std::alloc::{Allocator, Layout};
+use std::sync::Arc;
+use std::thread;
+
+fn _assert_send_sync<T: Send + Sync>() {}
+fn _assert_send<T: Send>() {}
+
+#[test]
+fn allocators_are_send_sync() {
+    _assert_send_sync::<MMapAllocator>();
+    _assert_send_sync::<&MMapAllocator>();
+    _assert_send_sync::<HybridAllocator>();
+    _assert_send_sync::<PagePool>();
+    #[cfg(unix)]
+    _assert_send_sync::<mmap_allocator::Reservation>();
+    #[cfg(unix)]
+    _assert_send_sync::<mmap_allocator::Stack>();
+    _assert_send::<BumpArena>();
+}
+
+#[test]
+fn share_allocator_across_threads() {
+    let allocator = Arc::new(MMapAllocator::with_stats());
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let allocator = Arc::clone(&allocator);
+            thread::spawn(move || {
+                let layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+                for _ in 0..16 {
+                    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+                    unsafe { allocation.as_mut().fill(42) };
+                    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(allocator.live_allocations(), 0);
+    assert_eq!(allocator.bytes_mapped(), 0);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn share_file_backed_allocator_across_threads() {
+    let fd =
+        unsafe { libc::memfd_create(b"mmap-allocator-test\0".as_ptr().cast(), libc::MFD_CLOEXEC) };
+    assert_ne!(fd, -1, "memfd_create failed");
+    let res = unsafe { libc::ftruncate(fd, (64 * page_size::get()) as libc::off_t) };
+    assert_ne!(res, -1, "ftruncate failed");
+
+    let allocator = Arc::new(MMapAllocator::file_backed(fd, 0));
+
+    // Every thread should get its own region of the file.
+    let threads: Vec<_> = (0..4u8)
+        .map(|thread| {
+            let allocator = Arc::clone(&allocator);
+            thread::spawn(move || {
+                let layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+                (0..16)
+                    .map(|_| {
+                        let mut allocation = allocator.allocate(layout).expect("allocate failed");
+                        unsafe { allocation.as_mut().fill(thread) };
+                        allocation.as_mut_ptr() as usize
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let mut regions = Vec::new();
+    for (thread, handle) in threads.into_iter().enumerate() {
+        for ptr in handle.join().unwrap() {
+            let allocation =
+                unsafe { std::slice::from_raw_parts(ptr as *const u8, page_size::get()) };
+            assert!(allocation.iter().all(|&byte| byte == thread as u8));
+            regions.push(ptr);
+        }
+    }
+
+    let layout = Layout::from_size_align(page_size::get(), 1).unwrap();
+    for ptr in regions {
+        unsafe { allocator.deallocate(std::ptr::NonNull::new(ptr as *mut u8).unwrap(), layout) };
+    }
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+fn own_allocator_through_arc() {
+    let allocator = Arc::new(MMapAllocator::with_stats());
+
+    let mut boxes: Vec<Box<[u8; 64], Arc<MMapAllocator>>> = (0..8u8)
+        .map(|i| Box::new_in([i; 64], Arc::clone(&allocator)))
+        .collect();
+    assert_eq!(allocator.live_allocations(), 8);
+    assert_eq!(Arc::strong_count(&allocator), 9);
+
+    // The boxes are dropped in an arbitrary order, and the last one outlives
+    // the original handle.
+    for i in [5, 0, 3, 3, 1] {
+        let boxed = boxes.remove(i);
+        assert!(boxed.iter().all(|&byte| byte == boxed[0]));
+    }
+    assert_eq!(allocator.live_allocations(), 3);
+
+    let last_box = boxes.pop().unwrap();
+    drop(boxes);
+    let weak_allocator = Arc::downgrade(&allocator);
+    drop(allocator);
+    assert!(weak_allocator.upgrade().is_some());
+    assert!(last_box.iter().all(|&byte| byte == last_box[0]));
+
+    drop(last_box);
+    assert!(weak_allocator.upgrade().is_none());
+}
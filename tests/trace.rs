@@ -0,0 +1,118 @@
+#![cfg(all(feature = "trace", target_os = "linux"))]
+#![feature(allocator_api)]
+#![feature(internal_output_capture)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::MMapAllocator;
+use std::alloc::{Allocator, Layout};
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::io::FromRawFd;
+
+/// Runs `f` in a forked child process with `MMAP_ALLOC_TRACE` set, returning
+/// what it printed to `stderr`.
+fn trace_in_child(f: impl FnOnce()) -> String {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0, "pipe failed");
+    let [read_fd, write_fd] = fds;
+
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1, "fork failed");
+
+    if pid == 0 {
+        // Capture the traces, which the test harness would otherwise keep to
+        // itself. The environment is read by the first operation, so nothing
+        // was traced by the parent.
+        std::io::set_output_capture(None);
+        unsafe { libc::dup2(write_fd, libc::STDERR_FILENO) };
+        std::env::set_var("MMAP_ALLOC_TRACE", "1");
+        f();
+        unsafe { libc::_exit(0) };
+    }
+
+    unsafe { libc::close(write_fd) };
+    let mut output = String::new();
+    unsafe { File::from_raw_fd(read_fd) }
+        .read_to_string(&mut output)
+        .expect("cannot read the traces");
+
+    let mut status = 0;
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "waitpid failed");
+    assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+
+    output
+}
+
+#[test]
+fn trace_every_operation() {
+    let page_size = page_size::get();
+
+    let output = trace_in_child(|| {
+        let allocator = MMapAllocator::new();
+
+        let layout = Layout::from_size_align(10, 16).unwrap();
+        let allocation = allocator.allocate(layout).expect("allocate failed");
+
+        let grown_layout = Layout::from_size_align(2 * page_size, 16).unwrap();
+        let grown = unsafe { allocator.grow(allocation.as_non_null_ptr(), layout, grown_layout) }
+            .expect("grow failed");
+
+        let shrunk = unsafe { allocator.shrink(grown.as_non_null_ptr(), grown_layout, layout) }
+            .expect("shrink failed");
+
+        unsafe { allocator.deallocate(shrunk.as_non_null_ptr(), layout) };
+    });
+
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 4, "unexpected traces: {}", output);
+
+    // Returns the address following `prefix` in `line`.
+    let ptr_after = |line: &str, prefix: &str| -> String {
+        let start = line.find(prefix).expect("missing pointer") + prefix.len();
+        line[start..].split([',', ')']).next().unwrap().to_owned()
+    };
+
+    assert!(lines[0].starts_with("mmap-allocator: allocate(size=10, align=16) -> ptr=0x"));
+    assert!(lines[0].ends_with(&format!(", len={}", page_size)));
+    let allocated = ptr_after(lines[0], "-> ptr=");
+
+    assert!(lines[1].starts_with(&format!(
+        "mmap-allocator: grow(ptr={}, old_size=10, new_size={}) -> ptr=",
+        allocated,
+        2 * page_size
+    )));
+    assert!(lines[1].ends_with(&format!(", len={}", 2 * page_size)));
+    let grown = ptr_after(lines[1], "-> ptr=");
+
+    assert!(lines[2].starts_with(&format!(
+        "mmap-allocator: shrink(ptr={}, old_size={}, new_size=10) -> ptr=",
+        grown,
+        2 * page_size
+    )));
+    assert!(lines[2].ends_with(&format!(", len={}", page_size)));
+    let shrunk = ptr_after(lines[2], "-> ptr=");
+
+    assert_eq!(
+        lines[3],
+        format!("mmap-allocator: deallocate(ptr={}, size=10)", shrunk)
+    );
+}
+
+#[test]
+fn trace_failures() {
+    let output = trace_in_child(|| {
+        let allocator = MMapAllocator::new();
+
+        let layout = Layout::from_size_align(isize::MAX as usize - 1, 1).unwrap();
+        assert!(allocator.allocate(layout).is_err());
+    });
+
+    assert_eq!(
+        output.trim_end(),
+        format!(
+            "mmap-allocator: allocate(size={}, align=1) -> failed",
+            isize::MAX as usize - 1
+        )
+    );
+}
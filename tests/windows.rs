@@ -0,0 +1,117 @@
+#![cfg(windows)]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{MMapAllocator, Protection};
+use std::alloc::{Allocator, Layout};
+
+#[test]
+fn allocate_deallocate() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(page_size::get() + 10, 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    let allocation_slice = unsafe { allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), 2 * page_size::get());
+
+    *allocation_slice.first_mut().unwrap() = 42;
+    *allocation_slice.last_mut().unwrap() = 42;
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn allocate_over_aligned_fails() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 4 * page_size::get()).unwrap();
+    assert!(allocator.allocate(layout).is_err());
+}
+
+#[test]
+fn shrink_decommits_tail_pages() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(4 * page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    let allocation_slice = unsafe { initial_allocation.as_mut() };
+    *allocation_slice.first_mut().unwrap() = 42;
+
+    let shrunk_layout = Layout::from_size_align(10, 16).unwrap();
+    let mut shrunk_allocation = unsafe {
+        allocator
+            .shrink(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                shrunk_layout,
+            )
+            .expect("shrink failed")
+    };
+    let allocation_slice = unsafe { shrunk_allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), page_size::get());
+    assert_eq!(*allocation_slice.first().unwrap(), 42);
+
+    // Releasing the region should also release the decommitted pages.
+    unsafe { allocator.deallocate(shrunk_allocation.as_non_null_ptr(), shrunk_layout) };
+}
+
+#[test]
+fn grow_moves_contents() {
+    let allocator = MMapAllocator::new();
+
+    let initial_layout = Layout::from_size_align(10, 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    let allocation_slice = unsafe { initial_allocation.as_mut() };
+    *allocation_slice.first_mut().unwrap() = 42;
+
+    let grown_layout = Layout::from_size_align(3 * page_size::get(), 16).unwrap();
+    let mut grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    let allocation_slice = unsafe { grown_allocation.as_mut() };
+    assert_eq!(allocation_slice.len(), 3 * page_size::get());
+    assert_eq!(*allocation_slice.first().unwrap(), 42);
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+fn allocate_read_only() {
+    let allocator = MMapAllocator::with_protection(Protection::READ);
+
+    let layout = Layout::from_size_align(10, 16).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    let allocation_slice = unsafe { allocation.as_ref() };
+    assert!(allocation_slice.iter().all(|&byte| byte == 0));
+
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+}
+
+#[test]
+fn max_supported_align_is_page_size() {
+    let allocator = MMapAllocator::new();
+    let max_align = allocator.max_supported_align();
+    assert_eq!(max_align, page_size::get());
+
+    let layout = Layout::from_size_align(10, max_align).unwrap();
+    let allocation = allocator.allocate(layout).expect("allocate failed");
+    assert_eq!(allocation.as_mut_ptr() as usize % max_align, 0);
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+
+    let layout = Layout::from_size_align(10, 2 * max_align).unwrap();
+    assert!(allocator.allocate(layout).is_err());
+}
+
+#[test]
+fn try_allocate_opt_over_aligned_layout() {
+    let allocator = MMapAllocator::new();
+
+    let layout = Layout::from_size_align(10, 2 * page_size::get()).unwrap();
+    assert!(allocator.try_allocate_opt(layout).is_none());
+}
@@ -0,0 +1,166 @@
+#![cfg(all(unix, feature = "syscall-hooks"))]
+#![feature(allocator_api)]
+#![feature(slice_ptr_get)]
+
+use mmap_allocator::{hooks, MMapAllocator};
+use std::alloc::{Allocator, Layout};
+use std::cell::Cell;
+use std::ffi::c_void;
+
+std::thread_local! {
+    /// Whether every region passed to `munmap` was zeroed.
+    static UNMAPPED_ZEROED: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Records whether the region is zeroed before unmapping it.
+unsafe fn check_zeroed_munmap(addr: *mut c_void, len: usize) -> libc::c_int {
+    let region = std::slice::from_raw_parts(addr.cast::<u8>(), len);
+    let zeroed = region.iter().all(|&byte| byte == 0);
+    UNMAPPED_ZEROED.with(|unmapped_zeroed| {
+        unmapped_zeroed.set(Some(unmapped_zeroed.get().unwrap_or(true) && zeroed))
+    });
+
+    libc::munmap(addr, len)
+}
+
+/// Fills the allocation, then frees it and returns whether it was zeroed
+/// before being unmapped.
+fn fill_and_free(allocator: &MMapAllocator) -> bool {
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(0xaa) };
+
+    UNMAPPED_ZEROED.with(|unmapped_zeroed| unmapped_zeroed.set(None));
+    hooks::set_munmap_hook(Some(check_zeroed_munmap));
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    hooks::set_munmap_hook(None);
+
+    UNMAPPED_ZEROED
+        .with(Cell::get)
+        .expect("munmap was not called")
+}
+
+#[test]
+fn deallocate_zeroes_memory() {
+    assert!(fill_and_free(&MMapAllocator::with_zero_on_free(true)));
+}
+
+#[test]
+fn deallocate_keeps_memory_by_default() {
+    assert!(!fill_and_free(&MMapAllocator::new()));
+}
+
+#[test]
+fn grow_zeroes_old_memory() {
+    let allocator = MMapAllocator::with_zero_on_free(true);
+
+    // Over-aligning the new layout forces `grow` to copy the contents.
+    let initial_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut().fill(0xaa) };
+
+    let grown_layout = Layout::from_size_align(2 * page_size::get(), 4 * page_size::get()).unwrap();
+
+    UNMAPPED_ZEROED.with(|unmapped_zeroed| unmapped_zeroed.set(None));
+    hooks::set_munmap_hook(Some(check_zeroed_munmap));
+    let mut grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    hooks::set_munmap_hook(None);
+
+    assert_eq!(UNMAPPED_ZEROED.with(Cell::get), Some(true));
+
+    // The contents should have been copied before zeroing.
+    assert_eq!(unsafe { grown_allocation.as_mut()[0] }, 0xaa);
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+std::thread_local! {
+    /// Whether every region passed to `munmap` was filled with `POISON`.
+    static UNMAPPED_POISONED: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+const POISON: u8 = 0xde;
+
+/// Records whether the region is poisoned before unmapping it.
+unsafe fn check_poisoned_munmap(addr: *mut c_void, len: usize) -> libc::c_int {
+    let region = std::slice::from_raw_parts(addr.cast::<u8>(), len);
+    let poisoned = region.iter().all(|&byte| byte == POISON);
+    UNMAPPED_POISONED.with(|unmapped_poisoned| {
+        unmapped_poisoned.set(Some(unmapped_poisoned.get().unwrap_or(true) && poisoned))
+    });
+
+    libc::munmap(addr, len)
+}
+
+#[test]
+fn deallocate_poisons_memory() {
+    let allocator = MMapAllocator::with_poison(POISON);
+
+    let layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+    let mut allocation = allocator.allocate(layout).expect("allocate failed");
+    unsafe { allocation.as_mut().fill(0xaa) };
+
+    UNMAPPED_POISONED.with(|unmapped_poisoned| unmapped_poisoned.set(None));
+    hooks::set_munmap_hook(Some(check_poisoned_munmap));
+    unsafe { allocator.deallocate(allocation.as_non_null_ptr(), layout) };
+    hooks::set_munmap_hook(None);
+
+    assert_eq!(UNMAPPED_POISONED.with(Cell::get), Some(true));
+}
+
+#[test]
+fn grow_poisons_old_memory() {
+    // Shared mappings cannot be extended by `mremap`, so `grow` copies the
+    // contents to a new mapping. Unlike over-aligning, this does not unmap
+    // any padding.
+    let allocator = MMapAllocator::builder()
+        .shared()
+        .poison(POISON)
+        .build()
+        .expect("build failed");
+
+    let initial_layout = Layout::from_size_align(page_size::get(), 16).unwrap();
+    let mut initial_allocation = allocator.allocate(initial_layout).expect("allocate failed");
+    unsafe { initial_allocation.as_mut().fill(0xaa) };
+
+    let grown_layout = Layout::from_size_align(2 * page_size::get(), 16).unwrap();
+
+    UNMAPPED_POISONED.with(|unmapped_poisoned| unmapped_poisoned.set(None));
+    hooks::set_munmap_hook(Some(check_poisoned_munmap));
+    let grown_allocation = unsafe {
+        allocator
+            .grow(
+                initial_allocation.as_non_null_ptr(),
+                initial_layout,
+                grown_layout,
+            )
+            .expect("grow failed")
+    };
+    hooks::set_munmap_hook(None);
+
+    assert_eq!(UNMAPPED_POISONED.with(Cell::get), Some(true));
+    assert!(unsafe { grown_allocation.as_ref() }[..page_size::get()]
+        .iter()
+        .all(|&byte| byte == 0xaa));
+
+    unsafe { allocator.deallocate(grown_allocation.as_non_null_ptr(), grown_layout) };
+}
+
+#[test]
+fn zero_on_free_overrides_poison() {
+    let allocator = MMapAllocator::builder()
+        .poison(POISON)
+        .zero_on_free(true)
+        .build()
+        .expect("build failed");
+
+    assert!(fill_and_free(&allocator));
+}